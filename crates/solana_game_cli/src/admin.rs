@@ -0,0 +1,170 @@
+// Commands that send transactions. Each returns the signatures as JSON rows.
+
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+use solana_game_client::{
+    admin,
+    decode::{self, fetch_required},
+    pda,
+    solana_game::{
+        instruction, ConfigV3Update, LevelPool, StatsShard, MAX_LEVEL, STATS_SHARD_COUNT,
+    },
+    AccountSource,
+};
+use solana_keypair::Keypair;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+
+pub fn send(rpc: &RpcClient, signer: &Keypair, ixs: &[Instruction]) -> Result<String> {
+    let blockhash = rpc.get_latest_blockhash().context("get_latest_blockhash")?;
+    let tx = Transaction::new_signed_with_payer(ixs, Some(&signer.pubkey()), &[signer], blockhash);
+    let sig = rpc.send_and_confirm_transaction(&tx)?;
+    Ok(sig.to_string())
+}
+
+fn sent(instruction: &str, target: &Pubkey, signature: String) -> Value {
+    json!({ "instruction": instruction, "target": target.to_string(), "signature": signature, "skipped": null })
+}
+
+fn skipped(instruction: &str, target: &Pubkey, reason: &str) -> Value {
+    json!({ "instruction": instruction, "target": target.to_string(), "signature": null, "skipped": reason })
+}
+
+pub fn init_config(
+    rpc: &RpcClient,
+    signer: &Keypair,
+    args: instruction::InitializeConfigV3,
+) -> Result<Value> {
+    let (config, _) = pda::config();
+    if rpc.exists(&config)? {
+        bail!("ConfigV3 {config} already exists (use `config update`)");
+    }
+    let sig = send(
+        rpc,
+        signer,
+        &[admin::initialize_config_v3(&signer.pubkey(), args)],
+    )?;
+    Ok(json!([sent("initialize_config_v3", &config, sig)]))
+}
+
+pub fn migrate_config(rpc: &RpcClient, signer: &Keypair) -> Result<Value> {
+    let sig = send(rpc, signer, &[admin::migrate_config_v3(&signer.pubkey())])?;
+    Ok(json!([sent("migrate_config_v3", &pda::config().0, sig)]))
+}
+
+pub fn update_config(rpc: &RpcClient, signer: &Keypair, args: ConfigV3Update) -> Result<Value> {
+    let sig = send(
+        rpc,
+        signer,
+        &[admin::update_config_v3(&signer.pubkey(), args)],
+    )?;
+    Ok(json!([sent("update_config_v3", &pda::config().0, sig)]))
+}
+
+// Creates the stats shards that don't exist yet
+pub fn init_stats_shards(rpc: &RpcClient, signer: &Keypair) -> Result<Value> {
+    let mut rows = Vec::new();
+    for shard in 0..STATS_SHARD_COUNT {
+        let (key, _) = pda::stats_shard(shard);
+        if rpc.exists(&key)? {
+            rows.push(skipped("init_stats_shard", &key, "exists"));
+            continue;
+        }
+        let sig = send(
+            rpc,
+            signer,
+            &[admin::init_stats_shard(&signer.pubkey(), shard)],
+        )?;
+        rows.push(sent("init_stats_shard", &key, sig));
+    }
+    Ok(Value::Array(rows))
+}
+
+pub fn init_treasury_vault(rpc: &RpcClient, signer: &Keypair) -> Result<Value> {
+    let sig = send(rpc, signer, &[admin::init_treasury_vault(&signer.pubkey())])?;
+    Ok(json!([sent(
+        "init_treasury_vault",
+        &pda::treasury_vault().0,
+        sig
+    )]))
+}
+
+pub fn withdraw_treasury(
+    rpc: &RpcClient,
+    signer: &Keypair,
+    amount: u64,
+    destination: &Pubkey,
+) -> Result<Value> {
+    let sig = send(
+        rpc,
+        signer,
+        &[admin::withdraw_treasury(
+            &signer.pubkey(),
+            amount,
+            destination,
+        )],
+    )?;
+    Ok(json!([sent("withdraw_treasury", destination, sig)]))
+}
+
+pub fn set_treasury_split(
+    rpc: &RpcClient,
+    signer: &Keypair,
+    split: Vec<(Pubkey, u16)>,
+) -> Result<Value> {
+    let (beneficiaries, bps) = split.into_iter().unzip();
+    let ix = admin::set_treasury_split(&signer.pubkey(), beneficiaries, bps);
+    let sig = send(rpc, signer, &[ix])?;
+    Ok(json!([sent(
+        "set_treasury_split",
+        &pda::treasury_split().0,
+        sig
+    )]))
+}
+
+pub fn distribute_treasury(rpc: &RpcClient, signer: &Keypair) -> Result<Value> {
+    let (key, _) = pda::treasury_split();
+    let split = fetch_required(rpc, &key, "TreasurySplit", decode::treasury_split)?;
+    let sig = send(rpc, signer, &[admin::distribute_treasury(&split)])?;
+    Ok(json!([sent("distribute_treasury", &key, sig)]))
+}
+
+// Same as scripts/upgrade_event_seq.js: grows pre-event_seq LevelPools and StatsShards
+pub fn upgrade_accounts(rpc: &RpcClient, signer: &Keypair) -> Result<Value> {
+    let needs_upgrade = |key: &Pubkey, size: usize| -> Result<bool> {
+        Ok(rpc
+            .account_data(key)?
+            .is_some_and(|d| !d.is_empty() && d.len() < 8 + size))
+    };
+
+    let mut rows = Vec::new();
+    for level in 1..=MAX_LEVEL {
+        let (key, _) = pda::level_pool(level);
+        if !needs_upgrade(&key, LevelPool::SIZE)? {
+            rows.push(skipped("upgrade_level_pool", &key, "missing or current"));
+            continue;
+        }
+        let sig = send(
+            rpc,
+            signer,
+            &[admin::upgrade_level_pool(&signer.pubkey(), level)],
+        )?;
+        rows.push(sent("upgrade_level_pool", &key, sig));
+    }
+    for shard in 0..STATS_SHARD_COUNT {
+        let (key, _) = pda::stats_shard(shard);
+        if !needs_upgrade(&key, StatsShard::SIZE)? {
+            rows.push(skipped("upgrade_stats_shard", &key, "missing or current"));
+            continue;
+        }
+        let sig = send(
+            rpc,
+            signer,
+            &[admin::upgrade_stats_shard(&signer.pubkey(), shard)],
+        )?;
+        rows.push(sent("upgrade_stats_shard", &key, sig));
+    }
+    Ok(Value::Array(rows))
+}
@@ -0,0 +1,188 @@
+// Read-only commands: config, stats, queue, player.
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{bail, Result};
+use serde_json::{json, Value};
+use solana_game_client::{
+    decode::{self, fetch, fetch_required},
+    ix, pda,
+    solana_game::{ConfigV3, LevelState, MAX_LEVEL, STATS_SHARD_COUNT},
+    AccountSource,
+};
+
+// Page walk limit: a next_page cycle would otherwise never end
+const MAX_QUEUE_PAGES: usize = 100_000;
+
+fn key(k: &Pubkey) -> Value {
+    Value::String(k.to_string())
+}
+
+fn opt_key(k: &Option<Pubkey>) -> Value {
+    k.as_ref().map_or(Value::Null, key)
+}
+
+fn config_json(cfg: &ConfigV3) -> Value {
+    json!({
+        "address": key(&pda::config().0),
+        "admin": key(&cfg.admin),
+        "treasury": key(&cfg.treasury),
+        "version": format!("{}.{}", cfg.version, cfg.version_minor),
+        "perc": [cfg.perc_admin, cfg.perc_ref1, cfg.perc_ref2, cfg.perc_ref3, cfg.perc_treasury],
+        "base_price_lamports": cfg.base_price_lamports,
+        "price_ratio": cfg.price_ratio,
+        "min_entry_delay": cfg.min_entry_delay,
+        "auto_recycle": cfg.auto_recycle,
+        "slots_to_recycle": cfg.slots_to_recycle,
+        "max_levels": cfg.max_levels,
+        "tx_guard_close_delay": cfg.tx_guard_close_delay,
+        "legacy_tx_guard": cfg.legacy_tx_guard,
+        "use_treasury_vault": cfg.use_treasury_vault,
+        "treasury_epoch_limit": cfg.treasury_epoch_limit,
+        "first_activation_delay_exempt": cfg.first_activation_delay_exempt,
+        "require_sequential_levels": cfg.require_sequential_levels,
+        "payment_mint": key(&cfg.payment_mint),
+        "token_level_prices": cfg.token_level_prices,
+        "usd_price_feed": key(&cfg.usd_price_feed),
+        "usd_level_prices_cents": cfg.usd_level_prices_cents,
+        "usd_feed_max_age": cfg.usd_feed_max_age,
+        "usd_feed_max_conf_bps": cfg.usd_feed_max_conf_bps,
+        "usd_feed_id": cfg.usd_feed_id.iter().map(|b| format!("{b:02x}")).collect::<String>(),
+    })
+}
+
+pub fn config(source: &impl AccountSource) -> Result<Value> {
+    Ok(config_json(&ix::fetch_config(source)?))
+}
+
+// GlobalStats plus every shard; `total_players` sums them like get_total_players
+pub fn stats(source: &impl AccountSource) -> Result<Value> {
+    let (global_key, _) = pda::global_stats();
+    let global = fetch_required(source, &global_key, "GlobalStats", decode::global_stats)?;
+
+    let mut total = global.total_players;
+    let mut shards = Vec::new();
+    for shard in 0..STATS_SHARD_COUNT {
+        let (shard_key, _) = pda::stats_shard(shard);
+        let row = match fetch(source, &shard_key, "StatsShard", decode::stats_shard)? {
+            Some(s) => {
+                total = total.saturating_add(s.total_players);
+                json!({
+                    "shard": shard,
+                    "address": key(&shard_key),
+                    "total_players": s.total_players,
+                    "last_player": key(&s.last_player),
+                    "event_seq": s.event_seq,
+                })
+            }
+            None => json!({
+                "shard": shard,
+                "address": key(&shard_key),
+                "total_players": null,
+                "last_player": null,
+                "event_seq": null,
+            }),
+        };
+        shards.push(row);
+    }
+
+    Ok(json!({
+        "global_stats": key(&global_key),
+        "legacy_total_players": global.total_players,
+        "legacy_last_player": key(&global.last_player),
+        "total_players": total,
+        "shards": shards,
+    }))
+}
+
+// LevelPool summary and every queued Player PDA, head first
+pub fn queue(source: &impl AccountSource, level: u8) -> Result<Value> {
+    if !(1..=MAX_LEVEL).contains(&level) {
+        bail!("level must be 1..={MAX_LEVEL}");
+    }
+    let (pool_key, _) = pda::level_pool(level);
+    let pool = fetch_required(source, &pool_key, "LevelPool", decode::level_pool)?;
+
+    let mut entries = Vec::new();
+    let mut next = pool.head_page;
+    let mut pages = 0usize;
+    while let Some(page_key) = next {
+        pages += 1;
+        if pages > MAX_QUEUE_PAGES {
+            bail!(
+                "queue of level {level} has more than {MAX_QUEUE_PAGES} pages (next_page cycle?)"
+            );
+        }
+        let page = fetch_required(source, &page_key, "QueuePage", decode::queue_page)?;
+        for (position, player) in page.players.iter().enumerate() {
+            entries.push(json!({
+                "n": entries.len() + 1,
+                "page_index": page.page_index,
+                "position": position,
+                "player": key(player),
+                "page": key(&page_key),
+            }));
+        }
+        next = page.next_page;
+    }
+
+    Ok(json!({
+        "level": level,
+        "level_pool": key(&pool_key),
+        "head_page": opt_key(&pool.head_page),
+        "tail_page": opt_key(&pool.tail_page),
+        "total_enqueued": pool.total_enqueued,
+        "total_dequeued": pool.total_dequeued,
+        "event_seq": pool.event_seq,
+        "pages": pages,
+        "queued": entries.len(),
+        "entries": entries,
+    }))
+}
+
+fn level_json(level: u8, address: &Pubkey, ls: &LevelState, legacy: bool) -> Value {
+    json!({
+        "level": level,
+        "address": key(address),
+        "authority": key(&ls.authority),
+        "activated_at": ls.activated_at,
+        "cycles": ls.cycles,
+        "slots_filled": ls.slots_filled,
+        "queue_page": opt_key(&ls.queue_page),
+        "queue_pos": ls.queue_pos,
+        "legacy_layout": legacy,
+    })
+}
+
+// `who` is a wallet (resolved through AuthorityLink) or a Player PDA
+pub fn player(source: &impl AccountSource, who: &Pubkey) -> Result<Value> {
+    let player_key = match fetch(source, who, "Player", decode::player) {
+        Ok(Some(_)) => *who,
+        _ => ix::resolve_player(source, who)?,
+    };
+    let player = fetch_required(source, &player_key, "Player", decode::player)?;
+
+    let mut levels = Vec::new();
+    for level in 1..=MAX_LEVEL {
+        let (ls_key, _) = pda::level_state(&player_key, level);
+        let Some(data) = source.account_data(&ls_key)? else {
+            continue;
+        };
+        if let Some(ls) = decode::level_state_current(&data) {
+            levels.push(level_json(level, &ls_key, &ls, false));
+        } else if let Some(ls) = decode::level_state(&data) {
+            levels.push(level_json(level, &ls_key, &ls, true));
+        }
+    }
+
+    Ok(json!({
+        "player": key(&player_key),
+        "authority": key(&player.authority),
+        "created_at": player.created_at,
+        "games_played": player.games_played,
+        "next_nonce": player.next_nonce,
+        "upline1": key(&player.upline1),
+        "upline2": key(&player.upline2),
+        "upline3": key(&player.upline3),
+        "levels": levels,
+    }))
+}
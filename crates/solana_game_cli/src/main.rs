@@ -0,0 +1,271 @@
+// ============================================================
+// solana-game-cli — admin and inspection commands for solana_game
+//
+// Replaces the one-off ops scripts (init_config_v3.js, check-balance.js, ...).
+// Read commands need only --url; transaction commands sign with --keypair.
+// ============================================================
+
+mod admin;
+mod inspect;
+mod output;
+
+use std::path::PathBuf;
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::{Args, Parser, Subcommand};
+use solana_commitment_config::CommitmentConfig;
+use solana_game_client::solana_game::{instruction, ConfigV3Update, MAX_TREASURY_BENEFICIARIES};
+use solana_keypair::{read_keypair_file, Keypair};
+use solana_rpc_client::rpc_client::RpcClient;
+
+use output::Format;
+
+#[derive(Parser)]
+#[command(
+    name = "solana-game-cli",
+    version,
+    about = "Admin and inspection CLI for solana_game"
+)]
+struct Cli {
+    /// RPC endpoint (local validator by default)
+    #[arg(
+        long,
+        short = 'u',
+        env = "RPC_URL",
+        default_value = "http://127.0.0.1:8899",
+        global = true
+    )]
+    url: String,
+
+    /// Signer for transaction commands [default: ~/.config/solana/id.json]
+    #[arg(long, short = 'k', env = "KEYPAIR", global = true)]
+    keypair: Option<PathBuf>,
+
+    #[arg(long, short = 'o', value_enum, default_value_t = Format::Table, global = true)]
+    output: Format,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// ConfigV3: show, init, migrate, update
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// GlobalStats and the per-shard registration counters
+    Stats,
+    /// Dump a level's queue (LevelPool.head_page -> next_page)
+    Queue { level: u8 },
+    /// A player's uplines and level states (wallet or Player PDA)
+    Player { who: Pubkey },
+    /// Admin and maintenance instructions
+    #[command(subcommand)]
+    Admin(AdminCommand),
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    Show,
+    /// initialize_config_v3 (one time; also creates GlobalStats)
+    Init(InitConfigArgs),
+    /// migrate_config_v3: grow an older ConfigV3 to the current layout
+    Migrate,
+    /// update_config_v3: only the given fields change
+    Update(UpdateConfigArgs),
+}
+
+#[derive(Args)]
+struct InitConfigArgs {
+    #[arg(long)]
+    admin: Pubkey,
+    #[arg(long)]
+    treasury: Pubkey,
+    #[arg(long, default_value_t = 50_000_000)]
+    base_price_lamports: u64,
+    #[arg(long, default_value_t = 2)]
+    price_ratio: u8,
+    #[arg(long, default_value_t = 0)]
+    min_entry_delay: u32,
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    auto_recycle: bool,
+    #[arg(long, default_value_t = 3)]
+    slots_to_recycle: u8,
+    #[arg(long, default_value_t = 16)]
+    max_levels: u8,
+}
+
+#[derive(Args)]
+struct UpdateConfigArgs {
+    #[arg(long)]
+    tx_guard_close_delay: Option<u32>,
+    #[arg(long)]
+    legacy_tx_guard: Option<bool>,
+    #[arg(long)]
+    use_treasury_vault: Option<bool>,
+    #[arg(long)]
+    treasury_epoch_limit: Option<u64>,
+    #[arg(long)]
+    first_activation_delay_exempt: Option<bool>,
+    #[arg(long)]
+    require_sequential_levels: Option<bool>,
+    #[arg(long)]
+    payment_mint: Option<Pubkey>,
+    /// 16 comma-separated prices in payment_mint units
+    #[arg(long, value_delimiter = ',')]
+    token_level_prices: Option<Vec<u64>>,
+    #[arg(long)]
+    usd_price_feed: Option<Pubkey>,
+    /// 16 comma-separated prices in US cents
+    #[arg(long, value_delimiter = ',')]
+    usd_level_prices_cents: Option<Vec<u32>>,
+    #[arg(long)]
+    usd_feed_max_age: Option<u32>,
+    #[arg(long)]
+    usd_feed_max_conf_bps: Option<u16>,
+    /// Pyth feed id, 64 hex chars (0x prefix optional)
+    #[arg(long, value_parser = parse_feed_id)]
+    usd_feed_id: Option<[u8; 32]>,
+}
+
+#[derive(Subcommand)]
+enum AdminCommand {
+    /// init_stats_shard for every shard that doesn't exist yet
+    InitStatsShards,
+    InitTreasuryVault,
+    WithdrawTreasury {
+        #[arg(long)]
+        amount: u64,
+        #[arg(long)]
+        destination: Pubkey,
+    },
+    /// Replace the beneficiaries: --beneficiary WALLET:BPS (repeat, bps sum to 10000)
+    SetTreasurySplit {
+        #[arg(long = "beneficiary", value_parser = parse_split, required = true)]
+        split: Vec<(Pubkey, u16)>,
+    },
+    /// Permissionless: pay the vault balance out per the split
+    DistributeTreasury,
+    /// Permissionless: grow pre-event_seq LevelPools and StatsShards
+    UpgradeAccounts,
+}
+
+fn parse_split(s: &str) -> Result<(Pubkey, u16)> {
+    let (wallet, bps) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected WALLET:BPS"))?;
+    Ok((wallet.parse()?, bps.parse()?))
+}
+
+fn parse_feed_id(s: &str) -> Result<[u8; 32]> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    if s.len() != 64 || !s.is_ascii() {
+        bail!("expected 32 bytes as 64 hex chars");
+    }
+    let mut id = [0u8; 32];
+    for (i, b) in id.iter_mut().enumerate() {
+        *b = u8::from_str_radix(&s[2 * i..2 * i + 2], 16)?;
+    }
+    Ok(id)
+}
+
+fn prices<T, const N: usize>(name: &str, v: Option<Vec<T>>) -> Result<Option<[T; N]>> {
+    v.map(|v| {
+        <[T; N]>::try_from(v).map_err(|v| anyhow!("--{name}: expected {N} values, got {}", v.len()))
+    })
+    .transpose()
+}
+
+impl InitConfigArgs {
+    fn into_instruction(self) -> instruction::InitializeConfigV3 {
+        // perc_* are ignored on-chain (fixed 60/13/8/5/14); same values as init_config_v3.js
+        instruction::InitializeConfigV3 {
+            admin: self.admin,
+            treasury: self.treasury,
+            perc_admin: 60,
+            perc_ref1: 13,
+            perc_ref2: 8,
+            perc_ref3: 5,
+            perc_treasury: 14,
+            base_price_lamports: self.base_price_lamports,
+            price_ratio: self.price_ratio,
+            min_entry_delay: self.min_entry_delay,
+            auto_recycle: self.auto_recycle,
+            slots_to_recycle: self.slots_to_recycle,
+            max_levels: self.max_levels,
+        }
+    }
+}
+
+impl UpdateConfigArgs {
+    fn into_update(self) -> Result<ConfigV3Update> {
+        Ok(ConfigV3Update {
+            tx_guard_close_delay: self.tx_guard_close_delay,
+            legacy_tx_guard: self.legacy_tx_guard,
+            use_treasury_vault: self.use_treasury_vault,
+            treasury_epoch_limit: self.treasury_epoch_limit,
+            first_activation_delay_exempt: self.first_activation_delay_exempt,
+            require_sequential_levels: self.require_sequential_levels,
+            payment_mint: self.payment_mint,
+            token_level_prices: prices("token-level-prices", self.token_level_prices)?,
+            usd_price_feed: self.usd_price_feed,
+            usd_level_prices_cents: prices("usd-level-prices-cents", self.usd_level_prices_cents)?,
+            usd_feed_max_age: self.usd_feed_max_age,
+            usd_feed_max_conf_bps: self.usd_feed_max_conf_bps,
+            usd_feed_id: self.usd_feed_id,
+        })
+    }
+}
+
+fn load_keypair(path: Option<PathBuf>) -> Result<Keypair> {
+    let path = match path {
+        Some(p) => p,
+        None => PathBuf::from(std::env::var("HOME").context("HOME not set")?)
+            .join(".config/solana/id.json"),
+    };
+    read_keypair_file(&path).map_err(|e| anyhow!("keypair {}: {e}", path.display()))
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed());
+    let keypair = cli.keypair;
+    let signer = || load_keypair(keypair.clone());
+
+    let value = match cli.command {
+        Command::Config(ConfigCommand::Show) => inspect::config(&rpc)?,
+        Command::Config(ConfigCommand::Init(args)) => {
+            admin::init_config(&rpc, &signer()?, args.into_instruction())?
+        }
+        Command::Config(ConfigCommand::Migrate) => admin::migrate_config(&rpc, &signer()?)?,
+        Command::Config(ConfigCommand::Update(args)) => {
+            admin::update_config(&rpc, &signer()?, args.into_update()?)?
+        }
+        Command::Stats => inspect::stats(&rpc)?,
+        Command::Queue { level } => inspect::queue(&rpc, level)?,
+        Command::Player { who } => inspect::player(&rpc, &who)?,
+        Command::Admin(cmd) => {
+            let signer = signer()?;
+            match cmd {
+                AdminCommand::InitStatsShards => admin::init_stats_shards(&rpc, &signer)?,
+                AdminCommand::InitTreasuryVault => admin::init_treasury_vault(&rpc, &signer)?,
+                AdminCommand::WithdrawTreasury {
+                    amount,
+                    destination,
+                } => admin::withdraw_treasury(&rpc, &signer, amount, &destination)?,
+                AdminCommand::SetTreasurySplit { split } => {
+                    if split.len() > MAX_TREASURY_BENEFICIARIES {
+                        bail!("at most {MAX_TREASURY_BENEFICIARIES} beneficiaries");
+                    }
+                    admin::set_treasury_split(&rpc, &signer, split)?
+                }
+                AdminCommand::DistributeTreasury => admin::distribute_treasury(&rpc, &signer)?,
+                AdminCommand::UpgradeAccounts => admin::upgrade_accounts(&rpc, &signer)?,
+            }
+        }
+    };
+
+    output::print(cli.output, &value);
+    Ok(())
+}
@@ -0,0 +1,98 @@
+// Every command builds a serde_json::Value; --output picks JSON or plain-text tables.
+//
+// Table layout: an object prints its scalar fields as `key  value` rows and each array of
+// objects as a titled column table; a top-level array of objects is one column table.
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Format {
+    Table,
+    Json,
+}
+
+pub fn print(format: Format, value: &Value) {
+    match format {
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(value).unwrap_or_default()
+        ),
+        Format::Table => print!("{}", render(value)),
+    }
+}
+
+fn render(value: &Value) -> String {
+    match value {
+        Value::Object(map) => render_object(map),
+        Value::Array(rows) if is_rows(rows) => render_rows(rows),
+        other => format!("{}\n", cell(other)),
+    }
+}
+
+fn is_rows(items: &[Value]) -> bool {
+    !items.is_empty() && items.iter().all(Value::is_object)
+}
+
+fn render_object(map: &Map<String, Value>) -> String {
+    let width = map.keys().map(String::len).max().unwrap_or(0);
+    let mut out = String::new();
+    let mut sections = Vec::new();
+    for (key, value) in map {
+        match value {
+            Value::Array(rows) if is_rows(rows) => sections.push((key, rows)),
+            _ => out.push_str(&format!("{key:<width$}  {}\n", cell(value))),
+        }
+    }
+    for (key, rows) in sections {
+        out.push_str(&format!("\n{key}:\n"));
+        out.push_str(&render_rows(rows));
+    }
+    out
+}
+
+fn render_rows(rows: &[Value]) -> String {
+    let columns: Vec<&String> = rows[0]
+        .as_object()
+        .map(|m| m.keys().collect())
+        .unwrap_or_default();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|c| cell(&row[c.as_str()])).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            cells
+                .iter()
+                .map(|r| r[i].len())
+                .max()
+                .unwrap_or(0)
+                .max(c.len())
+        })
+        .collect();
+
+    let line = |values: Vec<&str>| {
+        let padded: Vec<String> = values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{v:<w$}"))
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+    let mut out = line(columns.iter().map(|c| c.as_str()).collect());
+    for row in &cells {
+        out.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    out
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items.iter().map(cell).collect::<Vec<_>>().join(","),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,151 @@
+// Admin and maintenance instruction builders (config, stats shards, treasury, account upgrades).
+// Nothing here reads chain state except distribute_treasury, which needs the split's wallets.
+
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::{instruction::Instruction, system_program},
+};
+use solana_game::{accounts, instruction, ConfigV3Update, TreasurySplit, MAX_LEVEL};
+
+use crate::{build, pda};
+
+// One-time: creates ConfigV3 + GlobalStats. `args` carries every initialize_config_v3 argument.
+pub fn initialize_config_v3(payer: &Pubkey, args: instruction::InitializeConfigV3) -> Instruction {
+    let accounts = accounts::InitializeConfigV3 {
+        config_v3: pda::config().0,
+        global_stats: pda::global_stats().0,
+        payer: *payer,
+        system_program: system_program::ID,
+        #[cfg(feature = "event-cpi")]
+        event_authority: pda::event_authority().0,
+        #[cfg(feature = "event-cpi")]
+        program: crate::PROGRAM_ID,
+    };
+    build(&accounts, args)
+}
+
+pub fn migrate_config_v3(admin: &Pubkey) -> Instruction {
+    let accounts = accounts::MigrateConfigV3 {
+        config_v3: pda::config().0,
+        admin: *admin,
+        system_program: system_program::ID,
+    };
+    build(&accounts, instruction::MigrateConfigV3 {})
+}
+
+// Currency changes (payment_mint / token_level_prices / usd_price_feed) also pass the 16
+// LevelPool PDAs: the program rejects them once any level has a queue.
+pub fn update_config_v3(admin: &Pubkey, args: ConfigV3Update) -> Instruction {
+    let accounts = accounts::UpdateConfigV3 {
+        config_v3: pda::config().0,
+        admin: *admin,
+    };
+    let currency = args.payment_mint.is_some()
+        || args.token_level_prices.is_some()
+        || args.usd_price_feed.is_some();
+    let mut ix = build(&accounts, instruction::UpdateConfigV3 { args });
+    if currency {
+        ix.accounts.extend(
+            (1..=MAX_LEVEL).map(|level| AccountMeta::new_readonly(pda::level_pool(level).0, false)),
+        );
+    }
+    ix
+}
+
+pub fn init_stats_shard(admin: &Pubkey, shard: u8) -> Instruction {
+    let accounts = accounts::InitStatsShard {
+        stats_shard: pda::stats_shard(shard).0,
+        config_v3: pda::config().0,
+        admin: *admin,
+        system_program: system_program::ID,
+    };
+    build(&accounts, instruction::InitStatsShard { shard })
+}
+
+pub fn init_treasury_vault(admin: &Pubkey) -> Instruction {
+    let accounts = accounts::InitTreasuryVault {
+        treasury_vault: pda::treasury_vault().0,
+        config_v3: pda::config().0,
+        admin: *admin,
+        system_program: system_program::ID,
+    };
+    build(&accounts, instruction::InitTreasuryVault {})
+}
+
+pub fn withdraw_treasury(admin: &Pubkey, amount: u64, destination: &Pubkey) -> Instruction {
+    let accounts = accounts::WithdrawTreasury {
+        treasury_vault: pda::treasury_vault().0,
+        config_v3: pda::config().0,
+        admin: *admin,
+        destination: *destination,
+        #[cfg(feature = "event-cpi")]
+        event_authority: pda::event_authority().0,
+        #[cfg(feature = "event-cpi")]
+        program: crate::PROGRAM_ID,
+    };
+    build(
+        &accounts,
+        instruction::WithdrawTreasury {
+            amount,
+            destination: *destination,
+        },
+    )
+}
+
+pub fn set_treasury_split(
+    admin: &Pubkey,
+    beneficiaries: Vec<Pubkey>,
+    bps: Vec<u16>,
+) -> Instruction {
+    let accounts = accounts::SetTreasurySplit {
+        treasury_split: pda::treasury_split().0,
+        config_v3: pda::config().0,
+        admin: *admin,
+        system_program: system_program::ID,
+    };
+    build(
+        &accounts,
+        instruction::SetTreasurySplit { beneficiaries, bps },
+    )
+}
+
+// Permissionless crank; the split's beneficiaries follow as remaining accounts, in order
+pub fn distribute_treasury(split: &TreasurySplit) -> Instruction {
+    let accounts = accounts::DistributeTreasury {
+        treasury_split: pda::treasury_split().0,
+        treasury_vault: pda::treasury_vault().0,
+        config_v3: pda::config().0,
+        #[cfg(feature = "event-cpi")]
+        event_authority: pda::event_authority().0,
+        #[cfg(feature = "event-cpi")]
+        program: crate::PROGRAM_ID,
+    };
+    let mut ix = build(&accounts, instruction::DistributeTreasury {});
+    ix.accounts.extend(
+        split.beneficiaries[..split.count as usize]
+            .iter()
+            .map(|b| AccountMeta::new(*b, false)),
+    );
+    ix
+}
+
+// Grows a pre-event_seq LevelPool (permissionless, payer funds the rent top-up)
+pub fn upgrade_level_pool(payer: &Pubkey, level: u8) -> Instruction {
+    let accounts = accounts::UpgradeLevelPool {
+        level_pool: pda::level_pool(level).0,
+        config_v3: pda::config().0,
+        payer: *payer,
+        system_program: system_program::ID,
+    };
+    build(&accounts, instruction::UpgradeLevelPool { level })
+}
+
+// Grows a pre-event_seq StatsShard (permissionless, payer funds the rent top-up)
+pub fn upgrade_stats_shard(payer: &Pubkey, shard: u8) -> Instruction {
+    let accounts = accounts::UpgradeStatsShard {
+        stats_shard: pda::stats_shard(shard).0,
+        payer: *payer,
+        system_program: system_program::ID,
+    };
+    build(&accounts, instruction::UpgradeStatsShard { shard })
+}
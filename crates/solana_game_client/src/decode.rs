@@ -0,0 +1,132 @@
+// Account decoders. Accounts created before a layout grew (Player.next_nonce, ConfigV3 v3.13+,
+// StatsShard/LevelPool.event_seq) decode with the missing trailing fields as zero; LevelState
+// also accepts the pre-queue-membership layout. For Player and ConfigV3 this is more lenient
+// than the program: only activation and the views take an old Player (growing it), the other
+// `Account<Player>` instructions need upgrade_player first, and an old ConfigV3 is unusable
+// until migrate_config_v3.
+
+use anchor_lang::{prelude::Pubkey, AccountDeserialize, Discriminator};
+use solana_game::{
+    AuthorityLink, AuthorityRecovery, ConfigV3, GiftOptIn, GlobalStats, LevelPool, LevelState,
+    Player, PythPrice, QueuePage, StatsShard, TreasurySplit, TreasuryVault, TxGuard,
+};
+
+use crate::error::{ClientError, Result};
+use crate::source::AccountSource;
+
+fn strict<T: AccountDeserialize>(data: &[u8]) -> Option<T> {
+    let mut slice = data;
+    T::try_deserialize(&mut slice).ok()
+}
+
+// Zero-extends `data` to 8 + `size` (a no-op for current layouts)
+fn padded<T: AccountDeserialize>(data: &[u8], size: usize) -> Option<T> {
+    let mut buf = data.to_vec();
+    if buf.len() < 8 + size {
+        buf.resize(8 + size, 0);
+    }
+    strict(&buf)
+}
+
+pub fn config(data: &[u8]) -> Option<ConfigV3> {
+    solana_game::decode_config_padded(data).ok()
+}
+
+pub fn player(data: &[u8]) -> Option<Player> {
+    solana_game::decode_player_padded(data).ok()
+}
+
+// Current layout only (inspect flags the rest as legacy)
+pub fn level_state_current(data: &[u8]) -> Option<LevelState> {
+    strict(data)
+}
+
+// Current layout, else the legacy one (same discriminator, shorter body), like the
+// program's peek_level_state
+pub fn level_state(data: &[u8]) -> Option<LevelState> {
+    if let Some(ls) = level_state_current(data) {
+        return Some(ls);
+    }
+    if !data.starts_with(LevelState::DISCRIMINATOR) {
+        return None;
+    }
+    solana_game::decode_legacy_level_state(data).ok()
+}
+
+pub fn level_pool(data: &[u8]) -> Option<LevelPool> {
+    padded(data, LevelPool::SIZE)
+}
+
+pub fn queue_page(data: &[u8]) -> Option<QueuePage> {
+    strict(data)
+}
+
+pub fn global_stats(data: &[u8]) -> Option<GlobalStats> {
+    strict(data)
+}
+
+pub fn stats_shard(data: &[u8]) -> Option<StatsShard> {
+    padded(data, StatsShard::SIZE)
+}
+
+pub fn tx_guard(data: &[u8]) -> Option<TxGuard> {
+    strict(data)
+}
+
+pub fn authority_link(data: &[u8]) -> Option<AuthorityLink> {
+    strict(data)
+}
+
+pub fn authority_recovery(data: &[u8]) -> Option<AuthorityRecovery> {
+    strict(data)
+}
+
+pub fn gift_opt_in(data: &[u8]) -> Option<GiftOptIn> {
+    strict(data)
+}
+
+pub fn treasury_vault(data: &[u8]) -> Option<TreasuryVault> {
+    strict(data)
+}
+
+pub fn treasury_split(data: &[u8]) -> Option<TreasurySplit> {
+    strict(data)
+}
+
+// Pyth PriceUpdateV2 (same offsets as the program's read_pyth_price; staleness not checked)
+pub fn pyth_price(data: &[u8]) -> Option<PythPrice> {
+    fn field<const N: usize>(data: &[u8], at: usize) -> Option<[u8; N]> {
+        data.get(at..at + N)?.try_into().ok()
+    }
+    Some(PythPrice {
+        price: i64::from_le_bytes(field(data, 73)?),
+        conf: u64::from_le_bytes(field(data, 81)?),
+        exponent: i32::from_le_bytes(field(data, 89)?),
+        publish_time: i64::from_le_bytes(field(data, 93)?),
+    })
+}
+
+// Fetch + decode: Ok(None) if the account is missing, Err if it exists but doesn't decode.
+pub fn fetch<T>(
+    source: &impl AccountSource,
+    key: &Pubkey,
+    what: &'static str,
+    decode: fn(&[u8]) -> Option<T>,
+) -> Result<Option<T>> {
+    match source.account_data(key)? {
+        Some(data) if !data.is_empty() => decode(&data)
+            .map(Some)
+            .ok_or(ClientError::Decode(*key, what)),
+        _ => Ok(None),
+    }
+}
+
+// Like `fetch`, but a missing account is an error too
+pub fn fetch_required<T>(
+    source: &impl AccountSource,
+    key: &Pubkey,
+    what: &'static str,
+    decode: fn(&[u8]) -> Option<T>,
+) -> Result<T> {
+    fetch(source, key, what, decode)?.ok_or(ClientError::AccountNotFound(*key))
+}
@@ -0,0 +1,19 @@
+use anchor_lang::prelude::Pubkey;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("account {0} not found")]
+    AccountNotFound(Pubkey),
+    #[error("account {0} is not a valid {1}")]
+    Decode(Pubkey, &'static str),
+    #[error("invalid level {0}")]
+    InvalidLevel(u8),
+    #[error("queue of level {level} is corrupt: {reason}")]
+    QueueState { level: u8, reason: &'static str },
+    #[error("USD price feed {0} has no usable price")]
+    PriceFeed(Pubkey),
+    #[error("account source: {0}")]
+    Source(String),
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
@@ -0,0 +1,288 @@
+// Program events: decoding from `Program data:` log lines (default build) and from the
+// self-CPI inner instructions of an `event-cpi` build (EVENT_IX_TAG_LE + discriminator + borsh).
+//
+// Events emitted before a field existed (before schema_version) are shorter than the current
+// struct: like old accounts, the missing trailing fields decode as zero (schema_version 0).
+
+use anchor_lang::{event::EVENT_IX_TAG_LE, prelude::Pubkey, AnchorDeserialize, Discriminator};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_game::{
+    AuthorityRotated, ConfigInitialized, DequeuedOwner, Enqueued, LevelActivated, LevelGifted,
+    LevelRecycled, PayoutDistributed, PayoutRedirect, PlayerCreated, QueuePageCreated,
+    RecoveryStarted, SlotsFilledN, TreasuryDistributed, TreasuryWithdrawn, ID as PROGRAM_ID,
+};
+
+// Per-stream event_seq (docs/EVENT_SCHEMA.md): LevelPool per level, StatsShard per shard
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Level(u8),
+    Shard(u8),
+}
+
+impl std::fmt::Display for Stream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Stream::Level(level) => write!(f, "level:{level}"),
+            Stream::Shard(shard) => write!(f, "shard:{shard}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DecodedEvent {
+    pub name: &'static str,
+    pub schema_version: u8,
+    pub stream: Option<Stream>,
+    pub event_seq: Option<u64>,
+    // All fields, pubkeys as base58
+    pub data: Value,
+}
+
+trait JsonField {
+    fn json(&self) -> Value;
+}
+
+impl JsonField for Pubkey {
+    fn json(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl JsonField for PayoutRedirect {
+    fn json(&self) -> Value {
+        Value::String(format!("{self:?}"))
+    }
+}
+
+macro_rules! json_scalar {
+    ($($t:ty),*) => {
+        $(impl JsonField for $t {
+            fn json(&self) -> Value {
+                json!(self)
+            }
+        })*
+    };
+}
+json_scalar!(u8, u32, u64, i64, bool);
+
+impl<T: JsonField, const N: usize> JsonField for [T; N] {
+    fn json(&self) -> Value {
+        Value::Array(self.iter().map(JsonField::json).collect())
+    }
+}
+
+fn parse<E: AnchorDeserialize>(body: &[u8]) -> Option<E> {
+    if let Ok(e) = E::deserialize(&mut &body[..]) {
+        return Some(e);
+    }
+    // Older, shorter layout: zero-extend (no event is anywhere near 512 bytes)
+    let mut padded = body.to_vec();
+    padded.resize(body.len() + 512, 0);
+    E::deserialize(&mut &padded[..]).ok()
+}
+
+// `bytes` = discriminator + borsh body (what emit! logs / the event CPI carries after the tag)
+pub fn decode_event(bytes: &[u8]) -> Option<DecodedEvent> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let (disc, body) = bytes.split_at(8);
+
+    macro_rules! event {
+        ($ty:ident, [$($field:ident),*], $stream:expr, $seq:expr) => {
+            if disc == $ty::DISCRIMINATOR {
+                let e: $ty = parse(body)?;
+                let stream: fn(&$ty) -> Option<Stream> = $stream;
+                let seq: fn(&$ty) -> Option<u64> = $seq;
+                return Some(DecodedEvent {
+                    name: stringify!($ty),
+                    schema_version: e.schema_version,
+                    stream: stream(&e),
+                    event_seq: seq(&e),
+                    data: json!({ $(stringify!($field): e.$field.json()),* }),
+                });
+            }
+        };
+        ($ty:ident, [$($field:ident),*], level) => {
+            event!($ty, [$($field),*], |e| Some(Stream::Level(e.level)), |e| Some(e.event_seq));
+        };
+        ($ty:ident, [$($field:ident),*]) => {
+            event!($ty, [$($field),*], |_| None, |_| None);
+        };
+    }
+
+    event!(
+        PlayerCreated,
+        [authority, timestamp, schema_version, shard, event_seq],
+        |e| Some(Stream::Shard(e.shard)),
+        |e| Some(e.event_seq)
+    );
+    event!(
+        LevelActivated,
+        [
+            owner,
+            level,
+            lamports,
+            timestamp,
+            actual_price,
+            schema_version,
+            event_seq,
+            max_price
+        ],
+        level
+    );
+    event!(
+        LevelGifted,
+        [
+            payer,
+            beneficiary,
+            player,
+            level,
+            lamports,
+            timestamp,
+            schema_version,
+            event_seq
+        ],
+        level
+    );
+    event!(
+        LevelRecycled,
+        [
+            owner,
+            level,
+            cycles,
+            lamports,
+            timestamp,
+            schema_version,
+            event_seq
+        ],
+        level
+    );
+    event!(
+        SlotsFilledN,
+        [owner, level, cycles, filled, schema_version, event_seq],
+        level
+    );
+    event!(
+        PayoutDistributed,
+        [
+            activator,
+            player,
+            level,
+            owner,
+            owner_amount,
+            refs,
+            ref_amounts,
+            redirected_amounts,
+            redirect_reasons,
+            treasury_amount,
+            bookkeeping_rent,
+            is_self_owner,
+            timestamp,
+            schema_version,
+            event_seq
+        ],
+        level
+    );
+    event!(
+        DequeuedOwner,
+        [owner_player, level, page_index, schema_version, event_seq],
+        level
+    );
+    event!(
+        Enqueued,
+        [
+            player,
+            level,
+            page_index,
+            position_in_page,
+            schema_version,
+            event_seq
+        ],
+        level
+    );
+    event!(
+        QueuePageCreated,
+        [level, pool, page_index, page, schema_version, event_seq],
+        level
+    );
+    event!(
+        ConfigInitialized,
+        [admin, treasury, timestamp, schema_version]
+    );
+    event!(
+        TreasuryWithdrawn,
+        [
+            destination,
+            amount,
+            epoch,
+            withdrawn_in_epoch,
+            timestamp,
+            schema_version
+        ]
+    );
+    event!(
+        TreasuryDistributed,
+        [amount, beneficiaries, timestamp, schema_version]
+    );
+    event!(
+        AuthorityRotated,
+        [
+            player,
+            old_authority,
+            new_authority,
+            by_guardian,
+            timestamp,
+            schema_version
+        ]
+    );
+    event!(
+        RecoveryStarted,
+        [player, guardian, new_authority, unlock_at, schema_version]
+    );
+    None
+}
+
+// Events in a transaction's log, in order. Only `Program data:` lines written while this
+// program is the innermost running program count (CPI callees may log their own data).
+pub fn events_from_logs(logs: &[String]) -> Vec<DecodedEvent> {
+    let program = PROGRAM_ID.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+
+    for line in logs {
+        let Some(rest) = line.strip_prefix("Program ") else {
+            continue;
+        };
+        if let Some(data) = rest.strip_prefix("data: ") {
+            if stack.last() != Some(&program.as_str()) {
+                continue;
+            }
+            // sol_log_data: one base64 segment per slice, emit! logs a single slice
+            if let Some(event) = data
+                .split(' ')
+                .next()
+                .and_then(|b64| STANDARD.decode(b64).ok())
+                .and_then(|bytes| decode_event(&bytes))
+            {
+                events.push(event);
+            }
+            continue;
+        }
+        let mut words = rest.split(' ');
+        match (words.next(), words.next()) {
+            (Some(id), Some("invoke")) => stack.push(id),
+            (Some(_), Some("success")) | (Some(_), Some("failed:")) => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+// Event carried by an event-cpi inner instruction's data (None for any other instruction)
+pub fn event_from_cpi_data(data: &[u8]) -> Option<DecodedEvent> {
+    decode_event(data.strip_prefix(EVENT_IX_TAG_LE)?)
+}
@@ -0,0 +1,334 @@
+// Instruction builders. Account lists are resolved from chain state the same way the program
+// validates them (process_existing_activation / resolve_refs / open_level_wallet), so callers
+// only pass the wallet, the level and the price bound.
+
+use anchor_lang::{
+    prelude::Pubkey,
+    solana_program::{instruction::Instruction, system_program},
+};
+use solana_game::{accounts, instruction, ConfigV3, MAX_LEVEL};
+
+use crate::decode::{self, fetch, fetch_required};
+use crate::error::{ClientError, Result};
+use crate::source::AccountSource;
+use crate::{build, pda};
+
+// Player PDA of a wallet: AuthorityLink target after rotate_authority, else ["player", wallet]
+pub fn resolve_player(source: &impl AccountSource, wallet: &Pubkey) -> Result<Pubkey> {
+    let link = fetch(
+        source,
+        &pda::authority_link(wallet).0,
+        "AuthorityLink",
+        decode::authority_link,
+    )?;
+    Ok(link.map_or_else(|| pda::player(wallet).0, |l| l.player))
+}
+
+pub fn fetch_config(source: &impl AccountSource) -> Result<ConfigV3> {
+    fetch_required(source, &pda::config().0, "ConfigV3", decode::config)
+}
+
+// Current price of `level` in the configured currency (lamports or payment_mint units).
+// USD mode converts through the feed like the program, without the staleness checks.
+pub fn quote_price(source: &impl AccountSource, cfg: &ConfigV3, level: u8) -> Result<u64> {
+    if !(1..=MAX_LEVEL).contains(&level) {
+        return Err(ClientError::InvalidLevel(level));
+    }
+    if !cfg.usd_mode() {
+        return Ok(solana_game::expected_price_from_cfg(level, cfg));
+    }
+    let feed = cfg.usd_price_feed;
+    let price = fetch_required(source, &feed, "PriceUpdateV2", decode::pyth_price)?;
+    if price.price <= 0 {
+        return Err(ClientError::PriceFeed(feed));
+    }
+    solana_game::usd_cents_to_lamports(cfg.usd_level_prices_cents[(level - 1) as usize], &price)
+        .map_err(|_| ClientError::PriceFeed(feed))
+}
+
+// Wallet that receives a ref share and the LevelState proving it (None = paid as admin).
+// Mirrors open_level_wallet: the upline is a Player PDA or a seed wallet (["player", upline]),
+// and only an activated, current-layout LevelState of that Player counts.
+fn resolve_ref(
+    source: &impl AccountSource,
+    admin: &Pubkey,
+    upline: &Pubkey,
+    level: u8,
+) -> Result<(Pubkey, Option<Pubkey>)> {
+    if upline == admin || *upline == Pubkey::default() {
+        return Ok((*admin, None));
+    }
+    for upline_player in [*upline, pda::player(upline).0] {
+        let (ls_key, _) = pda::level_state(&upline_player, level);
+        let Some(ls) = source
+            .account_data(&ls_key)?
+            .and_then(|d| decode::level_state(&d))
+        else {
+            continue;
+        };
+        if ls.player == upline_player && ls.level == level && ls.activated_at > 0 {
+            return Ok((ls.authority, Some(ls_key)));
+        }
+    }
+    Ok((*admin, None))
+}
+
+// Resolved activate_level_v3 / activate_level_usd accounts for one wallet and level.
+pub struct ActivationPlan {
+    pub level: u8,
+    // Player.next_nonce (the tx_guard slot is left empty)
+    pub nonce: u64,
+    // No queue yet: 100% goes to treasury and page 0 is created
+    pub first_activation: bool,
+    // Player PDA at the head of the queue (the activator itself on first activation)
+    pub owner_player: Pubkey,
+    pub owner_wallet: Pubkey,
+    pub refs: [Pubkey; 3],
+    pub accounts: accounts::ActivateLevelV3,
+}
+
+pub fn resolve_activation(
+    source: &impl AccountSource,
+    authority: &Pubkey,
+    level: u8,
+) -> Result<ActivationPlan> {
+    if !(1..=MAX_LEVEL).contains(&level) {
+        return Err(ClientError::InvalidLevel(level));
+    }
+    let queue_err = |reason| ClientError::QueueState { level, reason };
+
+    let cfg = fetch_config(source)?;
+    let player_pda = resolve_player(source, authority)?;
+    let player = fetch_required(source, &player_pda, "Player", decode::player)?;
+    let (level_pool, _) = pda::level_pool(level);
+    let pool = fetch(source, &level_pool, "LevelPool", decode::level_pool)?;
+    let first_activation = pool.as_ref().and_then(|p| p.head_page).is_none();
+
+    let (head_page, tail_page, new_page, owner_player, owner_wallet, refs, ref_level_states) =
+        match pool.as_ref().and_then(|p| p.head_page) {
+            None => {
+                // First activation only touches new_page (= page 0); the rest is not validated
+                let (page0, _) = pda::queue_page(&level_pool, 0);
+                (
+                    page0,
+                    page0,
+                    page0,
+                    player_pda,
+                    *authority,
+                    [cfg.admin; 3],
+                    [None; 3],
+                )
+            }
+            Some(head_key) => {
+                let tail_key = pool
+                    .as_ref()
+                    .and_then(|p| p.tail_page)
+                    .ok_or(queue_err("head page set without a tail page"))?;
+                let head = fetch_required(source, &head_key, "QueuePage", decode::queue_page)?;
+                let tail = fetch_required(source, &tail_key, "QueuePage", decode::queue_page)?;
+                let owner_player = *head
+                    .players
+                    .first()
+                    .ok_or(queue_err("head page is empty"))?;
+                let next_index = tail
+                    .page_index
+                    .checked_add(1)
+                    .ok_or(queue_err("page index overflow"))?;
+                // Rollover target: used only if the tail (or the owner's re-enqueue) fills it up
+                let (new_page, _) = pda::queue_page(&level_pool, next_index);
+
+                let owner = fetch(source, &owner_player, "Player", decode::player)?;
+                let owner_wallet = match &owner {
+                    Some(p) => p.authority,
+                    None => fetch(
+                        source,
+                        &pda::level_state(&owner_player, level).0,
+                        "LevelState",
+                        decode::level_state,
+                    )?
+                    .map(|ls| ls.authority)
+                    .filter(|a| *a != Pubkey::default())
+                    .ok_or(queue_err("owner has neither Player nor LevelState"))?,
+                };
+
+                let uplines = owner
+                    .as_ref()
+                    .map(|p| [p.upline1, p.upline2, p.upline3])
+                    .unwrap_or([cfg.admin; 3]);
+                let mut refs = [cfg.admin; 3];
+                let mut ref_level_states = [None; 3];
+                for i in 0..3 {
+                    (refs[i], ref_level_states[i]) =
+                        resolve_ref(source, &cfg.admin, &uplines[i], level)?;
+                }
+                (
+                    head_key,
+                    tail_key,
+                    new_page,
+                    owner_player,
+                    owner_wallet,
+                    refs,
+                    ref_level_states,
+                )
+            }
+        };
+
+    let treasury_account = if cfg.use_treasury_vault {
+        pda::treasury_vault().0
+    } else {
+        cfg.treasury
+    };
+
+    let accounts = accounts::ActivateLevelV3 {
+        player: player_pda,
+        level_state: pda::level_state(&player_pda, level).0,
+        tx_guard: None,
+        authority: *authority,
+        config_v3: pda::config().0,
+        level_pool,
+        admin_account: cfg.admin,
+        treasury_account,
+        ref1_account: refs[0],
+        ref2_account: refs[1],
+        ref3_account: refs[2],
+        tail_page,
+        new_page,
+        head_page,
+        owner_player,
+        owner_level_state: pda::level_state(&owner_player, level).0,
+        owner_wallet,
+        ref1_level_state: ref_level_states[0],
+        ref2_level_state: ref_level_states[1],
+        ref3_level_state: ref_level_states[2],
+        system_program: system_program::ID,
+        rent_reserve: Some(pda::rent_reserve().0),
+        previous_level_state: (level > 1).then(|| pda::level_state(&player_pda, level - 1).0),
+        price_feed: cfg.usd_mode().then_some(cfg.usd_price_feed),
+        #[cfg(feature = "event-cpi")]
+        event_authority: pda::event_authority().0,
+        #[cfg(feature = "event-cpi")]
+        program: crate::PROGRAM_ID,
+    };
+
+    Ok(ActivationPlan {
+        level,
+        nonce: player.next_nonce,
+        first_activation,
+        owner_player,
+        owner_wallet,
+        refs,
+        accounts,
+    })
+}
+
+impl ActivationPlan {
+    // Legacy replay protection (cfg.legacy_tx_guard): a fresh TxGuard PDA instead of next_nonce
+    pub fn with_tx_guard(mut self, nonce: u64) -> Self {
+        self.nonce = nonce;
+        self.accounts.tx_guard = Some(
+            pda::tx_guard(
+                &self.accounts.player,
+                pda::TX_GUARD_ACTIVATE,
+                self.level,
+                nonce,
+            )
+            .0,
+        );
+        self
+    }
+
+    pub fn activate_level_v3(&self, max_price_lamports: u64) -> Instruction {
+        build(
+            &self.accounts,
+            instruction::ActivateLevelV3 {
+                level: self.level,
+                max_price_lamports,
+                nonce: self.nonce,
+            },
+        )
+    }
+
+    // USD mode: the program charges at most quoted_lamports * (1 + max_slippage_bps / 10000)
+    pub fn activate_level_usd(&self, quoted_lamports: u64, max_slippage_bps: u16) -> Instruction {
+        build(
+            &self.accounts,
+            instruction::ActivateLevelUsd {
+                level: self.level,
+                quoted_lamports,
+                nonce: self.nonce,
+                max_slippage_bps,
+            },
+        )
+    }
+}
+
+// Referrer arguments for a registration: a referrer with a Player account passes it (uplines
+// are copied from it); otherwise the referrer wallet itself becomes upline1.
+fn resolve_referrer(
+    source: &impl AccountSource,
+    referrer_wallet: Option<&Pubkey>,
+) -> Result<(Option<Pubkey>, Option<Pubkey>)> {
+    let Some(wallet) = referrer_wallet else {
+        return Ok((None, None));
+    };
+    let referrer_pda = resolve_player(source, wallet)?;
+    if source.exists(&referrer_pda)? {
+        Ok((Some(referrer_pda), Some(referrer_pda)))
+    } else {
+        Ok((Some(*wallet), None))
+    }
+}
+
+// register_player for `authority`, without a TxGuard
+pub fn register_player(
+    source: &impl AccountSource,
+    authority: &Pubkey,
+    referrer_wallet: Option<&Pubkey>,
+) -> Result<Instruction> {
+    let (referrer, referrer_player) = resolve_referrer(source, referrer_wallet)?;
+
+    let accounts = accounts::RegisterPlayer {
+        player: pda::player(authority).0,
+        authority: *authority,
+        tx_guard: None,
+        config_v3: pda::config().0,
+        global_stats: pda::global_stats().0,
+        stats_shard: pda::stats_shard_for(authority).0,
+        referrer_player,
+        system_program: system_program::ID,
+        authority_link: pda::authority_link(authority).0,
+        #[cfg(feature = "event-cpi")]
+        event_authority: pda::event_authority().0,
+        #[cfg(feature = "event-cpi")]
+        program: crate::PROGRAM_ID,
+    };
+    Ok(build(
+        &accounts,
+        instruction::RegisterPlayer { referrer, nonce: 0 },
+    ))
+}
+
+// create_player_v3 for `authority`: same accounts as register_player minus the tx_guard slot
+pub fn create_player_v3(
+    source: &impl AccountSource,
+    authority: &Pubkey,
+    referrer_wallet: Option<&Pubkey>,
+) -> Result<Instruction> {
+    let (referrer, referrer_player) = resolve_referrer(source, referrer_wallet)?;
+
+    let accounts = accounts::CreatePlayerV3 {
+        player: pda::player(authority).0,
+        authority: *authority,
+        config_v3: pda::config().0,
+        global_stats: pda::global_stats().0,
+        stats_shard: pda::stats_shard_for(authority).0,
+        referrer_player,
+        system_program: system_program::ID,
+        authority_link: pda::authority_link(authority).0,
+        #[cfg(feature = "event-cpi")]
+        event_authority: pda::event_authority().0,
+        #[cfg(feature = "event-cpi")]
+        program: crate::PROGRAM_ID,
+    };
+    Ok(build(&accounts, instruction::CreatePlayerV3 { referrer }))
+}
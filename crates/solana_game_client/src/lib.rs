@@ -0,0 +1,35 @@
+// ============================================================
+// solana_game_client — off-chain access to the solana_game program
+//
+// pda     — every PDA seed used in processor.rs
+// decode  — account decoders (older layouts included)
+// events  — program events from logs / event-cpi inner instructions
+// source  — AccountSource: where builders read chain state from
+// ix      — instruction builders that resolve queue/owner/ref accounts
+// admin   — config / shard / treasury / upgrade instructions
+// ============================================================
+
+use anchor_lang::{solana_program::instruction::Instruction, InstructionData, ToAccountMetas};
+
+pub mod admin;
+pub mod decode;
+pub mod error;
+pub mod events;
+pub mod ix;
+pub mod pda;
+pub mod source;
+
+pub use error::{ClientError, Result};
+pub use source::AccountSource;
+
+// Program types (accounts, events, anchor client modules) for callers of this crate
+pub use solana_game;
+pub use solana_game::ID as PROGRAM_ID;
+
+fn build(accounts: &impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
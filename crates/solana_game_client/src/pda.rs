@@ -0,0 +1,109 @@
+// PDA helpers: one function per seed layout in processor.rs. All return (address, bump).
+
+use anchor_lang::prelude::Pubkey;
+use solana_game::{
+    AUTHORITY_LINK_SEED, AUTHORITY_RECOVERY_SEED, GIFT_OPT_IN_SEED, ID as PROGRAM_ID,
+    RENT_RESERVE_SEED, TREASURY_SPLIT_SEED, TREASURY_VAULT_SEED,
+};
+
+// TxGuard kinds (`tx` seed, byte after the player)
+pub use solana_game::{TX_GUARD_ACTIVATE, TX_GUARD_RECYCLE};
+
+fn find(seeds: &[&[u8]]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, &PROGRAM_ID)
+}
+
+// ["config_v3_new"]
+pub fn config() -> (Pubkey, u8) {
+    find(&[b"config_v3_new"])
+}
+
+// ["global_stats_v1"]
+pub fn global_stats() -> (Pubkey, u8) {
+    find(&[b"global_stats_v1"])
+}
+
+// ["stats_shard_v1", shard]
+pub fn stats_shard(shard: u8) -> (Pubkey, u8) {
+    find(&[b"stats_shard_v1", &[shard]])
+}
+
+// Shard that register_player / create_player_v3 write for this wallet
+pub fn stats_shard_for(authority: &Pubkey) -> (Pubkey, u8) {
+    stats_shard(solana_game::stats_shard_for(authority))
+}
+
+// ["player", authority] — the original wallet; rotated wallets go through AuthorityLink
+pub fn player(authority: &Pubkey) -> (Pubkey, u8) {
+    find(&[b"player", authority.as_ref()])
+}
+
+// ["lvl", player_pda, level]
+pub fn level_state(player_pda: &Pubkey, level: u8) -> (Pubkey, u8) {
+    find(&[b"lvl", player_pda.as_ref(), &[level]])
+}
+
+// ["tx", player_pda, kind, level, nonce_le] (kind: TX_GUARD_ACTIVATE / TX_GUARD_RECYCLE)
+pub fn tx_guard(player_pda: &Pubkey, kind: u8, level: u8, nonce: u64) -> (Pubkey, u8) {
+    find(&[
+        b"tx",
+        player_pda.as_ref(),
+        &[kind],
+        &[level],
+        &nonce.to_le_bytes(),
+    ])
+}
+
+// ["tx", "register", authority, nonce_le]
+pub fn register_tx_guard(authority: &Pubkey, nonce: u64) -> (Pubkey, u8) {
+    find(&[b"tx", b"register", authority.as_ref(), &nonce.to_le_bytes()])
+}
+
+// ["level_pool_v1", config, level]
+pub fn level_pool(level: u8) -> (Pubkey, u8) {
+    find(&[b"level_pool_v1", config().0.as_ref(), &[level]])
+}
+
+// ["queue_page_v1", level_pool, page_index_le]
+pub fn queue_page(level_pool: &Pubkey, page_index: u32) -> (Pubkey, u8) {
+    find(&[
+        b"queue_page_v1",
+        level_pool.as_ref(),
+        &page_index.to_le_bytes(),
+    ])
+}
+
+// [RENT_RESERVE_SEED]
+pub fn rent_reserve() -> (Pubkey, u8) {
+    find(&[RENT_RESERVE_SEED])
+}
+
+// [TREASURY_VAULT_SEED]
+pub fn treasury_vault() -> (Pubkey, u8) {
+    find(&[TREASURY_VAULT_SEED])
+}
+
+// [TREASURY_SPLIT_SEED]
+pub fn treasury_split() -> (Pubkey, u8) {
+    find(&[TREASURY_SPLIT_SEED])
+}
+
+// [AUTHORITY_LINK_SEED, wallet]
+pub fn authority_link(wallet: &Pubkey) -> (Pubkey, u8) {
+    find(&[AUTHORITY_LINK_SEED, wallet.as_ref()])
+}
+
+// [AUTHORITY_RECOVERY_SEED, player_pda]
+pub fn authority_recovery(player_pda: &Pubkey) -> (Pubkey, u8) {
+    find(&[AUTHORITY_RECOVERY_SEED, player_pda.as_ref()])
+}
+
+// [GIFT_OPT_IN_SEED, player_pda]
+pub fn gift_opt_in(player_pda: &Pubkey) -> (Pubkey, u8) {
+    find(&[GIFT_OPT_IN_SEED, player_pda.as_ref()])
+}
+
+// ["__event_authority"] (#[event_cpi] builds only)
+pub fn event_authority() -> (Pubkey, u8) {
+    find(&[b"__event_authority"])
+}
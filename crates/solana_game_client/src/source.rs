@@ -0,0 +1,40 @@
+// Where builders read chain state from. Plug an RPC client in by implementing `account_data`;
+// a HashMap snapshot works for tests and replays.
+
+use std::collections::HashMap;
+
+use anchor_lang::prelude::Pubkey;
+
+use crate::error::Result;
+
+pub trait AccountSource {
+    // Raw account data; None when the account doesn't exist (or holds no data).
+    fn account_data(&self, key: &Pubkey) -> Result<Option<Vec<u8>>>;
+
+    fn exists(&self, key: &Pubkey) -> Result<bool> {
+        Ok(self.account_data(key)?.is_some_and(|d| !d.is_empty()))
+    }
+}
+
+impl AccountSource for HashMap<Pubkey, Vec<u8>> {
+    fn account_data(&self, key: &Pubkey) -> Result<Option<Vec<u8>>> {
+        Ok(self.get(key).cloned())
+    }
+}
+
+impl<S: AccountSource + ?Sized> AccountSource for &S {
+    fn account_data(&self, key: &Pubkey) -> Result<Option<Vec<u8>>> {
+        (**self).account_data(key)
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl AccountSource for solana_rpc_client::rpc_client::RpcClient {
+    fn account_data(&self, key: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let account = self
+            .get_account_with_commitment(key, self.commitment())
+            .map_err(|e| crate::ClientError::Source(e.to_string()))?
+            .value;
+        Ok(account.map(|a| a.data))
+    }
+}
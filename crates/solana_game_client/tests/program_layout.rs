@@ -0,0 +1,477 @@
+// The client against the program, without a bank: PDAs and account lists against the IDL
+// (idl/solana_game.json, built with event-cpi) and the program's own ActivateLevelV3 account
+// validation, plus event decoding for every schema version. TxGuard seeds are only exercised
+// by quote_matches_execution (creating the guard is a CPI).
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Once;
+
+use anchor_lang::{
+    event::EVENT_IX_TAG_LE,
+    prelude::{AccountInfo, Pubkey, Rent},
+    solana_program::{instruction::Instruction, system_program},
+    AccountDeserialize, AccountSerialize, Accounts, Bumps, Discriminator, Event,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+use solana_game::{
+    ActivateLevelV3, ConfigV3, LevelActivated, LevelState, Player, PlayerCreated, QueuePage,
+    EVENT_SCHEMA_VERSION,
+};
+use solana_game_client::{
+    events::{self, Stream},
+    ix, pda, PROGRAM_ID,
+};
+use solana_sysvar::program_stubs;
+
+const NONCE: u64 = 7;
+
+fn idl() -> Value {
+    serde_json::from_str(include_str!("../../../idl/solana_game.json")).unwrap()
+}
+
+fn idl_instruction<'a>(idl: &'a Value, name: &str) -> &'a Value {
+    idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|ix| ix["name"] == name)
+        .unwrap_or_else(|| panic!("{name} is not in the IDL"))
+}
+
+// Instruction accounts in order, nested groups (activate_level_for's `activate`) flattened
+fn flatten(accounts: &Value, out: &mut Vec<Value>) {
+    for account in accounts.as_array().unwrap() {
+        match account.get("accounts") {
+            Some(nested) => flatten(nested, out),
+            None => out.push(account.clone()),
+        }
+    }
+}
+
+// The IDL's account list as this build of the client sends it: the trailing event-cpi pair
+// only with the feature
+fn sent_accounts(ix: &Value) -> Vec<Value> {
+    let mut accounts = Vec::new();
+    flatten(&ix["accounts"], &mut accounts);
+    let names: Vec<&str> = accounts
+        .iter()
+        .map(|a| a["name"].as_str().unwrap())
+        .collect();
+    assert!(
+        names.ends_with(&["event_authority", "program"]),
+        "IDL built without event-cpi"
+    );
+    if !cfg!(feature = "event-cpi") {
+        accounts.truncate(accounts.len() - 2);
+    }
+    accounts
+}
+
+// Every meta matches the IDL account at its position: expected key, signer and writable flags
+// (an omitted optional account is the program id, read-only)
+fn assert_follows_idl(ix_name: &str, ix: &Instruction, keys: &HashMap<&str, Pubkey>) {
+    let idl = idl();
+    let accounts = sent_accounts(idl_instruction(&idl, ix_name));
+    assert_eq!(
+        ix.accounts.len(),
+        accounts.len(),
+        "{ix_name}: account count"
+    );
+    for (meta, account) in ix.accounts.iter().zip(&accounts) {
+        let name = account["name"].as_str().unwrap();
+        let key = keys
+            .get(name)
+            .unwrap_or_else(|| panic!("{ix_name}: no expected key for {name}"));
+        assert_eq!(meta.pubkey, *key, "{ix_name}.{name}");
+        let omitted = account["optional"] == true && meta.pubkey == PROGRAM_ID;
+        assert_eq!(
+            meta.is_signer,
+            account["signer"] == true,
+            "{ix_name}.{name}"
+        );
+        assert_eq!(
+            meta.is_writable,
+            account["writable"] == true && !omitted,
+            "{ix_name}.{name}"
+        );
+    }
+}
+
+// Zeroed current-layout account of `size` bytes after the discriminator
+fn zeroed<T: AccountDeserialize + Discriminator>(size: usize) -> T {
+    let mut data = T::DISCRIMINATOR.to_vec();
+    data.resize(8 + size, 0);
+    T::try_deserialize(&mut &data[..]).unwrap()
+}
+
+fn serialized<T: AccountSerialize>(account: &T, size: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    account.try_serialize(&mut data).unwrap();
+    data.resize(8 + size, 0);
+    data
+}
+
+struct Setup {
+    accounts: HashMap<Pubkey, Vec<u8>>,
+    admin: Pubkey,
+    treasury: Pubkey,
+}
+
+// Config, and a registered `wallet` that already holds its LevelState for `level`
+fn setup(wallet: &Pubkey, level: u8) -> Setup {
+    let admin = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    let mut cfg: ConfigV3 = solana_game::decode_config_padded(ConfigV3::DISCRIMINATOR).unwrap();
+    cfg.admin = admin;
+    cfg.treasury = treasury;
+    cfg.bump = pda::config().1;
+
+    let player_pda = pda::player(wallet).0;
+    let mut player: Player = solana_game::decode_player_padded(Player::DISCRIMINATOR).unwrap();
+    player.authority = *wallet;
+    player.next_nonce = NONCE;
+    let mut level_state: LevelState = zeroed(LevelState::SIZE);
+    level_state.player = player_pda;
+    level_state.authority = *wallet;
+    level_state.level = level;
+
+    let accounts = HashMap::from([
+        (pda::config().0, serialized(&cfg, ConfigV3::SIZE)),
+        (player_pda, serialized(&player, Player::SIZE)),
+        (
+            pda::level_state(&player_pda, level).0,
+            serialized(&level_state, LevelState::SIZE),
+        ),
+    ]);
+    Setup {
+        accounts,
+        admin,
+        treasury,
+    }
+}
+
+#[test]
+fn activation_accounts_follow_the_idl() {
+    let wallet = Pubkey::new_unique();
+    let level = 2;
+    let setup = setup(&wallet, level);
+    let player = pda::player(&wallet).0;
+    let plan = ix::resolve_activation(&setup.accounts, &wallet, level).unwrap();
+    assert!(plan.first_activation);
+    assert_eq!(plan.nonce, NONCE);
+
+    let page0 = pda::queue_page(&pda::level_pool(level).0, 0).0;
+    let mut keys = HashMap::from([
+        ("player", player),
+        ("level_state", pda::level_state(&player, level).0),
+        ("tx_guard", PROGRAM_ID),
+        ("authority", wallet),
+        ("config_v3", pda::config().0),
+        ("level_pool", pda::level_pool(level).0),
+        ("admin_account", setup.admin),
+        ("treasury_account", setup.treasury),
+        ("ref1_account", setup.admin),
+        ("ref2_account", setup.admin),
+        ("ref3_account", setup.admin),
+        ("tail_page", page0),
+        ("new_page", page0),
+        ("head_page", page0),
+        ("owner_player", player),
+        ("owner_level_state", pda::level_state(&player, level).0),
+        ("owner_wallet", wallet),
+        ("ref1_level_state", PROGRAM_ID),
+        ("ref2_level_state", PROGRAM_ID),
+        ("ref3_level_state", PROGRAM_ID),
+        ("system_program", system_program::ID),
+        ("rent_reserve", pda::rent_reserve().0),
+        (
+            "previous_level_state",
+            pda::level_state(&player, level - 1).0,
+        ),
+        ("price_feed", PROGRAM_ID),
+        ("event_authority", pda::event_authority().0),
+        ("program", PROGRAM_ID),
+    ]);
+    assert_follows_idl(
+        "activate_level_v3",
+        &plan.activate_level_v3(u64::MAX),
+        &keys,
+    );
+    assert_follows_idl("activate_level_usd", &plan.activate_level_usd(1, 0), &keys);
+
+    let plan = plan.with_tx_guard(NONCE + 1);
+    keys.insert(
+        "tx_guard",
+        pda::tx_guard(&player, pda::TX_GUARD_ACTIVATE, level, NONCE + 1).0,
+    );
+    assert_follows_idl(
+        "activate_level_v3",
+        &plan.activate_level_v3(u64::MAX),
+        &keys,
+    );
+}
+
+#[test]
+fn registration_accounts_follow_the_idl() {
+    let referrer = Pubkey::new_unique();
+    let setup = setup(&referrer, 1);
+    let wallet = Pubkey::new_unique();
+    let keys = HashMap::from([
+        ("player", pda::player(&wallet).0),
+        ("authority", wallet),
+        ("tx_guard", PROGRAM_ID),
+        ("config_v3", pda::config().0),
+        ("global_stats", pda::global_stats().0),
+        ("stats_shard", pda::stats_shard_for(&wallet).0),
+        ("referrer_player", pda::player(&referrer).0),
+        ("system_program", system_program::ID),
+        ("authority_link", pda::authority_link(&wallet).0),
+        ("event_authority", pda::event_authority().0),
+        ("program", PROGRAM_ID),
+    ]);
+    let register = ix::register_player(&setup.accounts, &wallet, Some(&referrer)).unwrap();
+    assert_follows_idl("register_player", &register, &keys);
+    let create = ix::create_player_v3(&setup.accounts, &wallet, Some(&referrer)).unwrap();
+    assert_follows_idl("create_player_v3", &create, &keys);
+}
+
+// Every PDA the IDL spells out (const / account / arg seeds) against the client helper for it
+#[test]
+fn idl_pdas_match_the_client() {
+    let idl = idl();
+    let mut keys: HashMap<String, Pubkey> = HashMap::new();
+    let mut checked = BTreeSet::new();
+
+    for ix in idl["instructions"].as_array().unwrap() {
+        let mut accounts = Vec::new();
+        flatten(&ix["accounts"], &mut accounts);
+        for account in accounts.iter().filter(|a| a.get("pda").is_some()) {
+            let name = account["name"].as_str().unwrap();
+            let mut seeds: Vec<Vec<u8>> = Vec::new();
+            // The one non-constant seed (wallet / player), if any
+            let mut input = None;
+            for seed in account["pda"]["seeds"].as_array().unwrap() {
+                let path = seed["path"].as_str().unwrap_or_default();
+                match seed["kind"].as_str().unwrap() {
+                    "const" => seeds.push(
+                        seed["value"]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .map(|b| b.as_u64().unwrap() as u8)
+                            .collect(),
+                    ),
+                    "arg" if path == "nonce" => seeds.push(NONCE.to_le_bytes().to_vec()),
+                    "account" | "arg" => {
+                        let key = *keys
+                            .entry(path.to_string())
+                            .or_insert_with(Pubkey::new_unique);
+                        seeds.push(key.to_bytes().to_vec());
+                        input = Some(key);
+                    }
+                    kind => panic!("unexpected seed kind {kind}"),
+                }
+            }
+            let seed_refs: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+            let (address, _) = Pubkey::find_program_address(&seed_refs, &PROGRAM_ID);
+
+            let key = || input.unwrap();
+            let expected = match name {
+                "config_v3" => pda::config(),
+                "global_stats" => pda::global_stats(),
+                "rent_reserve" => pda::rent_reserve(),
+                "treasury_vault" => pda::treasury_vault(),
+                "treasury_split" => pda::treasury_split(),
+                "event_authority" => pda::event_authority(),
+                "player" | "new_authority_player" => pda::player(&key()),
+                "authority_link" => pda::authority_link(&key()),
+                "recovery" => pda::authority_recovery(&key()),
+                "gift_opt_in" => pda::gift_opt_in(&key()),
+                "tx_guard" => pda::register_tx_guard(&key(), NONCE),
+                _ => panic!("no client PDA for {}.{name}", ix["name"]),
+            };
+            assert_eq!(address, expected.0, "{}.{name}", ix["name"]);
+            checked.insert(name.to_string());
+        }
+    }
+    assert!(checked.contains("player") && checked.contains("tx_guard"));
+}
+
+// Seeds the IDL can't express (byte-array args, helper calls) against the program's
+// definitions
+#[test]
+fn computed_seeds_match_the_program() {
+    let pool = pda::level_pool(3).0;
+    let seeds = QueuePage::seeds(&pool, 5);
+    let seed_refs: Vec<&[u8]> = seeds.iter().map(Vec::as_slice).collect();
+    assert_eq!(
+        pda::queue_page(&pool, 5).0,
+        Pubkey::find_program_address(&seed_refs, &PROGRAM_ID).0
+    );
+    let wallet = Pubkey::new_unique();
+    assert_eq!(
+        pda::stats_shard_for(&wallet),
+        pda::stats_shard(solana_game::stats_shard_for(&wallet))
+    );
+}
+
+// Anchor creates init accounts through the Rent sysvar, which has no native default
+fn stub_rent_sysvar() {
+    struct RentStub;
+    impl program_stubs::SyscallStubs for RentStub {
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { std::ptr::write(var_addr as *mut Rent, Rent::default()) };
+            0
+        }
+    }
+    static STUB: Once = Once::new();
+    STUB.call_once(|| {
+        program_stubs::set_syscall_stubs(Box::new(RentStub));
+    });
+}
+
+// ActivateLevelV3::try_accounts on the instruction's metas: owners, seeds (level_state,
+// level_pool, config bump, rent_reserve, event_authority), signer and writable constraints
+fn validate_activation(
+    accounts: &HashMap<Pubkey, Vec<u8>>,
+    ix: &Instruction,
+) -> anchor_lang::Result<()> {
+    stub_rent_sysvar();
+    let rent = Rent::default();
+    let mut state: Vec<(Pubkey, Pubkey, u64, Vec<u8>)> = ix
+        .accounts
+        .iter()
+        .map(|meta| {
+            let data = accounts.get(&meta.pubkey).cloned().unwrap_or_default();
+            let (owner, lamports) = if data.is_empty() {
+                (system_program::ID, 0)
+            } else {
+                (PROGRAM_ID, rent.minimum_balance(data.len()))
+            };
+            (meta.pubkey, owner, lamports, data)
+        })
+        .collect();
+    let infos: Vec<AccountInfo> = state
+        .iter_mut()
+        .zip(&ix.accounts)
+        .map(|((key, owner, lamports, data), meta)| {
+            let executable = *key == PROGRAM_ID || *key == system_program::ID;
+            AccountInfo::new(
+                key,
+                meta.is_signer,
+                meta.is_writable,
+                lamports,
+                data,
+                owner,
+                executable,
+                0,
+            )
+        })
+        .collect();
+    let mut bumps = <ActivateLevelV3 as Bumps>::Bumps::default();
+    ActivateLevelV3::try_accounts(
+        &PROGRAM_ID,
+        &mut &infos[..],
+        &ix.data[8..],
+        &mut bumps,
+        &mut BTreeSet::new(),
+    )
+    .map(|_| ())
+}
+
+#[test]
+fn program_accepts_the_resolved_activation_accounts() {
+    let wallet = Pubkey::new_unique();
+    let setup = setup(&wallet, 1);
+    let plan = ix::resolve_activation(&setup.accounts, &wallet, 1).unwrap();
+    validate_activation(&setup.accounts, &plan.activate_level_v3(u64::MAX)).unwrap();
+
+    // A LevelState of another level is rejected on its seeds
+    let mut wrong = plan.activate_level_v3(u64::MAX);
+    wrong.accounts[1].pubkey = pda::level_state(&plan.accounts.player, 2).0;
+    let mut accounts = setup.accounts.clone();
+    accounts.insert(
+        wrong.accounts[1].pubkey,
+        accounts[&plan.accounts.level_state].clone(),
+    );
+    assert!(validate_activation(&accounts, &wrong).is_err());
+}
+
+fn activated(schema_version: u8) -> LevelActivated {
+    LevelActivated {
+        owner: Pubkey::new_unique(),
+        level: 4,
+        lamports: 1_000,
+        timestamp: 1_700_000_000,
+        actual_price: 990,
+        schema_version,
+        event_seq: 12,
+        max_price: 1_100,
+    }
+}
+
+// Event bytes as an older program wrote them: the body cut after `len` bytes
+fn truncated(event: &impl Event, len: usize) -> Vec<u8> {
+    let mut data = event.data();
+    data.truncate(8 + len);
+    data
+}
+
+#[test]
+fn current_events_decode_from_logs_and_cpi_data() {
+    let event = activated(EVENT_SCHEMA_VERSION);
+    let program = PROGRAM_ID.to_string();
+    let logs = vec![
+        format!("Program {program} invoke [1]"),
+        format!("Program data: {}", STANDARD.encode(event.data())),
+        format!("Program {program} success"),
+    ];
+    let from_logs = events::events_from_logs(&logs);
+    assert_eq!(from_logs.len(), 1);
+
+    let mut cpi_data = EVENT_IX_TAG_LE.to_vec();
+    cpi_data.extend(event.data());
+    let from_cpi = events::event_from_cpi_data(&cpi_data).unwrap();
+
+    for decoded in [&from_logs[0], &from_cpi] {
+        assert_eq!(decoded.name, "LevelActivated");
+        assert_eq!(decoded.schema_version, EVENT_SCHEMA_VERSION);
+        assert_eq!(decoded.stream, Some(Stream::Level(4)));
+        assert_eq!(decoded.event_seq, Some(12));
+        assert_eq!(decoded.data["owner"], event.owner.to_string());
+        assert_eq!(decoded.data["actual_price"], 990);
+        assert_eq!(decoded.data["max_price"], 1_100);
+    }
+}
+
+#[test]
+fn older_schema_events_decode_with_zeroed_fields() {
+    // Schema 0: nothing from schema_version on
+    let v0 = truncated(&activated(0), 32 + 1 + 8 + 8 + 8);
+    let decoded = events::decode_event(&v0).unwrap();
+    assert_eq!(decoded.schema_version, 0);
+    assert_eq!(decoded.event_seq, Some(0));
+    assert_eq!(decoded.data["actual_price"], 990);
+    assert_eq!(decoded.data["max_price"], 0);
+
+    // Schema 1: everything but max_price
+    let v1 = truncated(&activated(1), 32 + 1 + 8 + 8 + 8 + 1 + 8);
+    let decoded = events::decode_event(&v1).unwrap();
+    assert_eq!(decoded.schema_version, 1);
+    assert_eq!(decoded.event_seq, Some(12));
+    assert_eq!(decoded.data["max_price"], 0);
+
+    let created = PlayerCreated {
+        authority: Pubkey::new_unique(),
+        timestamp: 1_700_000_000,
+        schema_version: 0,
+        shard: 9,
+        event_seq: 3,
+    };
+    let decoded = events::decode_event(&truncated(&created, 32 + 8)).unwrap();
+    assert_eq!(decoded.name, "PlayerCreated");
+    assert_eq!(decoded.schema_version, 0);
+    assert_eq!(decoded.stream, Some(Stream::Shard(0)));
+    assert_eq!(decoded.data["authority"], created.authority.to_string());
+}
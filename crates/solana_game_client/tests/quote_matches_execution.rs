@@ -0,0 +1,512 @@
+// quote_activation vs the activation it quotes, on a program-test bank. Accounts come from
+// ix::resolve_activation, as for any SDK caller; every lamport (and token) the activation
+// moves must match the quote.
+//
+// Needs the SBF build: anchor's CPIs (account init, transfers) only run on target_os =
+// "solana", so the program can't be loaded natively. Run with
+//   cargo build-sbf --manifest-path programs/solana_game/Cargo.toml
+//   SBF_OUT_DIR=$PWD/target/deploy cargo test -p solana_game_client \
+//     --test quote_matches_execution -- --ignored
+
+use std::collections::HashMap;
+
+use anchor_lang::{
+    prelude::{Clock, Pubkey},
+    solana_program::{instruction::Instruction, system_instruction},
+    AnchorDeserialize, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        self, get_associated_token_address_with_program_id,
+        spl_associated_token_account::instruction::create_associated_token_account,
+    },
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            transfer_fee::{self, TransferFeeConfig},
+            BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+    },
+};
+use solana_account::{Account, AccountSharedData};
+use solana_game::{
+    accounts, instruction, ActivationQuote, ConfigV3Update, LevelState, Player,
+    LEVEL_PRICES_LAMPORTS, MAX_LEVEL,
+};
+use solana_game_client::{admin, ix, ix::fetch_config, pda, AccountSource, PROGRAM_ID};
+use solana_keypair::Keypair;
+use solana_program_test::{ProgramTest, ProgramTestContext};
+use solana_signer::Signer;
+use solana_transaction::Transaction;
+use tokio::runtime::Runtime;
+
+const SOL: u64 = 1_000_000_000;
+
+struct Chain {
+    rt: Runtime,
+    ctx: ProgramTestContext,
+}
+
+impl AccountSource for Chain {
+    fn account_data(&self, key: &Pubkey) -> solana_game_client::Result<Option<Vec<u8>>> {
+        Ok(self.account(key).map(|a| a.data))
+    }
+}
+
+impl Chain {
+    fn start() -> Self {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let mut test = ProgramTest::new("solana_game", PROGRAM_ID, None);
+        test.prefer_bpf(true);
+        let ctx = rt.block_on(test.start_with_context());
+        Self { rt, ctx }
+    }
+
+    fn payer(&self) -> Pubkey {
+        self.ctx.payer.pubkey()
+    }
+
+    fn account(&self, key: &Pubkey) -> Option<Account> {
+        let banks = &self.ctx.banks_client;
+        self.rt.block_on(banks.get_account(*key)).unwrap()
+    }
+
+    fn lamports(&self, key: &Pubkey) -> u64 {
+        self.account(key).map_or(0, |a| a.lamports)
+    }
+
+    fn token_balance(&self, ata: &Pubkey) -> u64 {
+        self.account(ata).map_or(0, |a| {
+            StateWithExtensions::<spl_token_2022::state::Account>::unpack(&a.data)
+                .unwrap()
+                .base
+                .amount
+        })
+    }
+
+    fn rent(&self, space: usize) -> u64 {
+        let banks = &self.ctx.banks_client;
+        self.rt
+            .block_on(banks.get_rent())
+            .unwrap()
+            .minimum_balance(space)
+    }
+
+    fn epoch(&self) -> u64 {
+        let banks = &self.ctx.banks_client;
+        self.rt.block_on(banks.get_sysvar::<Clock>()).unwrap().epoch
+    }
+
+    fn transaction(&self, ixs: &[Instruction], signers: &[&Keypair]) -> Transaction {
+        let banks = &self.ctx.banks_client;
+        let blockhash = self.rt.block_on(banks.get_latest_blockhash()).unwrap();
+        let mut all = vec![&self.ctx.payer];
+        all.extend_from_slice(signers);
+        Transaction::new_signed_with_payer(ixs, Some(&self.payer()), &all, blockhash)
+    }
+
+    fn send(&self, ixs: &[Instruction], signers: &[&Keypair]) {
+        let tx = self.transaction(ixs, signers);
+        let banks = &self.ctx.banks_client;
+        self.rt.block_on(banks.process_transaction(tx)).unwrap();
+    }
+
+    fn fund(&self, to: &Pubkey, lamports: u64) {
+        self.send(
+            &[system_instruction::transfer(&self.payer(), to, lamports)],
+            &[],
+        );
+    }
+
+    // Rewrites an account with an older (shorter) layout, rent-exempt for that size
+    fn shrink(&mut self, key: &Pubkey, data: Vec<u8>) {
+        let account = Account {
+            lamports: self.rent(data.len()),
+            data,
+            owner: PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        self.ctx.set_account(key, &AccountSharedData::from(account));
+    }
+
+    fn quote(&self, ix: Instruction) -> ActivationQuote {
+        let tx = self.transaction(&[ix], &[]);
+        let banks = &self.ctx.banks_client;
+        let sim = self.rt.block_on(banks.simulate_transaction(tx)).unwrap();
+        let details = sim.simulation_details.unwrap();
+        assert!(
+            matches!(sim.result, Some(Ok(()))),
+            "quote failed: {:?}\n{:#?}",
+            sim.result,
+            details.logs
+        );
+        ActivationQuote::try_from_slice(&details.return_data.unwrap().data).unwrap()
+    }
+}
+
+// Config + the 16 stats shards; legacy_tx_guard off so players register on the nonce path
+fn setup() -> (Chain, Keypair) {
+    let chain = Chain::start();
+    let admin = Keypair::new();
+    let treasury = Pubkey::new_unique();
+    chain.fund(&admin.pubkey(), 10 * SOL);
+    chain.fund(&treasury, SOL);
+    chain.fund(&pda::rent_reserve().0, SOL);
+
+    chain.send(
+        &[admin::initialize_config_v3(
+            &chain.payer(),
+            instruction::InitializeConfigV3 {
+                admin: admin.pubkey(),
+                treasury,
+                perc_admin: 0,
+                perc_ref1: 0,
+                perc_ref2: 0,
+                perc_ref3: 0,
+                perc_treasury: 0,
+                base_price_lamports: LEVEL_PRICES_LAMPORTS[0],
+                price_ratio: 2,
+                min_entry_delay: 0,
+                auto_recycle: true,
+                slots_to_recycle: 3,
+                max_levels: MAX_LEVEL,
+            },
+        )],
+        &[],
+    );
+    for shards in (0..16).collect::<Vec<u8>>().chunks(4) {
+        let ixs: Vec<_> = shards
+            .iter()
+            .map(|&s| admin::init_stats_shard(&admin.pubkey(), s))
+            .collect();
+        chain.send(&ixs, &[&admin]);
+    }
+    set_config(
+        &chain,
+        &admin,
+        ConfigV3Update {
+            legacy_tx_guard: Some(false),
+            ..Default::default()
+        },
+    );
+    (chain, admin)
+}
+
+fn set_config(chain: &Chain, admin: &Keypair, args: ConfigV3Update) {
+    chain.send(&[admin::update_config_v3(&admin.pubkey(), args)], &[admin]);
+}
+
+fn register(chain: &Chain, referrer: Option<&Keypair>) -> Keypair {
+    let wallet = Keypair::new();
+    chain.fund(&wallet.pubkey(), 100 * SOL);
+    let ix = ix::register_player(
+        chain,
+        &wallet.pubkey(),
+        referrer.map(|r| r.pubkey()).as_ref(),
+    )
+    .unwrap();
+    chain.send(&[ix], &[&wallet]);
+    wallet
+}
+
+// Token-2022 mint with a 1% transfer fee; prices in its base units
+struct TokenSetup {
+    mint: Pubkey,
+}
+
+impl TokenSetup {
+    fn ata(&self, wallet: &Pubkey) -> Pubkey {
+        get_associated_token_address_with_program_id(wallet, &self.mint, &spl_token_2022::ID)
+    }
+
+    fn fee(&self, chain: &Chain, amount: u64) -> u64 {
+        let data = chain.account(&self.mint).unwrap().data;
+        StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)
+            .unwrap()
+            .get_extension::<TransferFeeConfig>()
+            .unwrap()
+            .calculate_epoch_fee(chain.epoch(), amount)
+            .unwrap()
+    }
+
+    fn create(chain: &Chain, admin: &Keypair) -> Self {
+        let mint = Keypair::new();
+        let payer = chain.payer();
+        let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+        ])
+        .unwrap();
+        chain.send(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &mint.pubkey(),
+                    chain.rent(space),
+                    space as u64,
+                    &spl_token_2022::ID,
+                ),
+                transfer_fee::instruction::initialize_transfer_fee_config(
+                    &spl_token_2022::ID,
+                    &mint.pubkey(),
+                    Some(&payer),
+                    Some(&payer),
+                    100,
+                    u64::MAX,
+                )
+                .unwrap(),
+                spl_token_2022::instruction::initialize_mint2(
+                    &spl_token_2022::ID,
+                    &mint.pubkey(),
+                    &payer,
+                    None,
+                    6,
+                )
+                .unwrap(),
+            ],
+            &[&mint],
+        );
+        let prices = std::array::from_fn(|i| 10_000_000 * (i as u64 + 1));
+        set_config(
+            chain,
+            admin,
+            ConfigV3Update {
+                payment_mint: Some(mint.pubkey()),
+                token_level_prices: Some(prices),
+                ..Default::default()
+            },
+        );
+        Self {
+            mint: mint.pubkey(),
+        }
+    }
+
+    fn fund(&self, chain: &Chain, wallet: &Pubkey, amount: u64) {
+        let payer = chain.payer();
+        chain.send(
+            &[
+                create_associated_token_account(&payer, wallet, &self.mint, &spl_token_2022::ID),
+                spl_token_2022::instruction::mint_to(
+                    &spl_token_2022::ID,
+                    &self.mint,
+                    &self.ata(wallet),
+                    &payer,
+                    &[],
+                    amount,
+                )
+                .unwrap(),
+            ],
+            &[],
+        );
+    }
+}
+
+// Activates `level` for `wallet` and checks every balance the activation moves against
+// the quote taken right before it. `tx_guard_nonce`: legacy TxGuard path.
+fn activate_as_quoted(
+    chain: &Chain,
+    wallet: &Keypair,
+    level: u8,
+    tx_guard_nonce: Option<u64>,
+    token: Option<&TokenSetup>,
+) -> ActivationQuote {
+    let mut plan = ix::resolve_activation(chain, &wallet.pubkey(), level).unwrap();
+    if let Some(nonce) = tx_guard_nonce {
+        plan = plan.with_tx_guard(nonce);
+    }
+    let cfg = fetch_config(chain).unwrap();
+    let a = &plan.accounts;
+    let ata = |w: &Pubkey| token.map(|t| t.ata(w));
+
+    let quote = chain.quote(Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts::QuoteActivation {
+            player: a.player,
+            level_state: a.level_state,
+            config_v3: a.config_v3,
+            level_pool: a.level_pool,
+            head_page: a.head_page,
+            tail_page: a.tail_page,
+            owner_player: a.owner_player,
+            owner_level_state: a.owner_level_state,
+            ref1_level_state: a.ref1_level_state,
+            ref2_level_state: a.ref2_level_state,
+            ref3_level_state: a.ref3_level_state,
+            price_feed: a.price_feed,
+            rent_reserve: a.rent_reserve,
+            stats_shard: None,
+            mint: token.map(|t| t.mint),
+            owner_token: ata(&plan.owner_wallet),
+            ref1_token: ata(&plan.refs[0]),
+            ref2_token: ata(&plan.refs[1]),
+            ref3_token: ata(&plan.refs[2]),
+            treasury_token: ata(&cfg.treasury),
+        }
+        .to_account_metas(None),
+        data: instruction::QuoteActivation { level }.data(),
+    });
+
+    // Expected balance changes, keyed by account
+    let mut lamports: HashMap<Pubkey, i128> = HashMap::new();
+    let mut tokens: HashMap<Pubkey, i128> = HashMap::new();
+    let p = &quote.payout;
+    let legs = [
+        (plan.owner_wallet, p.owner_amount),
+        (plan.refs[0], p.ref1_amount),
+        (plan.refs[1], p.ref2_amount),
+        (plan.refs[2], p.ref3_amount),
+    ];
+    *lamports.entry(wallet.pubkey()).or_default() -= quote.total_lamports as i128;
+    *lamports.entry(pda::rent_reserve().0).or_default() -= quote.rent_from_reserve as i128;
+    match token {
+        None => {
+            for (w, amount) in legs {
+                *lamports.entry(w).or_default() += amount as i128;
+            }
+            *lamports.entry(cfg.treasury).or_default() +=
+                (quote.treasury_amount - quote.rent_from_treasury_share) as i128;
+        }
+        Some(t) => {
+            for (w, amount) in legs {
+                lamports.entry(w).or_default();
+                *tokens.entry(t.ata(&w)).or_default() += amount as i128;
+            }
+            lamports.entry(cfg.treasury).or_default();
+            // The treasury bears the fee on its own leg
+            *tokens.entry(t.ata(&cfg.treasury)).or_default() +=
+                (quote.treasury_amount - t.fee(chain, quote.treasury_amount)) as i128;
+            *tokens.entry(t.ata(&wallet.pubkey())).or_default() -= quote.price as i128;
+        }
+    }
+    let before = |m: &HashMap<Pubkey, i128>, read: &dyn Fn(&Pubkey) -> u64| {
+        m.keys()
+            .map(|k| (*k, read(k) as i128))
+            .collect::<HashMap<_, _>>()
+    };
+    let lamports_before = before(&lamports, &|k| chain.lamports(k));
+    let tokens_before = before(&tokens, &|k| chain.token_balance(k));
+
+    let ix = match token {
+        None => plan.activate_level_v3(quote.price),
+        Some(t) => Instruction {
+            program_id: PROGRAM_ID,
+            accounts: accounts::ActivateLevelToken {
+                activate: plan.accounts,
+                mint: t.mint,
+                payer_token: t.ata(&wallet.pubkey()),
+                owner_token: t.ata(&plan.owner_wallet),
+                ref1_token: t.ata(&plan.refs[0]),
+                ref2_token: t.ata(&plan.refs[1]),
+                ref3_token: t.ata(&plan.refs[2]),
+                treasury_token: t.ata(&cfg.treasury),
+                token_program: spl_token_2022::ID,
+                associated_token_program: associated_token::ID,
+            }
+            .to_account_metas(None),
+            data: instruction::ActivateLevelToken {
+                level,
+                max_price: quote.price,
+                nonce: plan.nonce,
+            }
+            .data(),
+        },
+    };
+    chain.send(&[ix], &[wallet]);
+
+    for (key, delta) in &lamports {
+        let actual = chain.lamports(key) as i128 - lamports_before[key];
+        assert_eq!(actual, *delta, "lamports of {key}, quote {quote:#?}");
+    }
+    for (key, delta) in &tokens {
+        let actual = chain.token_balance(key) as i128 - tokens_before[key];
+        assert_eq!(actual, *delta, "tokens of {key}, quote {quote:#?}");
+    }
+    quote
+}
+
+// First activation, three slots for the head owner (the third recycles it), then a
+// payout whose ref1 is an upline with the level open.
+fn play_level_one(chain: &Chain, token: Option<&TokenSetup>) {
+    let x = register(chain, None);
+    let u = register(chain, Some(&x));
+    let others: Vec<Keypair> = (0..4).map(|_| register(chain, None)).collect();
+    if let Some(t) = token {
+        for w in [&x, &u].into_iter().chain(&others) {
+            t.fund(chain, &w.pubkey(), 1_000_000_000);
+        }
+    }
+
+    let first = activate_as_quoted(chain, &x, 1, None, token);
+    assert!(first.is_first_activation && first.rent_level_pool > 0);
+
+    let slot1 = activate_as_quoted(chain, &u, 1, None, token);
+    assert_eq!(slot1.payout.owner_wallet, x.pubkey());
+    activate_as_quoted(chain, &others[0], 1, None, token);
+    let slot3 = activate_as_quoted(chain, &others[1], 1, None, token);
+    assert!(slot3.owner_recycles);
+
+    // Head is now U, whose upline1 is X's Player (level 1 open)
+    let paid = activate_as_quoted(chain, &others[2], 1, None, token);
+    assert_eq!(paid.payout.owner_wallet, u.pubkey());
+    assert_eq!(paid.payout.ref1_wallet, x.pubkey());
+    assert!(paid.payout.ref1_amount > 0);
+}
+
+#[test]
+#[ignore = "needs the SBF build, see the top of this file"]
+fn sol_activations_execute_as_quoted() {
+    let (chain, _admin) = setup();
+    play_level_one(&chain, None);
+}
+
+#[test]
+#[ignore = "needs the SBF build, see the top of this file"]
+fn token_activations_execute_as_quoted() {
+    let (chain, admin) = setup();
+    let token = TokenSetup::create(&chain, &admin);
+    play_level_one(&chain, Some(&token));
+}
+
+#[test]
+#[ignore = "needs the SBF build, see the top of this file"]
+fn legacy_accounts_execute_as_quoted() {
+    let (mut chain, admin) = setup();
+    let x = register(&chain, None);
+    let b = register(&chain, None);
+    activate_as_quoted(&chain, &x, 1, None, None);
+
+    // Accounts written before Player.next_nonce, LevelState queue membership and
+    // LevelPool.event_seq, and the TxGuard replay protection
+    set_config(
+        &chain,
+        &admin,
+        ConfigV3Update {
+            legacy_tx_guard: Some(true),
+            ..Default::default()
+        },
+    );
+    let b_player = pda::player(&b.pubkey()).0;
+    let data = chain.account(&b_player).unwrap().data;
+    chain.shrink(&b_player, data[..8 + Player::SIZE - 8].to_vec());
+
+    let x_ls = pda::level_state(&pda::player(&x.pubkey()).0, 1).0;
+    let data = chain.account(&x_ls).unwrap().data;
+    // Through slots_filled, then head_page / tail_page = None
+    let mut legacy = data[..8 + 32 + 32 + 1 + 1 + 8 + 8 + 8].to_vec();
+    legacy.extend_from_slice(&[0, 0]);
+    chain.shrink(&x_ls, legacy);
+    assert!(8 + LevelState::SIZE > chain.account(&x_ls).unwrap().data.len());
+
+    let pool = pda::level_pool(1).0;
+    let data = chain.account(&pool).unwrap().data;
+    chain.shrink(&pool, data[..data.len() - 8].to_vec());
+
+    let quote = activate_as_quoted(&chain, &b, 1, Some(7), None);
+    assert!(quote.rent_tx_guard > 0);
+    assert!(quote.rent_owner_level_state > 0);
+    assert!(quote.rent_realloc_top_ups > 0);
+    assert_eq!(quote.payout.owner_wallet, x.pubkey());
+}
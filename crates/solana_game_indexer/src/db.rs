@@ -0,0 +1,123 @@
+// SQLite history. Rows are keyed by (signature, event_index), so re-indexing any range is an
+// idempotent upsert; `transactions` also records signatures without events (or failed) so
+// `follow` can resume after the newest one.
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Value};
+
+use crate::fetch::TxEvents;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature   TEXT PRIMARY KEY,
+    slot        INTEGER NOT NULL,
+    block_time  INTEGER,
+    failed      INTEGER NOT NULL,
+    events      INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transactions_slot ON transactions (slot);
+
+CREATE TABLE IF NOT EXISTS events (
+    signature       TEXT NOT NULL,
+    event_index     INTEGER NOT NULL,
+    slot            INTEGER NOT NULL,
+    block_time      INTEGER,
+    name            TEXT NOT NULL,
+    schema_version  INTEGER NOT NULL,
+    stream          TEXT,
+    event_seq       INTEGER,
+    data            TEXT NOT NULL,
+    PRIMARY KEY (signature, event_index)
+);
+CREATE INDEX IF NOT EXISTS events_stream ON events (stream, event_seq);
+CREATE INDEX IF NOT EXISTS events_name ON events (name, slot);
+";
+
+pub fn open(path: &str) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+// One SQLite transaction per chain transaction: its events land completely or not at all
+pub fn upsert(conn: &mut Connection, tx: &TxEvents) -> Result<()> {
+    let db = conn.transaction()?;
+    db.execute(
+        "INSERT INTO transactions (signature, slot, block_time, failed, events)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (signature) DO UPDATE SET
+             slot = excluded.slot, block_time = excluded.block_time,
+             failed = excluded.failed, events = excluded.events",
+        params![
+            tx.signature,
+            tx.slot,
+            tx.block_time,
+            tx.failed,
+            tx.events.len()
+        ],
+    )?;
+    for (index, event) in tx.events.iter().enumerate() {
+        db.execute(
+            "INSERT INTO events
+                 (signature, event_index, slot, block_time, name, schema_version, stream, event_seq, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT (signature, event_index) DO UPDATE SET
+                 slot = excluded.slot, block_time = excluded.block_time, name = excluded.name,
+                 schema_version = excluded.schema_version, stream = excluded.stream,
+                 event_seq = excluded.event_seq, data = excluded.data",
+            params![
+                tx.signature,
+                index,
+                tx.slot,
+                tx.block_time,
+                event.name,
+                event.schema_version,
+                event.stream.map(|s| s.to_string()),
+                event.event_seq,
+                event.data.to_string(),
+            ],
+        )?;
+    }
+    db.commit()?;
+    Ok(())
+}
+
+// Newest indexed signature (resume point for `follow`)
+pub fn last_signature(conn: &Connection) -> Result<Option<(String, u64)>> {
+    Ok(conn
+        .query_row(
+            "SELECT signature, slot FROM transactions ORDER BY slot DESC, rowid DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+// Holes in each event_seq stream (docs/EVENT_SCHEMA.md). Only gaps between indexed events
+// count: a stream backfilled from a later slot doesn't start at 1.
+pub fn gaps(conn: &Connection) -> Result<Value> {
+    let mut stmt = conn.prepare(
+        "SELECT stream, prev, event_seq FROM (
+             SELECT stream, event_seq,
+                    LAG(event_seq) OVER (PARTITION BY stream ORDER BY event_seq) AS prev
+             FROM events WHERE stream IS NOT NULL AND event_seq > 0
+         ) WHERE prev IS NOT NULL AND event_seq - prev <> 1
+         ORDER BY stream, event_seq",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let (stream, prev, seq): (String, i64, i64) = (row.get(0)?, row.get(1)?, row.get(2)?);
+        Ok(json!({
+            "stream": stream,
+            "after": prev,
+            "next": seq,
+            // 0 = the same event_seq indexed twice (different signatures)
+            "missing": (seq - prev - 1).max(0),
+        }))
+    })?;
+    Ok(Value::Array(rows.collect::<rusqlite::Result<_>>()?))
+}
+
+#[cfg(test)]
+mod tests;
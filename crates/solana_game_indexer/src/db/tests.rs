@@ -0,0 +1,120 @@
+use super::*;
+use solana_game_client::events::{DecodedEvent, Stream};
+
+fn event(stream: Option<Stream>, event_seq: Option<u64>) -> DecodedEvent {
+    DecodedEvent {
+        name: "LevelActivated",
+        schema_version: 2,
+        stream,
+        event_seq,
+        data: json!({ "event_seq": event_seq }),
+    }
+}
+
+fn tx(signature: &str, slot: u64, events: Vec<DecodedEvent>) -> TxEvents {
+    TxEvents {
+        signature: signature.to_string(),
+        slot,
+        block_time: Some(1_700_000_000 + slot as i64),
+        failed: false,
+        events,
+    }
+}
+
+fn level(level: u8, seq: u64) -> DecodedEvent {
+    event(Some(Stream::Level(level)), Some(seq))
+}
+
+fn count(conn: &Connection, table: &str) -> i64 {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+        row.get(0)
+    })
+    .unwrap()
+}
+
+fn history() -> Vec<TxEvents> {
+    vec![
+        tx("a", 10, vec![level(1, 1), level(1, 2), level(2, 3)]),
+        // Shard stream backfilled from a later slot: starts at 5, no gap before it
+        tx("b", 11, vec![event(Some(Stream::Shard(3)), Some(5))]),
+        tx("c", 12, vec![event(Some(Stream::Shard(3)), Some(6))]),
+        // Level 1 event 3 never indexed; level 2 event 3 seen under a second signature
+        tx("d", 13, vec![level(1, 4), level(2, 3)]),
+        // Pre-event_seq (schema 0) and stream-less events don't take part
+        tx("e", 14, vec![level(1, 0), event(None, None)]),
+    ]
+}
+
+#[test]
+fn gaps_report_missing_and_duplicate_event_seq() {
+    let mut conn = open(":memory:").unwrap();
+    for t in history() {
+        upsert(&mut conn, &t).unwrap();
+    }
+    assert_eq!(
+        gaps(&conn).unwrap(),
+        json!([
+            { "stream": "level:1", "after": 2, "next": 4, "missing": 1 },
+            { "stream": "level:2", "after": 3, "next": 3, "missing": 0 },
+        ])
+    );
+
+    // Indexing the missing event closes the gap
+    upsert(&mut conn, &tx("f", 12, vec![level(1, 3)])).unwrap();
+    assert_eq!(
+        gaps(&conn).unwrap(),
+        json!([{ "stream": "level:2", "after": 3, "next": 3, "missing": 0 }])
+    );
+}
+
+#[test]
+fn reindexing_a_range_is_idempotent() {
+    let mut conn = open(":memory:").unwrap();
+    for t in history() {
+        upsert(&mut conn, &t).unwrap();
+    }
+    let rows = (count(&conn, "transactions"), count(&conn, "events"));
+    let report = gaps(&conn).unwrap();
+    let last = last_signature(&conn).unwrap();
+    assert_eq!(rows, (5, 9));
+    assert_eq!(last, Some(("e".to_string(), 14)));
+
+    // Overlapping backfill (follow resumes at the last slot, so it re-reads that slot too)
+    for t in history().into_iter().skip(2) {
+        upsert(&mut conn, &t).unwrap();
+    }
+    for t in history() {
+        upsert(&mut conn, &t).unwrap();
+    }
+    assert_eq!((count(&conn, "transactions"), count(&conn, "events")), rows);
+    assert_eq!(gaps(&conn).unwrap(), report);
+    assert_eq!(last_signature(&conn).unwrap(), last);
+}
+
+#[test]
+fn reindexing_replaces_a_transaction_in_place() {
+    let mut conn = open(":memory:").unwrap();
+    let mut failed = tx("a", 10, Vec::new());
+    failed.failed = true;
+    upsert(&mut conn, &failed).unwrap();
+    upsert(&mut conn, &tx("a", 10, vec![level(1, 1), level(1, 2)])).unwrap();
+
+    let (failed, events): (bool, i64) = conn
+        .query_row(
+            "SELECT failed, events FROM transactions WHERE signature = 'a'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert!(!failed);
+    assert_eq!(events, 2);
+    assert_eq!(count(&conn, "events"), 2);
+    let seqs: Vec<i64> = conn
+        .prepare("SELECT event_seq FROM events ORDER BY event_index")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<rusqlite::Result<_>>()
+        .unwrap();
+    assert_eq!(seqs, [1, 2]);
+}
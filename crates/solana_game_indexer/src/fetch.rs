@@ -0,0 +1,134 @@
+// RPC side: program signatures in a slot range and the events of one transaction.
+
+use anyhow::{Context, Result};
+use solana_commitment_config::CommitmentConfig;
+use solana_game_client::{
+    events::{event_from_cpi_data, events_from_logs, DecodedEvent},
+    PROGRAM_ID,
+};
+use solana_rpc_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_rpc_client_api::{
+    config::RpcTransactionConfig, response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_signature::Signature;
+use solana_transaction_status_client_types::{
+    EncodedTransaction, UiInstruction, UiMessage, UiTransactionEncoding,
+};
+
+// getSignaturesForAddress page size (RPC maximum)
+const SIGNATURE_PAGE: usize = 1000;
+
+pub struct TxEvents {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub failed: bool,
+    pub events: Vec<DecodedEvent>,
+}
+
+// Program signatures with from_slot <= slot (<= to_slot), newer than `until` if given; oldest first.
+pub fn signatures(
+    rpc: &RpcClient,
+    from_slot: u64,
+    to_slot: Option<u64>,
+    until: Option<Signature>,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+    let mut out = Vec::new();
+    let mut before = None;
+    loop {
+        let page = rpc
+            .get_signatures_for_address_with_config(
+                &PROGRAM_ID,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(SIGNATURE_PAGE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+            .context("getSignaturesForAddress")?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        let done = page.len() < SIGNATURE_PAGE || last.slot < from_slot;
+        before = Some(last.signature.parse()?);
+        out.extend(
+            page.into_iter()
+                .filter(|s| s.slot >= from_slot && to_slot.is_none_or(|to| s.slot <= to)),
+        );
+        if done {
+            break;
+        }
+    }
+    out.reverse();
+    Ok(out)
+}
+
+// Decoded events of one transaction: `Program data:` logs, then event-cpi inner instructions.
+// Failed transactions are returned without events (their events were rolled back).
+pub fn transaction_events(rpc: &RpcClient, signature: &str) -> Result<TxEvents> {
+    let tx = rpc
+        .get_transaction_with_config(
+            &signature.parse()?,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Json),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+        .with_context(|| format!("getTransaction {signature}"))?;
+
+    let meta = tx
+        .transaction
+        .meta
+        .context("transaction without status meta")?;
+    let mut out = TxEvents {
+        signature: signature.to_string(),
+        slot: tx.slot,
+        block_time: tx.block_time,
+        failed: meta.err.is_some(),
+        events: Vec::new(),
+    };
+    if out.failed {
+        return Ok(out);
+    }
+
+    let logs: Vec<String> = meta.log_messages.clone().unwrap_or(Vec::new());
+    out.events.extend(events_from_logs(&logs));
+
+    // Account index -> key: static keys, then loaded writable, then loaded readonly
+    let mut keys: Vec<String> = match tx.transaction.transaction {
+        EncodedTransaction::Json(ui) => match ui.message {
+            UiMessage::Raw(raw) => raw.account_keys,
+            UiMessage::Parsed(parsed) => {
+                parsed.account_keys.into_iter().map(|k| k.pubkey).collect()
+            }
+        },
+        _ => Vec::new(),
+    };
+    if let Some(loaded) = meta.loaded_addresses.as_ref().map(|l| l.clone()) {
+        keys.extend(loaded.writable);
+        keys.extend(loaded.readonly);
+    }
+
+    let program = PROGRAM_ID.to_string();
+    let inner = meta.inner_instructions.unwrap_or(Vec::new());
+    for group in inner {
+        for ix in group.instructions {
+            let UiInstruction::Compiled(ix) = ix else {
+                continue;
+            };
+            if keys.get(ix.program_id_index as usize) != Some(&program) {
+                continue;
+            }
+            if let Some(event) = bs58::decode(&ix.data)
+                .into_vec()
+                .ok()
+                .and_then(|data| event_from_cpi_data(&data))
+            {
+                out.events.push(event);
+            }
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,152 @@
+// ============================================================
+// solana-game-indexer — program event history in SQLite
+//
+// Replaces the browser-side lib/sdk/history/local.ts: every event the program emits
+// (log or event-cpi) is decoded and stored per (signature, event_index). Re-running any
+// range is safe; see docs/INDEXER.md.
+// ============================================================
+
+mod db;
+mod fetch;
+
+use std::{thread, time::Duration};
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use solana_commitment_config::CommitmentConfig;
+use solana_rpc_client::rpc_client::RpcClient;
+
+#[derive(Parser)]
+#[command(
+    name = "solana-game-indexer",
+    version,
+    about = "Event indexer for solana_game"
+)]
+struct Cli {
+    /// RPC endpoint (local validator by default)
+    #[arg(
+        long,
+        short = 'u',
+        env = "RPC_URL",
+        default_value = "http://127.0.0.1:8899",
+        global = true
+    )]
+    url: String,
+
+    /// SQLite database (created if missing)
+    #[arg(
+        long,
+        env = "INDEXER_DB",
+        default_value = "solana_game_events.sqlite",
+        global = true
+    )]
+    db: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Index every program transaction from --from-slot (to --to-slot) and exit
+    Backfill {
+        #[arg(long)]
+        from_slot: u64,
+        #[arg(long)]
+        to_slot: Option<u64>,
+    },
+    /// Index new transactions after the newest stored one, polling
+    Follow {
+        /// Start slot when the database is empty
+        #[arg(long, default_value_t = 0)]
+        from_slot: u64,
+        /// Poll interval, seconds
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+    /// Print one transaction's decoded events (no database)
+    Tx { signature: String },
+    /// Report event_seq gaps per stream
+    Check,
+}
+
+// Index the program transactions in range, oldest first; returns (transactions, events)
+fn index(
+    rpc: &RpcClient,
+    conn: &mut Connection,
+    from_slot: u64,
+    to_slot: Option<u64>,
+    until: Option<&str>,
+) -> Result<(usize, usize)> {
+    let until = until.map(str::parse).transpose()?;
+    let signatures = fetch::signatures(rpc, from_slot, to_slot, until)?;
+    let mut events = 0;
+    for s in &signatures {
+        let tx = fetch::transaction_events(rpc, &s.signature)?;
+        db::upsert(conn, &tx)?;
+        events += tx.events.len();
+    }
+    Ok((signatures.len(), events))
+}
+
+fn tx_json(tx: &fetch::TxEvents) -> Value {
+    json!({
+        "signature": tx.signature,
+        "slot": tx.slot,
+        "block_time": tx.block_time,
+        "failed": tx.failed,
+        "events": tx.events.iter().map(|e| json!({
+            "name": e.name,
+            "schema_version": e.schema_version,
+            "stream": e.stream.map(|s| s.to_string()),
+            "event_seq": e.event_seq,
+            "data": e.data,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let rpc = RpcClient::new_with_commitment(cli.url, CommitmentConfig::confirmed());
+
+    match cli.command {
+        Command::Backfill { from_slot, to_slot } => {
+            let mut conn = db::open(&cli.db)?;
+            let (txs, events) = index(&rpc, &mut conn, from_slot, to_slot, None)?;
+            println!("indexed {txs} transactions, {events} events");
+        }
+        Command::Follow {
+            from_slot,
+            interval,
+        } => {
+            let mut conn = db::open(&cli.db)?;
+            loop {
+                // Signatures at the resume slot newer than `until` still come back: getSignaturesForAddress
+                // stops at `until` itself, not at its slot
+                let (from, until) = match db::last_signature(&conn)? {
+                    Some((signature, slot)) => (slot, Some(signature)),
+                    None => (from_slot, None),
+                };
+                match index(&rpc, &mut conn, from, None, until.as_deref()) {
+                    Ok((0, _)) => {}
+                    Ok((txs, events)) => println!("indexed {txs} transactions, {events} events"),
+                    // RPC hiccups: retry on the next poll from the same cursor
+                    Err(e) => eprintln!("error: {e:#}"),
+                }
+                thread::sleep(Duration::from_secs(interval));
+            }
+        }
+        Command::Tx { signature } => {
+            let tx = fetch::transaction_events(&rpc, &signature)?;
+            println!("{}", serde_json::to_string_pretty(&tx_json(&tx))?);
+        }
+        Command::Check => {
+            let conn = db::open(&cli.db)?;
+            let gaps = db::gaps(&conn)?;
+            println!("{}", serde_json::to_string_pretty(&gaps)?);
+        }
+    }
+    Ok(())
+}
@@ -27,6 +27,11 @@ import {
   deriveLevelPoolPda,
   deriveQueuePagePda,
   deriveTxGuardActivate,
+  deriveRentReservePda,
+  deriveTreasuryVaultPda,
+  deriveAuthorityLinkPda,
+  deriveEventAuthorityPda,
+  EVENT_CPI,
 } from "./pda";
 
 import { MAX_LEVELS, priceLamportsForLevel } from "./prices";
@@ -46,6 +51,10 @@ const ZERO_PUBKEY = SystemProgram.programId;
 /* ------------------------------------------------------------ */
 
 const DISCR_ACTIVATE_LEVEL_V3 = Buffer.from([0, 26, 75, 130, 110, 192, 143, 66]);
+const DISCR_ACTIVATE_LEVEL_USD = Buffer.from([22, 181, 53, 144, 228, 214, 199, 249]);
+
+// USD mode: accepted drift between the quoted and the executed lamport price
+const USD_MAX_SLIPPAGE_BPS = 100;
 
 /* ------------------------------------------------------------ */
 /* Binary readers (script-aligned)                              */
@@ -55,13 +64,53 @@ function readU64LE(buf: Buffer, off: number): bigint {
   return buf.readBigUInt64LE(off);
 }
 
+// ConfigV3.legacy_tx_guard (v3.13+): discr 8 + fixed fields 88 + tx_guard_close_delay 4
+const CFG_LEGACY_TX_GUARD_OFFSET = 100;
+// ConfigV3.use_treasury_vault (v3.15+): ... + legacy_tx_guard 1
+const CFG_USE_TREASURY_VAULT_OFFSET = 101;
+
+// ConfigV3 v3.19+: usd_price_feed(32) usd_level_prices_cents(4 * 16)
+const CFG_USD_PRICE_FEED_OFFSET = 272;
+const CFG_USD_LEVEL_PRICES_OFFSET = 304;
+
 function readConfigAdminTreasury(buf: Buffer) {
+  const useVault = buf.length > CFG_USE_TREASURY_VAULT_OFFSET && buf[CFG_USE_TREASURY_VAULT_OFFSET] === 1;
   return {
     admin: new PublicKey(buf.slice(8, 40)),
-    treasury: new PublicKey(buf.slice(40, 72)),
+    // treasury_account slot receives the treasury share: vault PDA or the treasury wallet
+    treasury: useVault ? deriveTreasuryVaultPda()[0] : new PublicKey(buf.slice(40, 72)),
   };
 }
 
+// TxGuard PDAs are accepted only while the transition window is open
+function readConfigLegacyTxGuard(buf: Buffer): boolean {
+  return buf.length > CFG_LEGACY_TX_GUARD_OFFSET && buf[CFG_LEGACY_TX_GUARD_OFFSET] === 1;
+}
+
+// null = fixed lamport prices
+function readConfigUsd(buf: Buffer, level: number): { feed: PublicKey; cents: number } | null {
+  if (buf.length < CFG_USD_LEVEL_PRICES_OFFSET + 4 * 16) return null;
+  const feed = new PublicKey(buf.slice(CFG_USD_PRICE_FEED_OFFSET, CFG_USD_PRICE_FEED_OFFSET + 32));
+  if (feed.equals(PublicKey.default)) return null;
+  return { feed, cents: buf.readUInt32LE(CFG_USD_LEVEL_PRICES_OFFSET + 4 * (level - 1)) };
+}
+
+// Pyth PriceUpdateV2: price i64 @73, exponent i32 @89. Mirrors usd_cents_to_lamports (ceil).
+async function usdCentsToLamports(connection: Connection, feed: PublicKey, cents: number): Promise<bigint> {
+  const acc = await connection.getAccountInfo(feed, "confirmed");
+  if (!acc) throw new Error("USD price feed account not found");
+  const data = Buffer.from(acc.data);
+  const price = data.readBigInt64LE(73);
+  const expo = data.readInt32LE(89);
+  if (price <= 0n) throw new Error("USD price feed has no valid price");
+
+  let num = BigInt(cents) * 10_000_000n;
+  let den = price;
+  if (expo < 0) num *= 10n ** BigInt(-expo);
+  else den *= 10n ** BigInt(expo);
+  return (num + den - 1n) / den;
+}
+
 function readLevelPoolHeadTail(buf: Buffer) {
   let off = 8 + 32 + 1 + 1;
 
@@ -93,6 +142,19 @@ function readQueuePageMeta(buf: Buffer) {
   return { pageIndex, firstPlayerPda };
 }
 
+// Player.next_nonce (v3.14+): disc 8 + authority 32 + bump 1 + created_at 8 + games_played 8
+// + upline1..3 96. Older Players are grown by the activation and start at 0.
+const PLAYER_NEXT_NONCE_OFFSET = 153;
+
+// Not cached: a stale nonce fails the activation (StaleNonce)
+async function readPlayerNextNonce(connection: Connection, playerPda: PublicKey): Promise<bigint> {
+  const acc = await connection.getAccountInfo(playerPda, "confirmed");
+  if (!acc) throw new Error("Player not found");
+  const data = Buffer.from(acc.data);
+  if (data.length < PLAYER_NEXT_NONCE_OFFSET + 8) return 0n;
+  return data.readBigUInt64LE(PLAYER_NEXT_NONCE_OFFSET);
+}
+
 function readPlayerFull(buf: Buffer) {
   const authority = new PublicKey(buf.slice(8, 40));
   const off = 8 + 32 + 1 + 8 + 8;
@@ -138,6 +200,34 @@ async function hasOpenLevel(connection: Connection, levelStatePda: PublicKey): P
   }
 }
 
+// Rotated wallets (rotate_authority) reach their original Player PDA via AuthorityLink
+async function resolvePlayerPda(connection: Connection, wallet: PublicKey): Promise<PublicKey> {
+  try {
+    const [link] = deriveAuthorityLinkPda(wallet);
+    const acc = await HeliusRPCOptimizer.getAccountInfo(connection, link, "confirmed");
+    if (acc?.data && acc.data.length >= 40) {
+      return new PublicKey(Buffer.from(acc.data).slice(8, 40));
+    }
+  } catch {
+    // no link -> wallet is the Player seed
+  }
+  return derivePlayerPda(wallet)[0];
+}
+
+// LevelState.authority = current payout wallet (follows rotate_authority)
+async function readLevelStateAuthority(
+  connection: Connection,
+  levelStatePda: PublicKey
+): Promise<PublicKey | null> {
+  try {
+    const acc = await HeliusRPCOptimizer.getAccountInfo(connection, levelStatePda, "confirmed");
+    if (!acc?.data || acc.data.length < 72) return null;
+    return new PublicKey(Buffer.from(acc.data).slice(40, 72));
+  } catch {
+    return null;
+  }
+}
+
 function normalizeUpline(wallet: PublicKey, admin: PublicKey): PublicKey {
   // Если в Player записан “нулевой” pubkey (111..), считаем что это admin
   return pkEq(wallet, ZERO_PUBKEY) ? admin : wallet;
@@ -158,7 +248,7 @@ async function buildActivateLevelV3Ix(
 
   /* ---------- Core PDAs ---------- */
 
-  const [playerPda] = derivePlayerPda(authority);
+  const playerPda = await resolvePlayerPda(connection, authority);
   const [levelStatePda] = deriveLevelStatePda(playerPda, level);
 
   const [configPda] = deriveConfigPda();
@@ -177,6 +267,8 @@ async function buildActivateLevelV3Ix(
   if (!cfgAcc || !cfgAcc.data) throw new Error("ConfigV3 not found");
 
   const { admin, treasury } = readConfigAdminTreasury(Buffer.from(cfgAcc.data));
+  const usd = readConfigUsd(Buffer.from(cfgAcc.data), level);
+  const legacyTxGuard = readConfigLegacyTxGuard(Buffer.from(cfgAcc.data));
 
   /* ---------- LevelPool ---------- */
 
@@ -359,63 +451,72 @@ async function buildActivateLevelV3Ix(
   const [adminPlayerPda] = derivePlayerPda(admin);
   const [adminLevelStatePda] = deriveLevelStatePda(adminPlayerPda, level);
 
-  async function resolveRefWallet(wallet: PublicKey): Promise<PublicKey> {
-    if (pkEq(wallet, admin)) return admin;
-    const [p] = derivePlayerPda(wallet);
-    const [ls] = deriveLevelStatePda(p, level);
-    return (await hasOpenLevel(connection, ls)) ? wallet : admin;
-  }
-
-  const ref1Wallet = await resolveRefWallet(u1);
-  const ref2Wallet = await resolveRefWallet(u2);
-  const ref3Wallet = await resolveRefWallet(u3);
+  // ref*_level_state MUST correspond to the resolved ref wallet; if the upline has no
+  // open level we pass adminLevelStatePda and the ref share goes to admin (treasury).
+  // Uplines are Player PDAs (older sign-ups / orphans: the seed wallet, never a rotated one),
+  // like open_level_wallet. The paid wallet is LevelState.authority.
+  async function resolveRef(upline: PublicKey): Promise<{ wallet: PublicKey; levelState: PublicKey }> {
+    const none = { wallet: admin, levelState: adminLevelStatePda };
+    if (pkEq(upline, admin)) return none;
 
-  /* ---------- ref*_level_state (MUST correspond to RESOLVED ref wallets) ---------- */
-  // По скрипту: всегда передаём 3 PDA; если PDA отсутствует — подставляем adminLevelStatePda
+    for (const p of [upline, derivePlayerPda(upline)[0]]) {
+      const [ls] = deriveLevelStatePda(p, level);
+      if (!(await accountExists(connection, ls)) || !(await hasOpenLevel(connection, ls))) continue;
 
-  async function refLevelStateOrAdminByWallet(resolvedRefWallet: PublicKey): Promise<PublicKey> {
-    if (pkEq(resolvedRefWallet, admin)) return adminLevelStatePda;
+      const payoutWallet = await readLevelStateAuthority(connection, ls);
+      if (payoutWallet) return { wallet: payoutWallet, levelState: ls };
+    }
+    return none;
+  }
 
-    const [p] = derivePlayerPda(resolvedRefWallet);
-    const [ls] = deriveLevelStatePda(p, level);
+  const ref1 = await resolveRef(u1);
+  const ref2 = await resolveRef(u2);
+  const ref3 = await resolveRef(u3);
 
-    return (await accountExists(connection, ls)) ? ls : adminLevelStatePda;
-  }
+  const ref1Wallet = ref1.wallet;
+  const ref2Wallet = ref2.wallet;
+  const ref3Wallet = ref3.wallet;
 
-  const ref1LevelStatePda = await refLevelStateOrAdminByWallet(ref1Wallet);
-  const ref2LevelStatePda = await refLevelStateOrAdminByWallet(ref2Wallet);
-  const ref3LevelStatePda = await refLevelStateOrAdminByWallet(ref3Wallet);
+  const ref1LevelStatePda = ref1.levelState;
+  const ref2LevelStatePda = ref2.levelState;
+  const ref3LevelStatePda = ref3.levelState;
 
-  /* ---------- TxGuard + data ---------- */
+  /* ---------- Replay protection + data ---------- */
 
-  const priceLamports = priceLamportsForLevel(level);
-  const nonce = BigInt(Date.now());
+  const priceLamports = usd
+    ? await usdCentsToLamports(connection, usd.feed, usd.cents)
+    : BigInt(priceLamportsForLevel(level));
 
-  const [txGuardPda] = deriveTxGuardActivate(playerPda, level, nonce);
+  // Player.next_nonce; while ConfigV3.legacy_tx_guard is on, a fresh TxGuard PDA instead
+  // (the program ID marks the optional tx_guard slot as None)
+  const nonce = legacyTxGuard ? BigInt(Date.now()) : await readPlayerNextNonce(connection, playerPda);
+  const txGuardPda = legacyTxGuard ? deriveTxGuardActivate(playerPda, level, nonce)[0] : PROGRAM_ID;
 
-  // data = discr(8) + level(u8) + price(u64 LE) + nonce(u64 LE)
-  const data = Buffer.concat([
-    DISCR_ACTIVATE_LEVEL_V3,
-    Buffer.from([level]),
-    Buffer.alloc(8),
-    Buffer.alloc(8),
-  ]);
+  // v3:  discr(8) + level(u8) + max_price_lamports(u64 LE) + nonce(u64 LE)
+  // usd: discr(8) + level(u8) + quoted(u64 LE) + nonce(u64 LE) + max_slippage_bps(u16 LE)
+  const price = Buffer.alloc(8);
+  price.writeBigUInt64LE(priceLamports);
+  const nonceBuf = Buffer.alloc(8);
+  nonceBuf.writeBigUInt64LE(nonce);
+  const slippage = Buffer.alloc(2);
+  slippage.writeUInt16LE(USD_MAX_SLIPPAGE_BPS);
 
-  data.writeBigUInt64LE(BigInt(priceLamports), 9);
-  data.writeBigUInt64LE(nonce, 17);
+  const data = usd
+    ? Buffer.concat([DISCR_ACTIVATE_LEVEL_USD, Buffer.from([level]), price, nonceBuf, slippage])
+    : Buffer.concat([DISCR_ACTIVATE_LEVEL_V3, Buffer.from([level]), price, nonceBuf]);
 
-  /* ---------- ACCOUNTS (STRICT 21) ---------- */
+  /* ---------- ACCOUNTS (STRICT 24) ---------- */
 
   const keys = [
-    { pubkey: playerPda, isSigner: false, isWritable: true }, // 0
+    { pubkey: playerPda, isSigner: false, isWritable: true }, // 0 (Player.next_nonce)
     { pubkey: levelStatePda, isSigner: false, isWritable: true }, // 1
-    { pubkey: txGuardPda, isSigner: false, isWritable: true }, // 2
+    { pubkey: txGuardPda, isSigner: false, isWritable: legacyTxGuard }, // 2 (legacy only)
     { pubkey: authority, isSigner: true, isWritable: true }, // 3
 
-    { pubkey: configPda, isSigner: false, isWritable: true }, // 4
+    { pubkey: configPda, isSigner: false, isWritable: false }, // 4 (read-only: no global write lock)
     { pubkey: levelPoolPda, isSigner: false, isWritable: true }, // 5
 
-    { pubkey: admin, isSigner: false, isWritable: true }, // 6
+    { pubkey: admin, isSigner: false, isWritable: false }, // 6
     { pubkey: treasury, isSigner: false, isWritable: true }, // 7
 
     { pubkey: ref1Wallet, isSigner: false, isWritable: true }, // 8
@@ -426,7 +527,7 @@ async function buildActivateLevelV3Ix(
     { pubkey: newPage, isSigner: false, isWritable: true }, // 12
     { pubkey: headPage, isSigner: false, isWritable: true }, // 13
 
-    { pubkey: ownerPlayerPda, isSigner: false, isWritable: true }, // 14
+    { pubkey: ownerPlayerPda, isSigner: false, isWritable: false }, // 14
     { pubkey: ownerLevelStatePda, isSigner: false, isWritable: true }, // 15
     { pubkey: ownerWallet, isSigner: false, isWritable: true }, // 16
 
@@ -435,8 +536,29 @@ async function buildActivateLevelV3Ix(
     { pubkey: ref3LevelStatePda, isSigner: false, isWritable: false }, // 19
 
     { pubkey: SystemProgram.programId, isSigner: false, isWritable: false }, // 20
+
+    // 21 rent_reserve: refunds bookkeeping rent the treasury share can't cover
+    { pubkey: deriveRentReservePda()[0], isSigner: false, isWritable: true },
+
+    // 22 previous_level_state: checked only when ConfigV3.require_sequential_levels
+    {
+      pubkey: level > 1 ? deriveLevelStatePda(playerPda, level - 1)[0] : PROGRAM_ID,
+      isSigner: false,
+      isWritable: false,
+    },
+
+    // 23 price_feed: ConfigV3.usd_price_feed in USD mode
+    { pubkey: usd ? usd.feed : PROGRAM_ID, isSigner: false, isWritable: false },
   ];
 
+  // 24 event_authority, 25 program: only for a program built with "event-cpi"
+  if (EVENT_CPI) {
+    keys.push(
+      { pubkey: deriveEventAuthorityPda()[0], isSigner: false, isWritable: false },
+      { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }
+    );
+  }
+
   return new TransactionInstruction({
     programId: PROGRAM_ID,
     keys,
@@ -146,6 +146,10 @@ export async function checkForNewReferrals(
     if (!lastPlayer) return;
     
     const currentWalletStr = currentWallet.toBase58();
+    // Uplines are Player PDAs (older sign-ups: the wallet)
+    const currentPlayerStr = currentPlayerPda.toBase58();
+    const isCurrentPlayer = (upline: string) =>
+      upline === currentWalletStr || upline === currentPlayerStr;
     const referralCreatedAt = lastPlayer.created_at * 1000; // Конвертируем в миллисекунды
     const referralAddress = lastPlayer.authority.toBase58();
     
@@ -177,7 +181,7 @@ export async function checkForNewReferrals(
     
     // Проверяем upline1 (линия 1) - ПРИОРИТЕТ 1 (самый важный)
     if (
-      upline1Str === currentWalletStr &&
+      isCurrentPlayer(upline1Str) &&
       isValidReferrerUpline(upline1Str, adminStr, treasuryStr)
     ) {
       const sig = `referral-registered-line1-${referralAddress}-${referralCreatedAt}`;
@@ -198,7 +202,7 @@ export async function checkForNewReferrals(
     
     // Проверяем upline2 (линия 2) - ПРИОРИТЕТ 2
     if (
-      upline2Str === currentWalletStr &&
+      isCurrentPlayer(upline2Str) &&
       isValidReferrerUpline(upline2Str, adminStr, treasuryStr)
     ) {
       const sig = `referral-registered-line2-${referralAddress}-${referralCreatedAt}`;
@@ -219,7 +223,7 @@ export async function checkForNewReferrals(
     
     // Проверяем upline3 (линия 3) - ПРИОРИТЕТ 3
     if (
-      upline3Str === currentWalletStr &&
+      isCurrentPlayer(upline3Str) &&
       isValidReferrerUpline(upline3Str, adminStr, treasuryStr)
     ) {
       const sig = `referral-registered-line3-${referralAddress}-${referralCreatedAt}`;
@@ -118,6 +118,12 @@ export function isProgramIdFromEnv(): boolean {
 export const PLAYER_SEED = "player";
 export const CONFIG_SEED = "config_v3_new";
 export const GLOBAL_STATS_SEED = "global_stats_v1";
+export const STATS_SHARD_SEED = "stats_shard_v1";
+export const STATS_SHARD_COUNT = 16;
+export const RENT_RESERVE_SEED = "rent_reserve_v1";
+export const TREASURY_VAULT_SEED = "treasury_vault_v1";
+export const AUTHORITY_LINK_SEED = "authority_link_v1";
+export const EVENT_AUTHORITY_SEED = "__event_authority";
 
 export const LEVEL_STATE_SEED = "lvl";
 export const LEVEL_POOL_SEED = "level_pool_v1";
@@ -212,6 +218,98 @@ export function deriveGlobalStatsPda(): [PublicKey, number] {
   return cachedStats;
 }
 
+/* ------------------------------------------------------------
+   STATS SHARD PDA: ["stats_shard_v1", [shard]]
+   shard = sha256(authority)[0] % STATS_SHARD_COUNT (Rust stats_shard_for)
+------------------------------------------------------------ */
+
+export async function statsShardFor(authority: PublicKey): Promise<number> {
+  const digest = await crypto.subtle.digest("SHA-256", authority.toBytes());
+  return new Uint8Array(digest)[0] % STATS_SHARD_COUNT;
+}
+
+export function deriveStatsShardPda(shard: number): [PublicKey, number] {
+  if (!Number.isInteger(shard) || shard < 0 || shard >= STATS_SHARD_COUNT) {
+    throw new RangeError(
+      `deriveStatsShardPda: shard=${shard} invalid, expected 0..${STATS_SHARD_COUNT - 1}`
+    );
+  }
+
+  return PublicKey.findProgramAddressSync(
+    [encodeSeed(STATS_SHARD_SEED), Uint8Array.of(shard)],
+    PROGRAM_ID
+  );
+}
+
+/* ------------------------------------------------------------
+   RENT RESERVE PDA: ["rent_reserve_v1"] (system-owned, lamports only)
+------------------------------------------------------------ */
+
+let cachedRentReserve: [PublicKey, number] | null = null;
+
+export function deriveRentReservePda(): [PublicKey, number] {
+  if (cachedRentReserve) return cachedRentReserve;
+
+  cachedRentReserve = PublicKey.findProgramAddressSync(
+    [encodeSeed(RENT_RESERVE_SEED)],
+    PROGRAM_ID
+  );
+
+  return cachedRentReserve;
+}
+
+/* ------------------------------------------------------------
+   EVENT AUTHORITY PDA: ["__event_authority"] (program built with "event-cpi")
+------------------------------------------------------------ */
+
+/**
+ * Программа собрана с cargo feature "event-cpi": инструкции с событиями
+ * принимают ещё два аккаунта в конце — event_authority и саму программу.
+ */
+export const EVENT_CPI = process.env.NEXT_PUBLIC_EVENT_CPI === "1";
+
+let cachedEventAuthority: [PublicKey, number] | null = null;
+
+export function deriveEventAuthorityPda(): [PublicKey, number] {
+  if (cachedEventAuthority) return cachedEventAuthority;
+
+  cachedEventAuthority = PublicKey.findProgramAddressSync(
+    [encodeSeed(EVENT_AUTHORITY_SEED)],
+    PROGRAM_ID
+  );
+
+  return cachedEventAuthority;
+}
+
+/* ------------------------------------------------------------
+   TREASURY VAULT PDA: ["treasury_vault_v1"] (used when cfg.use_treasury_vault)
+------------------------------------------------------------ */
+
+let cachedTreasuryVault: [PublicKey, number] | null = null;
+
+export function deriveTreasuryVaultPda(): [PublicKey, number] {
+  if (cachedTreasuryVault) return cachedTreasuryVault;
+
+  cachedTreasuryVault = PublicKey.findProgramAddressSync(
+    [encodeSeed(TREASURY_VAULT_SEED)],
+    PROGRAM_ID
+  );
+
+  return cachedTreasuryVault;
+}
+
+/* ------------------------------------------------------------
+   AUTHORITY LINK PDA: ["authority_link_v1", wallet]
+   rotate_authority: new wallet -> original Player PDA
+------------------------------------------------------------ */
+
+export function deriveAuthorityLinkPda(wallet: PublicKey): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [encodeSeed(AUTHORITY_LINK_SEED), wallet.toBuffer()],
+    PROGRAM_ID
+  );
+}
+
 /* ------------------------------------------------------------
    LEVEL STATE PDA: ["lvl", player_pda, [level]]
 ------------------------------------------------------------ */
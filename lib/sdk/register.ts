@@ -28,7 +28,10 @@ import {
   derivePlayerPda,
   deriveConfigPda,
   deriveGlobalStatsPda,
+  deriveStatsShardPda,
+  statsShardFor,
   deriveTxGuardRegister,
+  deriveAuthorityLinkPda,
 } from "./pda";
 
 import { sendTxWithPriority, waitForSignatureFinalized } from "./tx";
@@ -46,6 +49,13 @@ const REGISTER_DISC = Buffer.from([242, 146, 194, 234, 234, 145, 228, 42]);
    HELPERS
 ------------------------------------------------------------ */
 
+// ConfigV3.legacy_tx_guard (v3.13+): discr 8 + fixed fields 88 + tx_guard_close_delay 4
+const CFG_LEGACY_TX_GUARD_OFFSET = 100;
+
+function readConfigLegacyTxGuard(buf: Buffer): boolean {
+  return buf.length > CFG_LEGACY_TX_GUARD_OFFSET && buf[CFG_LEGACY_TX_GUARD_OFFSET] === 1;
+}
+
 function u64ToLeBuffer(n: bigint): Buffer {
   const buf = Buffer.alloc(8);
   buf.writeBigUInt64LE(n);
@@ -101,10 +111,16 @@ export async function createPlayer(
     const [playerPda] = derivePlayerPda(authority);
     const [configPda] = deriveConfigPda();
     const [statsPda] = deriveGlobalStatsPda();
+    const [statsShardPda] = deriveStatsShardPda(await statsShardFor(authority));
 
-    // nonce must be u64; Date.now ok for register (tx_guard uniqueness)
-    const nonce = BigInt(Date.now());
-    const [txGuardPda] = deriveTxGuardRegister(authority, nonce);
+    // Replay protection: a new Player starts at next_nonce 0. While ConfigV3.legacy_tx_guard
+    // is on, a fresh TxGuard PDA instead (Date.now keeps it unique); otherwise the program ID
+    // marks the optional tx_guard slot as None.
+    const cfgAcc = await connection.getAccountInfo(configPda, "confirmed");
+    if (!cfgAcc) throw new Error("ConfigV3 not found");
+    const legacyTxGuard = readConfigLegacyTxGuard(Buffer.from(cfgAcc.data));
+    const nonce = legacyTxGuard ? BigInt(Date.now()) : 0n;
+    const txGuardPda = legacyTxGuard ? deriveTxGuardRegister(authority, nonce)[0] : PROGRAM_ID;
 
     /* ----------------------------------------------------------
        REFERRER
@@ -142,20 +158,26 @@ export async function createPlayer(
       // 1 authority
       { pubkey: authority, isWritable: true, isSigner: true },
 
-      // 2 tx_guard
-      { pubkey: txGuardPda, isWritable: true, isSigner: false },
+      // 2 tx_guard (legacy only)
+      { pubkey: txGuardPda, isWritable: legacyTxGuard, isSigner: false },
 
-      // 3 config
-      { pubkey: configPda, isWritable: true, isSigner: false },
+      // 3 config (read-only)
+      { pubkey: configPda, isWritable: false, isSigner: false },
 
-      // 4 global_stats
-      { pubkey: statsPda, isWritable: true, isSigner: false },
+      // 4 global_stats (read-only: legacy orphan fallback)
+      { pubkey: statsPda, isWritable: false, isSigner: false },
 
-      // 5 referrer_player (ALWAYS PRESENT; readonly)
+      // 5 stats_shard (only write lock shared with other sign-ups)
+      { pubkey: statsShardPda, isWritable: true, isSigner: false },
+
+      // 6 referrer_player (ALWAYS PRESENT; readonly)
       { pubkey: referrerPlayerOrPlaceholder, isWritable: false, isSigner: false },
 
-      // 6 system_program
+      // 7 system_program
       { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+
+      // 8 authority_link (must not exist: rotated-to wallets can't register)
+      { pubkey: deriveAuthorityLinkPda(authority)[0], isWritable: false, isSigner: false },
     ];
 
     try {
@@ -9,6 +9,7 @@
 // ============================================================
 
 use anchor_lang::prelude::*;
+#[cfg(not(feature = "no-entrypoint"))]
 use solana_security_txt::security_txt;
 
 // ⚠️ Program ID ДОЛЖЕН совпадать с Anchor.toml и on-chain
@@ -33,7 +34,6 @@ mod processor;
 mod state;
 
 use processor::*;
-use state::*;
 
 // ============================================================
 // PROGRAM ROUTER (Anchor entrypoints)
@@ -81,6 +81,44 @@ pub mod solana_game {
         )
     }
 
+    pub fn migrate_config_v3(ctx: Context<MigrateConfigV3>) -> Result<()> {
+        handle_migrate_config_v3(ctx)
+    }
+
+    pub fn update_config_v3(ctx: Context<UpdateConfigV3>, args: ConfigV3Update) -> Result<()> {
+        handle_update_config_v3(ctx, args)
+    }
+
+    // --------------------------------------------------------
+    // TREASURY
+    // --------------------------------------------------------
+
+    pub fn init_treasury_vault(ctx: Context<InitTreasuryVault>) -> Result<()> {
+        handle_init_treasury_vault(ctx)
+    }
+
+    pub fn withdraw_treasury(
+        ctx: Context<WithdrawTreasury>,
+        amount: u64,
+        destination: Pubkey,
+    ) -> Result<()> {
+        handle_withdraw_treasury(ctx, amount, destination)
+    }
+
+    pub fn set_treasury_split(
+        ctx: Context<SetTreasurySplit>,
+        beneficiaries: Vec<Pubkey>,
+        bps: Vec<u16>,
+    ) -> Result<()> {
+        handle_set_treasury_split(ctx, beneficiaries, bps)
+    }
+
+    pub fn distribute_treasury<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DistributeTreasury<'info>>,
+    ) -> Result<()> {
+        handle_distribute_treasury(ctx)
+    }
+
     // --------------------------------------------------------
     // PLAYER
     // --------------------------------------------------------
@@ -100,6 +138,74 @@ pub mod solana_game {
         handle_register_player(ctx, referrer, nonce)
     }
 
+    pub fn register_and_activate<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RegisterAndActivate<'info>>,
+        referrer: Option<Pubkey>,
+        level: u8,
+        max_price_lamports: u64,
+        nonce: u64,
+    ) -> Result<()> {
+        handle_register_and_activate(ctx, referrer, level, max_price_lamports, nonce)
+    }
+
+    pub fn upgrade_player(ctx: Context<UpgradePlayer>) -> Result<()> {
+        handle_upgrade_player(ctx)
+    }
+
+    pub fn close_tx_guard(
+        ctx: Context<CloseTxGuard>,
+        kind: u8,
+        level: u8,
+        nonce: u64,
+    ) -> Result<()> {
+        handle_close_tx_guard(ctx, kind, level, nonce)
+    }
+
+    pub fn init_stats_shard(ctx: Context<InitStatsShard>, shard: u8) -> Result<()> {
+        handle_init_stats_shard(ctx, shard)
+    }
+
+    pub fn upgrade_level_pool(ctx: Context<UpgradeLevelPool>, level: u8) -> Result<()> {
+        handle_upgrade_level_pool(ctx, level)
+    }
+
+    pub fn upgrade_stats_shard(ctx: Context<UpgradeStatsShard>, shard: u8) -> Result<()> {
+        handle_upgrade_stats_shard(ctx, shard)
+    }
+
+    // --------------------------------------------------------
+    // AUTHORITY ROTATION / RECOVERY
+    // --------------------------------------------------------
+
+    pub fn rotate_authority<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RotateAuthority<'info>>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        handle_rotate_authority(ctx, new_authority)
+    }
+
+    pub fn set_guardian(
+        ctx: Context<SetGuardian>,
+        guardian: Option<Pubkey>,
+        delay: u32,
+    ) -> Result<()> {
+        handle_set_guardian(ctx, guardian, delay)
+    }
+
+    pub fn start_recovery(ctx: Context<StartRecovery>, new_authority: Pubkey) -> Result<()> {
+        handle_start_recovery(ctx, new_authority)
+    }
+
+    pub fn cancel_recovery(ctx: Context<CancelRecovery>) -> Result<()> {
+        handle_cancel_recovery(ctx)
+    }
+
+    pub fn complete_recovery<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CompleteRecovery<'info>>,
+    ) -> Result<()> {
+        handle_complete_recovery(ctx)
+    }
+
     // --------------------------------------------------------
     // LEVELS
     // --------------------------------------------------------
@@ -107,10 +213,55 @@ pub mod solana_game {
     pub fn activate_level_v3(
         ctx: Context<ActivateLevelV3>,
         level: u8,
-        price_lamports: u64,
+        max_price_lamports: u64,
+        nonce: u64,
+    ) -> Result<()> {
+        handle_activate_level_v3(ctx, level, max_price_lamports, nonce)
+    }
+
+    pub fn activate_level_usd(
+        ctx: Context<ActivateLevelV3>,
+        level: u8,
+        quoted_lamports: u64,
+        nonce: u64,
+        max_slippage_bps: u16,
+    ) -> Result<()> {
+        handle_activate_level_usd(ctx, level, quoted_lamports, nonce, max_slippage_bps)
+    }
+
+    pub fn activate_level_token(
+        ctx: Context<ActivateLevelToken>,
+        level: u8,
+        max_price: u64,
+        nonce: u64,
+    ) -> Result<()> {
+        handle_activate_level_token(ctx, level, max_price, nonce)
+    }
+
+    pub fn activate_levels_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ActivateLevelsBatch<'info>>,
+        levels: Vec<u8>,
+        max_prices: Vec<u64>,
+        nonce: u64,
+    ) -> Result<()> {
+        handle_activate_levels_batch(ctx, levels, max_prices, nonce)
+    }
+
+    pub fn activate_level_for(
+        ctx: Context<ActivateLevelFor>,
+        level: u8,
+        max_price_lamports: u64,
         nonce: u64,
     ) -> Result<()> {
-        handle_activate_level_v3(ctx, level, price_lamports, nonce)
+        handle_activate_level_for(ctx, level, max_price_lamports, nonce)
+    }
+
+    pub fn set_gift_opt_in(ctx: Context<SetGiftOptIn>, sponsor: Option<Pubkey>) -> Result<()> {
+        handle_set_gift_opt_in(ctx, sponsor)
+    }
+
+    pub fn revoke_gift_opt_in(ctx: Context<RevokeGiftOptIn>) -> Result<()> {
+        handle_revoke_gift_opt_in(ctx)
     }
 
     pub fn recycle_level_v3(
@@ -137,6 +288,30 @@ pub mod solana_game {
     pub fn dequeue_owner(ctx: Context<DequeueOwner>) -> Result<Pubkey> {
         handle_dequeue_owner(ctx)
     }
+
+    // --------------------------------------------------------
+    // VIEWS (simulateTransaction + return data)
+    // --------------------------------------------------------
+
+    pub fn get_queue_position(
+        ctx: Context<GetQueuePosition>,
+        level: u8,
+    ) -> Result<QueuePosition> {
+        handle_get_queue_position(ctx, level)
+    }
+
+    pub fn quote_activation(
+        ctx: Context<QuoteActivation>,
+        level: u8,
+    ) -> Result<ActivationQuote> {
+        handle_quote_activation(ctx, level)
+    }
+
+    pub fn get_total_players<'info>(
+        ctx: Context<'_, '_, 'info, 'info, GetTotalPlayers<'info>>,
+    ) -> Result<u64> {
+        handle_get_total_players(ctx)
+    }
 }
 
 // ============================================================
@@ -150,12 +325,66 @@ pub use state::{
     LevelState,
     TxGuard,
     GlobalStats,
+    StatsShard,
 };
 
 // Аккаунты очереди/пула реально объявлены в processor.rs (по rustc подсказке)
 pub use processor::{
     LevelPool,
     QueuePage,
+    TreasuryVault,
+    TreasurySplit,
+    AuthorityLink,
+    AuthorityRecovery,
+    GiftOptIn,
+};
+
+// Для off-chain клиентов (crates/solana_game_client): сиды, цены и декодеры старых layout'ов
+pub use processor::{
+    ConfigV3Update,
+    CustomError,
+    MAX_LEVEL,
+    STATS_SHARD_COUNT,
+    QUEUE_PAGE_CAPACITY_DEFAULT,
+    LEVEL_PRICES_LAMPORTS,
+    TX_GUARD_ACTIVATE,
+    TX_GUARD_RECYCLE,
+    RENT_RESERVE_SEED,
+    TREASURY_VAULT_SEED,
+    TREASURY_SPLIT_SEED,
+    AUTHORITY_LINK_SEED,
+    AUTHORITY_RECOVERY_SEED,
+    GIFT_OPT_IN_SEED,
+    MAX_TREASURY_BENEFICIARIES,
+    stats_shard_for,
+    expected_price_from_cfg,
+    usd_cents_to_lamports,
+    PythPrice,
+    decode_config_padded,
+    decode_player_padded,
+    decode_legacy_level_state,
+    ActivationQuote,
+    PayoutBreakdown,
+};
+
+// События — для декодирования off-chain (crates/solana_game_indexer)
+pub use processor::{
+    ConfigInitialized,
+    PlayerCreated,
+    LevelActivated,
+    LevelGifted,
+    LevelRecycled,
+    SlotsFilledN,
+    PayoutDistributed,
+    PayoutRedirect,
+    DequeuedOwner,
+    Enqueued,
+    QueuePageCreated,
+    TreasuryWithdrawn,
+    TreasuryDistributed,
+    AuthorityRotated,
+    RecoveryStarted,
+    EVENT_SCHEMA_VERSION,
 };
 
 // Anchor IDL hack — гарантирует, что аккаунты попадут в IDL
@@ -164,8 +393,14 @@ pub use processor::{
 #[account] pub struct _IncludeLevelState(pub LevelState);
 #[account] pub struct _IncludeTxGuard(pub TxGuard);
 #[account] pub struct _IncludeGlobalStats(pub GlobalStats);
+#[account] pub struct _IncludeStatsShard(pub StatsShard);
 #[account] pub struct _IncludeLevelPool(pub LevelPool);
 #[account] pub struct _IncludeQueuePage(pub QueuePage);
+#[account] pub struct _IncludeTreasuryVault(pub TreasuryVault);
+#[account] pub struct _IncludeTreasurySplit(pub TreasurySplit);
+#[account] pub struct _IncludeAuthorityLink(pub AuthorityLink);
+#[account] pub struct _IncludeAuthorityRecovery(pub AuthorityRecovery);
+#[account] pub struct _IncludeGiftOptIn(pub GiftOptIn);
 
 // ============================================================
 // EXPORT CONTEXTS TO IDL
@@ -180,4 +415,29 @@ pub use processor::{
     EnqueuePlayer,
     DequeueOwner,
     RegisterPlayer,
+    GetQueuePosition,
+    QuoteActivation,
+    InitStatsShard,
+    GetTotalPlayers,
+    MigrateConfigV3,
+    UpdateConfigV3,
+    CloseTxGuard,
+    UpgradePlayer,
+    InitTreasuryVault,
+    WithdrawTreasury,
+    SetTreasurySplit,
+    DistributeTreasury,
+    RotateAuthority,
+    SetGuardian,
+    StartRecovery,
+    CancelRecovery,
+    CompleteRecovery,
+    ActivateLevelFor,
+    SetGiftOptIn,
+    RevokeGiftOptIn,
+    RegisterAndActivate,
+    ActivateLevelsBatch,
+    ActivateLevelToken,
+    UpgradeLevelPool,
+    UpgradeStatsShard,
 };
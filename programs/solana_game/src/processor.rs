@@ -16,10 +16,27 @@
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
 use anchor_lang::{
-    solana_program::{program::invoke_signed, system_instruction},
+    solana_program::{program::invoke_signed, program_pack::Pack, system_instruction},
     AccountDeserialize, AccountSerialize,
 };
+use anchor_spl::associated_token::{
+    self, get_associated_token_address_with_program_id, AssociatedToken,
+};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{
+        transfer_fee::{TransferFee, TransferFeeConfig},
+        BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+    },
+};
+use anchor_spl::token_interface::{
+    self, get_mint_extension_data, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+use solana_sha256_hasher::hash;
+use std::collections::BTreeSet;
 use std::io::Cursor;
+#[cfg(not(feature = "event-cpi"))]
+use std::marker::PhantomData;
 
 // =============================================================
 // 1) ERRORS
@@ -63,23 +80,84 @@ pub enum CustomError {
     RecipientMustBeSystemWallet,
     #[msg("Rollover requires more than one new page (not supported in one tx)")]
     RolloverNeedsSecondNewPage,
+    #[msg("Player is not in queue")]
+    NotInQueue,
+    #[msg("Invalid stats shard")]
+    InvalidStatsShard,
+    #[msg("TxGuard is too young to close")]
+    TxGuardTooYoung,
+    #[msg("Invalid TxGuard kind")]
+    InvalidTxGuardKind,
+    #[msg("Stale nonce (must equal Player.next_nonce)")]
+    StaleNonce,
+    #[msg("TxGuard replay protection is disabled; use Player.next_nonce")]
+    LegacyTxGuardDisabled,
+    #[msg("Rent reserve cannot cover bookkeeping rent")]
+    RentReserveInsufficient,
+    #[msg("Invalid treasury account")]
+    InvalidTreasury,
+    #[msg("Treasury withdrawal exceeds the per-epoch limit")]
+    TreasuryEpochLimitExceeded,
+    #[msg("Treasury vault balance too low")]
+    InsufficientTreasuryFunds,
+    #[msg("Treasury split needs 1-8 beneficiaries with bps summing to 10000")]
+    InvalidTreasurySplit,
+    #[msg("Invalid new authority")]
+    InvalidNewAuthority,
+    #[msg("No authority recovery pending")]
+    RecoveryNotPending,
+    #[msg("Authority recovery is still timelocked")]
+    RecoveryTimelocked,
+    #[msg("Beneficiary must co-sign or opt in to gifts from this payer")]
+    GiftNotAccepted,
+    #[msg("Invalid batch: 1..=MAX_BATCH_LEVELS distinct levels, one account group each")]
+    InvalidBatch,
+    #[msg("Previous level must be active first")]
+    PreviousLevelRequired,
+    #[msg("Wrong payment mode for this config (lamports vs SPL token)")]
+    WrongPaymentMode,
+    #[msg("Token account is not the recipient's associated token account")]
+    InvalidTokenAccount,
+    #[msg("Mint transfer fees exceed the treasury share")]
+    TransferFeeExceedsTreasuryShare,
+    #[msg("Price feed account missing or invalid")]
+    InvalidPriceFeed,
+    #[msg("Price feed is stale")]
+    StalePriceFeed,
+    #[msg("Price feed confidence interval too wide")]
+    PriceFeedConfidenceTooWide,
+    #[msg("Level price is above the caller's maximum")]
+    PriceSlippageExceeded,
+    #[msg("Wallet is linked to a Player by rotate_authority")]
+    WalletLinkedToPlayer,
+    #[msg("Payment currency can only change before the first activation (pass all 16 LevelPools)")]
+    PaymentModeLocked,
+    #[msg("Pass the Player's LevelState PDAs of all 16 levels, in level order")]
+    LevelStatesIncomplete,
 }
 
 // =============================================================
 // 2) EVENTS
 // =============================================================
+// Every event ends with `schema_version` (EVENT_SCHEMA_VERSION). Level events carry the
+// LevelPool's `event_seq`, PlayerCreated its StatsShard's: consecutive from 1 per stream,
+// so an indexer can prove it has every event of a queue / shard.
 
 #[event]
 pub struct ConfigInitialized {
     pub admin: Pubkey,
     pub treasury: Pubkey,
     pub timestamp: i64,
+    pub schema_version: u8,
 }
 
 #[event]
 pub struct PlayerCreated {
     pub authority: Pubkey,
     pub timestamp: i64,
+    pub schema_version: u8,
+    pub shard: u8,
+    pub event_seq: u64,
 }
 
 #[event]
@@ -88,6 +166,24 @@ pub struct LevelActivated {
     pub level: u8,
     pub lamports: u64,
     pub timestamp: i64,
+    // Price actually charged (lamports, or token units in token mode)
+    pub actual_price: u64,
+    pub schema_version: u8,
+    pub event_seq: u64,
+    // v2+: caller's bound (max_price_lamports / max_price / quoted + slippage)
+    pub max_price: u64,
+}
+
+#[event]
+pub struct LevelGifted {
+    pub payer: Pubkey,
+    pub beneficiary: Pubkey,
+    pub player: Pubkey,
+    pub level: u8,
+    pub lamports: u64,
+    pub timestamp: i64,
+    pub schema_version: u8,
+    pub event_seq: u64,
 }
 
 #[event]
@@ -97,6 +193,8 @@ pub struct LevelRecycled {
     pub cycles: u64,
     pub lamports: u64,
     pub timestamp: i64,
+    pub schema_version: u8,
+    pub event_seq: u64,
 }
 
 #[event]
@@ -105,6 +203,31 @@ pub struct SlotsFilledN {
     pub level: u8,
     pub cycles: u64,
     pub filled: u8,
+    pub schema_version: u8,
+    pub event_seq: u64,
+}
+
+// Every payout of one activation. First activation (owner = default) and self-owner
+// activation: 100% -> treasury. `refs` are the wallets paid, or the upline whose share
+// was redirected to the treasury (`redirect_reasons`). `treasury_amount` is the treasury
+// share after Token-2022 fees; in lamports mode `bookkeeping_rent` is netted out of it.
+#[event]
+pub struct PayoutDistributed {
+    pub activator: Pubkey,
+    pub player: Pubkey,
+    pub level: u8,
+    pub owner: Pubkey,
+    pub owner_amount: u64,
+    pub refs: [Pubkey; 3],
+    pub ref_amounts: [u64; 3],
+    pub redirected_amounts: [u64; 3],
+    pub redirect_reasons: [PayoutRedirect; 3],
+    pub treasury_amount: u64,
+    pub bookkeeping_rent: u64,
+    pub is_self_owner: bool,
+    pub timestamp: i64,
+    pub schema_version: u8,
+    pub event_seq: u64,
 }
 
 #[event]
@@ -112,6 +235,8 @@ pub struct DequeuedOwner {
     pub owner_player: Pubkey,
     pub level: u8,
     pub page_index: u32,
+    pub schema_version: u8,
+    pub event_seq: u64,
 }
 
 #[event]
@@ -120,6 +245,8 @@ pub struct Enqueued {
     pub level: u8,
     pub page_index: u32,
     pub position_in_page: u32,
+    pub schema_version: u8,
+    pub event_seq: u64,
 }
 
 #[event]
@@ -128,6 +255,45 @@ pub struct QueuePageCreated {
     pub pool: Pubkey,
     pub page_index: u32,
     pub page: Pubkey,
+    pub schema_version: u8,
+    pub event_seq: u64,
+}
+
+#[event]
+pub struct TreasuryWithdrawn {
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub epoch: u64,
+    pub withdrawn_in_epoch: u64,
+    pub timestamp: i64,
+    pub schema_version: u8,
+}
+
+#[event]
+pub struct TreasuryDistributed {
+    pub amount: u64,
+    pub beneficiaries: u8,
+    pub timestamp: i64,
+    pub schema_version: u8,
+}
+
+#[event]
+pub struct AuthorityRotated {
+    pub player: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub by_guardian: bool,
+    pub timestamp: i64,
+    pub schema_version: u8,
+}
+
+#[event]
+pub struct RecoveryStarted {
+    pub player: Pubkey,
+    pub guardian: Pubkey,
+    pub new_authority: Pubkey,
+    pub unlock_at: i64,
+    pub schema_version: u8,
 }
 
 // =============================================================
@@ -152,10 +318,66 @@ pub struct ConfigV3 {
     pub bump: u8,
     pub version: u8,
     pub version_minor: u8,
+    // v3.13+: fields below are appended; migrate_config_v3 grows older accounts
+    pub tx_guard_close_delay: u32,
+    // Transition window: still accept TxGuard PDAs instead of Player.next_nonce
+    pub legacy_tx_guard: bool,
+    // v3.15+: treasury share goes to the TreasuryVault PDA instead of the `treasury` wallet
+    pub use_treasury_vault: bool,
+    // Max lamports withdraw_treasury may move out of the vault per epoch (0 = frozen)
+    pub treasury_epoch_limit: u64,
+    // v3.16+: register_and_activate may skip min_entry_delay for that first activation
+    pub first_activation_delay_exempt: bool,
+    // v3.17+: level N needs the player's level N-1 to be active
+    pub require_sequential_levels: bool,
+    // v3.18+: levels are priced and paid in this SPL mint (default = lamports)
+    pub payment_mint: Pubkey,
+    // Level prices in payment_mint base units (token mode only)
+    pub token_level_prices: [u64; 16],
+    // v3.19+: lamport prices follow USD via this Pyth PriceUpdateV2 (SOL/USD) account
+    // (default = fixed LEVEL_PRICES_LAMPORTS)
+    pub usd_price_feed: Pubkey,
+    pub usd_level_prices_cents: [u32; 16],
+    // Max age of the feed's publish_time, seconds
+    pub usd_feed_max_age: u32,
+    // Max conf / price, basis points
+    pub usd_feed_max_conf_bps: u16,
+    // v3.20+: expected Pyth feed id of usd_price_feed (SOL/USD)
+    pub usd_feed_id: [u8; 32],
 }
 
 impl ConfigV3 {
-    pub const SIZE: usize = 32 + 32 + 5 + 8 + 1 + 4 + 1 + 1 + 1 + 1 + 1 + 1 + 1;
+    pub const SIZE: usize = 32 + 32 + 5 + 8 + 1 + 4 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 4 + 1 + 1 + 8 + 1 + 1
+        + 32 + 8 * 16 + 32 + 4 * 16 + 4 + 2 + 32;
+
+    pub fn token_mode(&self) -> bool {
+        self.payment_mint != Pubkey::default()
+    }
+
+    pub fn usd_mode(&self) -> bool {
+        self.usd_price_feed != Pubkey::default()
+    }
+}
+
+// Admin config update: None = keep current value
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct ConfigV3Update {
+    pub tx_guard_close_delay: Option<u32>,
+    pub legacy_tx_guard: Option<bool>,
+    pub use_treasury_vault: Option<bool>,
+    pub treasury_epoch_limit: Option<u64>,
+    pub first_activation_delay_exempt: Option<bool>,
+    pub require_sequential_levels: Option<bool>,
+    // payment_mint / token_level_prices / usd_price_feed change only while no level has a
+    // queue (queues don't track the currency): remaining_accounts = the 16 LevelPool PDAs
+    pub payment_mint: Option<Pubkey>,
+    pub token_level_prices: Option<[u64; 16]>,
+    // Not combinable with payment_mint
+    pub usd_price_feed: Option<Pubkey>,
+    pub usd_level_prices_cents: Option<[u32; 16]>,
+    pub usd_feed_max_age: Option<u32>,
+    pub usd_feed_max_conf_bps: Option<u16>,
+    pub usd_feed_id: Option<[u8; 32]>,
 }
 
 #[account]
@@ -164,13 +386,16 @@ pub struct Player {
     pub bump: u8,
     pub created_at: i64,
     pub games_played: u64,
+    // Referrer Player PDAs (orphans and older sign-ups: the upline's seed wallet)
     pub upline1: Pubkey,
     pub upline2: Pubkey,
     pub upline3: Pubkey,
+    // v3.14+: monotonic replay-protection counter (upgrade_player grows older accounts)
+    pub next_nonce: u64,
 }
 
 impl Player {
-    pub const SIZE: usize = 32 + 1 + 8 + 8 + (32 * 3);
+    pub const SIZE: usize = 32 + 1 + 8 + 8 + (32 * 3) + 8;
 }
 
 #[account]
@@ -184,10 +409,14 @@ pub struct LevelState {
     pub slots_filled: u64,
     pub head_page: Option<Pubkey>,
     pub tail_page: Option<Pubkey>,
+    // Queue membership: page the player currently sits in (None = not queued)
+    // and absolute enqueue sequence number within the LevelPool.
+    pub queue_page: Option<Pubkey>,
+    pub queue_pos: u64,
 }
 
 impl LevelState {
-    pub const SIZE: usize = 32 + 32 + 1 + 1 + 8 + 8 + 8 + (1 + 32) + (1 + 32);
+    pub const SIZE: usize = 32 + 32 + 1 + 1 + 8 + 8 + 8 + (1 + 32) + (1 + 32) + (1 + 32) + 8;
 }
 
 #[account]
@@ -212,6 +441,89 @@ impl GlobalStats {
     pub const SIZE: usize = 8 + 32 + 1;
 }
 
+// Wallet -> Player PDA after rotate_authority (the PDA stays seeded by the original wallet).
+// Never closed: a wallet rotated away from stays reserved and can't register a Player.
+#[account]
+pub struct AuthorityLink {
+    pub player: Pubkey,
+    pub bump: u8,
+}
+
+impl AuthorityLink {
+    pub const SIZE: usize = 32 + 1;
+}
+
+// Optional guardian that can move a Player to a new wallet after `delay` seconds,
+// unless the current authority cancels first.
+#[account]
+pub struct AuthorityRecovery {
+    pub player: Pubkey,
+    pub guardian: Pubkey,
+    pub delay: u32,
+    pub pending_authority: Pubkey,
+    pub unlock_at: i64,
+    pub bump: u8,
+}
+
+impl AuthorityRecovery {
+    pub const SIZE: usize = 32 + 32 + 4 + 32 + 8 + 1;
+}
+
+// Standing consent to activate_level_for: `sponsor` may gift levels (default = anyone).
+#[account]
+pub struct GiftOptIn {
+    pub player: Pubkey,
+    pub sponsor: Pubkey,
+    pub bump: u8,
+}
+
+impl GiftOptIn {
+    pub const SIZE: usize = 32 + 32 + 1;
+}
+
+// Per-shard registration counters: sign-ups only write the shard picked by
+// hashing the authority, so registrations no longer serialise on GlobalStats.
+#[account]
+pub struct StatsShard {
+    pub shard: u8,
+    pub total_players: u64,
+    pub last_player: Pubkey,
+    pub bump: u8,
+    // Last event_seq of this shard's PlayerCreated (older shards: grown on the next sign-up)
+    pub event_seq: u64,
+}
+
+impl StatsShard {
+    pub const SIZE: usize = 1 + 8 + 32 + 1 + 8;
+}
+
+// Program-owned treasury: holds the treasury share as lamports on the account itself.
+#[account]
+pub struct TreasuryVault {
+    pub bump: u8,
+    pub epoch: u64,
+    pub withdrawn_in_epoch: u64,
+    pub total_withdrawn: u64,
+}
+
+impl TreasuryVault {
+    pub const SIZE: usize = 1 + 8 + 8 + 8;
+}
+
+// Beneficiaries of the vault balance, paid out by the permissionless distribute_treasury crank.
+#[account]
+pub struct TreasurySplit {
+    pub bump: u8,
+    pub count: u8,
+    pub beneficiaries: [Pubkey; MAX_TREASURY_BENEFICIARIES],
+    pub bps: [u16; MAX_TREASURY_BENEFICIARIES],
+    pub total_distributed: u64,
+}
+
+impl TreasurySplit {
+    pub const SIZE: usize = 1 + 1 + 32 * MAX_TREASURY_BENEFICIARIES + 2 * MAX_TREASURY_BENEFICIARIES + 8;
+}
+
 #[account]
 pub struct LevelPool {
     pub config: Pubkey,
@@ -221,10 +533,12 @@ pub struct LevelPool {
     pub tail_page: Option<Pubkey>,
     pub total_enqueued: u64,
     pub total_dequeued: u64,
+    // Last event_seq of this level's events (older pools: grown on the next activation)
+    pub event_seq: u64,
 }
 
 impl LevelPool {
-    pub const SIZE: usize = 32 + 1 + 1 + 33 + 33 + 8 + 8;
+    pub const SIZE: usize = 32 + 1 + 1 + 33 + 33 + 8 + 8 + 8;
 }
 
 // =============================================================
@@ -260,7 +574,11 @@ impl QueuePage {
 // 5) CONSTANTS + HELPERS
 // =============================================================
 
+pub const CONFIG_VERSION_MINOR: u8 = 20;
+pub const EVENT_SCHEMA_VERSION: u8 = 2;
+
 pub const MAX_LEVEL: u8 = 16;
+pub const STATS_SHARD_COUNT: u8 = 16;
 pub const QUEUE_PAGE_CAPACITY_DEFAULT: usize = 64;
 
 pub const LEVEL_PRICES_LAMPORTS: [u64; 16] = [
@@ -282,6 +600,50 @@ pub const LEVEL_PRICES_LAMPORTS: [u64; 16] = [
     8_700_000_000,
 ];
 
+// TxGuard kinds (seed layout) for close_tx_guard
+pub const TX_GUARD_ACTIVATE: u8 = 1;
+pub const TX_GUARD_RECYCLE: u8 = 2;
+pub const TX_GUARD_REGISTER: u8 = 3;
+
+// Floor for cfg.tx_guard_close_delay: well past blockhash expiry, so a closed
+// guard can never be raced by the original (still-valid) transaction.
+pub const MIN_TX_GUARD_CLOSE_DELAY: u32 = 600;
+
+// System-owned PDA (no data) holding lamports the treasury sets aside for
+// bookkeeping rent that the treasury share of a single activation can't cover.
+pub const RENT_RESERVE_SEED: &[u8] = b"rent_reserve_v1";
+
+pub const TREASURY_VAULT_SEED: &[u8] = b"treasury_vault_v1";
+pub const TREASURY_SPLIT_SEED: &[u8] = b"treasury_split_v1";
+pub const MAX_TREASURY_BENEFICIARIES: usize = 8;
+pub const BPS_TOTAL: u64 = 10_000;
+
+pub const AUTHORITY_LINK_SEED: &[u8] = b"authority_link_v1";
+pub const AUTHORITY_RECOVERY_SEED: &[u8] = b"authority_recovery_v1";
+// Floor for AuthorityRecovery.delay: time for the current authority to cancel
+pub const MIN_RECOVERY_DELAY: u32 = 3 * 24 * 60 * 60;
+
+pub const GIFT_OPT_IN_SEED: &[u8] = b"gift_opt_in_v1";
+
+// activate_levels_batch: bound by the 64 account locks per tx (8 shared + 13 unique accounts
+// per level in the common case). Compute is checked per batch by simulation, see
+// docs/BATCH_ACTIVATION_CU.md.
+pub const MAX_TX_ACCOUNT_LOCKS: usize = 64;
+pub const BATCH_SHARED_ACCOUNTS: usize = 8;
+pub const BATCH_ACCOUNTS_PER_LEVEL: usize = 13;
+pub const MAX_BATCH_LEVELS: usize =
+    (MAX_TX_ACCOUNT_LOCKS - BATCH_SHARED_ACCOUNTS) / BATCH_ACCOUNTS_PER_LEVEL;
+
+// Pyth pull-oracle receiver (owner of PriceUpdateV2 accounts)
+pub const PYTH_RECEIVER_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+pub const DEFAULT_USD_FEED_MAX_AGE: u32 = 60;
+pub const DEFAULT_USD_FEED_MAX_CONF_BPS: u16 = 200;
+// Pyth SOL/USD feed id
+pub const PYTH_SOL_USD_FEED_ID: [u8; 32] = [
+    0xef, 0x0d, 0x8b, 0x6f, 0xda, 0x2c, 0xeb, 0xa4, 0x1d, 0xa1, 0x5d, 0x40, 0x95, 0xd1, 0xda, 0x39,
+    0x2a, 0x0d, 0x2f, 0x8e, 0xd0, 0xc6, 0xc7, 0xbc, 0x0f, 0x4c, 0xfa, 0xc8, 0xc2, 0x80, 0xb5, 0x6d,
+];
+
 pub const PERC_OWNER: u64 = 60;
 pub const PERC_REF1: u64 = 13;
 pub const PERC_REF2: u64 = 8;
@@ -289,18 +651,162 @@ pub const PERC_REF3: u64 = 5;
 pub const PERC_TREASURY_BASE: u64 = 14;
 pub const PERC_TOTAL: u64 = 100;
 
-pub fn expected_price_from_cfg(level: u8, _cfg: &ConfigV3) -> u64 {
+pub fn expected_price_from_cfg(level: u8, cfg: &ConfigV3) -> u64 {
     if level == 0 || level > MAX_LEVEL {
         return 0;
     }
+    if cfg.token_mode() {
+        return cfg.token_level_prices[(level - 1) as usize];
+    }
     LEVEL_PRICES_LAMPORTS[(level - 1) as usize]
 }
 
+// Current level price: fixed table / token prices, or USD cents converted through the
+// configured feed (which must then be passed).
+fn level_price(level: u8, cfg: &ConfigV3, price_feed: Option<&AccountInfo>) -> Result<u64> {
+    if !cfg.usd_mode() || level == 0 || level > MAX_LEVEL {
+        return Ok(expected_price_from_cfg(level, cfg));
+    }
+    let feed = price_feed.ok_or(CustomError::InvalidPriceFeed)?;
+    require_keys_eq!(*feed.key, cfg.usd_price_feed, CustomError::InvalidPriceFeed);
+    let sol_usd = read_pyth_price(feed, cfg, Clock::get()?.unix_timestamp)?;
+    usd_cents_to_lamports(cfg.usd_level_prices_cents[(level - 1) as usize], &sol_usd)
+}
+
+pub struct PythPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+}
+
+// Pyth PriceUpdateV2 (pyth-solana-receiver): discriminator(8) write_authority(32)
+// verification_level(1, Full only) feed_id(32) price(8) conf(8) exponent(4) publish_time(8) ...
+const PYTH_PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+const PYTH_VERIFICATION_FULL: u8 = 1;
+
+fn read_pyth_price(feed: &AccountInfo, cfg: &ConfigV3, now: i64) -> Result<PythPrice> {
+    require_keys_eq!(*feed.owner, PYTH_RECEIVER_ID, CustomError::InvalidPriceFeed);
+    let data = feed.try_borrow_data()?;
+    require!(
+        data.len() >= 101
+            && data[..8] == PYTH_PRICE_UPDATE_V2_DISCRIMINATOR
+            && data[40] == PYTH_VERIFICATION_FULL,
+        CustomError::InvalidPriceFeed
+    );
+    require!(data[41..73] == cfg.usd_feed_id, CustomError::InvalidPriceFeed);
+
+    let field = |at: usize| -> [u8; 8] { data[at..at + 8].try_into().unwrap() };
+    let price = PythPrice {
+        price: i64::from_le_bytes(field(73)),
+        conf: u64::from_le_bytes(field(81)),
+        exponent: i32::from_le_bytes(data[89..93].try_into().unwrap()),
+        publish_time: i64::from_le_bytes(field(93)),
+    };
+
+    require!(price.price > 0, CustomError::InvalidPriceFeed);
+    require!(price.publish_time <= now, CustomError::InvalidPriceFeed);
+    require!(
+        now - price.publish_time <= cfg.usd_feed_max_age as i64,
+        CustomError::StalePriceFeed
+    );
+    require!(
+        (price.conf as u128) * (BPS_TOTAL as u128)
+            <= (price.price as u128) * (cfg.usd_feed_max_conf_bps as u128),
+        CustomError::PriceFeedConfidenceTooWide
+    );
+    Ok(price)
+}
+
+// lamports = cents * 10^7 / (price * 10^exponent), rounded up
+pub fn usd_cents_to_lamports(cents: u32, sol_usd: &PythPrice) -> Result<u64> {
+    require!(cents > 0, CustomError::InvalidPrice);
+    let scale = |e: u32| 10u128.checked_pow(e).ok_or(CustomError::Overflow);
+    let (num, den) = if sol_usd.exponent < 0 {
+        (
+            (cents as u128) * 10_000_000 * scale(sol_usd.exponent.unsigned_abs())?,
+            sol_usd.price as u128,
+        )
+    } else {
+        (
+            (cents as u128) * 10_000_000,
+            (sol_usd.price as u128) * scale(sol_usd.exponent as u32)?,
+        )
+    };
+    let lamports = num.div_ceil(den);
+    u64::try_from(lamports).map_err(|_| error!(CustomError::Overflow))
+}
+
+pub fn stats_shard_for(authority: &Pubkey) -> u8 {
+    hash(authority.as_ref()).to_bytes()[0] % STATS_SHARD_COUNT
+}
+
 #[inline(always)]
 fn calc_share(price: u64, perc: u64) -> u64 {
     price.saturating_mul(perc) / 100
 }
 
+// Where events go: program logs (emit!), or with the "event-cpi" feature anchor's emit_cpi!
+// self-CPI signed by the #[event_cpi] event authority. Inner instruction data is never
+// truncated like logs. The sink carries the `ctx.accounts` / `ctx.bumps` shape emit_cpi!
+// reads, so helpers below the handlers can emit without the whole Context.
+#[derive(Clone)]
+struct EventSink<'info> {
+    #[cfg(feature = "event-cpi")]
+    accounts: EventCpiAccounts<'info>,
+    #[cfg(feature = "event-cpi")]
+    bumps: EventCpiBumps,
+    #[cfg(not(feature = "event-cpi"))]
+    _info: PhantomData<&'info ()>,
+}
+
+#[cfg(feature = "event-cpi")]
+#[derive(Clone)]
+struct EventCpiAccounts<'info> {
+    event_authority: AccountInfo<'info>,
+}
+
+#[cfg(feature = "event-cpi")]
+#[derive(Clone)]
+struct EventCpiBumps {
+    event_authority: u8,
+}
+
+// Advances a LevelPool / StatsShard event stream; returns the new event's event_seq.
+fn next_event_seq(seq: &mut u64) -> Result<u64> {
+    *seq = seq.checked_add(1).ok_or(CustomError::Overflow)?;
+    Ok(*seq)
+}
+
+// EventSink of an #[event_cpi] context: event_sink!(ctx.accounts, ctx.bumps)
+macro_rules! event_sink {
+    ($accounts:expr, $bumps:expr) => {
+        EventSink {
+            #[cfg(feature = "event-cpi")]
+            accounts: EventCpiAccounts {
+                event_authority: $accounts.event_authority.to_account_info(),
+            },
+            #[cfg(feature = "event-cpi")]
+            bumps: EventCpiBumps { event_authority: $bumps.event_authority },
+            #[cfg(not(feature = "event-cpi"))]
+            _info: PhantomData,
+        }
+    };
+}
+
+impl EventSink<'_> {
+    fn emit<E: anchor_lang::Event>(&self, event: E) -> Result<()> {
+        #[cfg(feature = "event-cpi")]
+        {
+            let ctx = self;
+            emit_cpi!(event);
+        }
+        #[cfg(not(feature = "event-cpi"))]
+        emit!(event);
+        Ok(())
+    }
+}
+
 // MAINNET SAFE: recipient must be a wallet (SystemProgram owner + no data)
 fn require_system_wallet(ai: &AccountInfo) -> Result<()> {
     require!(
@@ -310,6 +816,18 @@ fn require_system_wallet(ai: &AccountInfo) -> Result<()> {
     Ok(())
 }
 
+// Treasury destination: the vault PDA when cfg.use_treasury_vault, else the cfg.treasury wallet.
+fn require_treasury_account(cfg: &ConfigV3, ai: &AccountInfo) -> Result<()> {
+    if cfg.use_treasury_vault {
+        let (vault, _) = Pubkey::find_program_address(&[TREASURY_VAULT_SEED], &crate::ID);
+        require_keys_eq!(*ai.key, vault, CustomError::InvalidTreasury);
+        require_keys_eq!(*ai.owner, crate::ID, CustomError::InvalidTreasury);
+        return Ok(());
+    }
+    require_keys_eq!(*ai.key, cfg.treasury, CustomError::InvalidTreasury);
+    require_system_wallet(ai)
+}
+
 // SAFE: transfer only from authority (Signer wallet)
 pub fn safe_transfer<'info>(
     from: &AccountInfo<'info>,
@@ -330,7 +848,7 @@ pub fn safe_transfer<'info>(
 pub fn read_queue_page(ai: &AccountInfo) -> Result<QueuePage> {
     let data = ai.try_borrow_data()?;
     let mut slice: &[u8] = &data;
-    Ok(QueuePage::try_deserialize(&mut slice)?)
+    QueuePage::try_deserialize(&mut slice)
 }
 
 pub fn write_queue_page(ai: &AccountInfo, page: &mut QueuePage) -> Result<()> {
@@ -340,117 +858,629 @@ pub fn write_queue_page(ai: &AccountInfo, page: &mut QueuePage) -> Result<()> {
     Ok(())
 }
 
-fn derive_level_state_pda(player_pda: &Pubkey, level: u8) -> (Pubkey, u8) {
-    Pubkey::find_program_address(&[b"lvl", player_pda.as_ref(), &[level]], &crate::ID)
+// Lamports needed to keep `ai` rent-exempt once grown to `space` bytes (0 if already large enough).
+fn realloc_top_up(ai: &AccountInfo, space: usize) -> Result<u64> {
+    if ai.data_len() >= space {
+        return Ok(0);
+    }
+    let required = Rent::get()?.minimum_balance(space);
+    Ok(required.saturating_sub(ai.lamports()))
 }
 
-fn read_player(ai: &AccountInfo) -> Result<Player> {
-    let data = ai.try_borrow_data()?;
-    let mut slice: &[u8] = &data;
-    let player = Player::try_deserialize(&mut slice)
-        .map_err(|_| error!(CustomError::AccountCastError))?;
-    Ok(player)
+// Grows a program-owned account to `space` bytes (zero-filled), rent top-up from payer.
+fn realloc_if_needed<'info>(
+    ai: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    sys: &AccountInfo<'info>,
+    space: usize,
+) -> Result<()> {
+    if ai.data_len() >= space {
+        return Ok(());
+    }
+    let top_up = realloc_top_up(ai, space)?;
+    safe_transfer(payer, ai, sys, top_up)?;
+    ai.resize(space)?;
+    Ok(())
 }
 
-fn is_level_open_for<'info>(
-    upline_wallet: &Pubkey,
-    level: u8,
-    level_state_ai: &AccountInfo<'info>,
-) -> Result<bool> {
-    let (player_pda, _) =
-        Pubkey::find_program_address(&[b"player", upline_wallet.as_ref()], &crate::ID);
-    let (expected_pda, _) = derive_level_state_pda(&player_pda, level);
-
-    if level_state_ai.key != &expected_pda {
-        return Ok(false);
-    }
-    if level_state_ai.owner != &crate::ID {
-        return Ok(false);
+// Program account at a (system-owned, empty) PDA. Like Anchor's init, lamports sent to the
+// address beforehand don't block creation.
+fn create_pda_account<'info>(
+    ai: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    sys: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    if ai.lamports() == 0 {
+        let ix =
+            system_instruction::create_account(payer.key, ai.key, rent, space as u64, &crate::ID);
+        invoke_signed(&ix, &[payer.clone(), ai.clone(), sys.clone()], &[seeds])?;
+        return Ok(());
     }
-    if level_state_ai.data_is_empty() {
-        return Ok(false);
+    safe_transfer(payer, ai, sys, rent.saturating_sub(ai.lamports()))?;
+    invoke_signed(
+        &system_instruction::allocate(ai.key, space as u64),
+        &[ai.clone(), sys.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(ai.key, &crate::ID),
+        &[ai.clone(), sys.clone()],
+        &[seeds],
+    )?;
+    Ok(())
+}
+
+// Bookkeeping rent (owner LevelState, queue pages, LevelPool; recipient ATAs in token
+// mode) is fronted by the activator inside the same tx. Lamports mode settles it so they
+// pay exactly the level price: netted out of the treasury share, the remainder refunded
+// from the rent reserve. Token mode can't net SOL rent out of a token share: the reserve
+// refunds what it can, the activator bears the rest.
+// Shared by settle_activation and quote_activation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct RentSettlement {
+    from_treasury_share: u64,
+    from_reserve: u64,
+    payer_bears: u64,
+}
+
+fn plan_rent_settlement(
+    token_mode: bool,
+    treasury_amount: u64,
+    bookkeeping_rent: u64,
+    reserve_available: Option<u64>,
+) -> Result<RentSettlement> {
+    if token_mode {
+        let from_reserve = bookkeeping_rent.min(reserve_available.unwrap_or(0));
+        return Ok(RentSettlement {
+            from_treasury_share: 0,
+            from_reserve,
+            payer_bears: bookkeeping_rent - from_reserve,
+        });
     }
 
-    let data = level_state_ai.try_borrow_data()?;
-    let mut slice: &[u8] = &data;
-    let ls = LevelState::try_deserialize(&mut slice)
-        .map_err(|_| error!(CustomError::AccountCastError))?;
+    let from_treasury_share = bookkeeping_rent.min(treasury_amount);
+    let from_reserve = bookkeeping_rent - from_treasury_share;
+    require!(
+        from_reserve == 0 || reserve_available.is_some_and(|a| a >= from_reserve),
+        CustomError::RentReserveInsufficient
+    );
+    Ok(RentSettlement {
+        from_treasury_share,
+        from_reserve,
+        payer_bears: 0,
+    })
+}
 
-    Ok(ls.level == level && ls.activated_at > 0 && ls.player == player_pda)
+// The reserve itself must stay rent-exempt
+fn rent_reserve_available(reserve: &AccountInfo) -> Result<u64> {
+    Ok(reserve
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(0)))
 }
 
-fn resolve_ref_with_level_or_admin<'info>(
-    upline_key: &Pubkey,
-    level: u8,
-    level_state_ai_opt: Option<&AccountInfo<'info>>,
-    cfg_admin: &Pubkey,
-) -> Pubkey {
-    if let Some(ai) = level_state_ai_opt {
-        if let Ok(true) = is_level_open_for(upline_key, level, ai) {
-            return *upline_key;
-        }
+fn refund_from_rent_reserve<'info>(
+    payer: &AccountInfo<'info>,
+    reserve: &AccountInfo<'info>,
+    bump: u8,
+    sys: &AccountInfo<'info>,
+    lamports: u64,
+) -> Result<()> {
+    if lamports == 0 {
+        return Ok(());
     }
-    *cfg_admin
+    invoke_signed(
+        &system_instruction::transfer(reserve.key, payer.key, lamports),
+        &[reserve.clone(), payer.clone(), sys.clone()],
+        &[&[RENT_RESERVE_SEED, &[bump]]],
+    )?;
+    Ok(())
 }
 
-// =============================================================
-// 5.1) UNIVERSAL LEVELSTATE MIGRATION
-// =============================================================
+// SPL token / Token-2022 leg of an activation (activate_level_token); None = lamports.
+struct TokenPayment<'info> {
+    mint: AccountInfo<'info>,
+    decimals: u8,
+    // Token-2022 TransferFeeConfig for the current epoch, if the mint has one
+    transfer_fee: Option<TransferFee>,
+    payer_token: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    associated_token_program: AccountInfo<'info>,
+    // Recipient ATAs, indexed by PAY_*
+    recipients: [AccountInfo<'info>; 5],
+}
 
-#[inline(never)]
-fn load_or_migrate_level_state(ai: &AccountInfo) -> Result<LevelState> {
-    {
-        let data = ai.try_borrow_data()?;
-        let mut slice: &[u8] = &data;
-        if let Ok(ls) = LevelState::try_deserialize(&mut slice) {
-            return Ok(ls);
-        }
+const PAY_OWNER: usize = 0;
+const PAY_REF1: usize = 1;
+const PAY_REF2: usize = 2;
+const PAY_REF3: usize = 3;
+const PAY_TREASURY: usize = 4;
+
+// Mint's transfer fee for this epoch (Token-2022 TransferFeeConfig only).
+fn mint_transfer_fee(mint: &AccountInfo) -> Result<Option<TransferFee>> {
+    if mint.owner != &anchor_spl::token_2022::ID {
+        return Ok(None);
     }
-    migrate_legacy_level_state(ai)
+    let epoch = Clock::get()?.epoch;
+    Ok(get_mint_extension_data::<TransferFeeConfig>(mint)
+        .ok()
+        .map(|cfg| *cfg.get_epoch_fee(epoch)))
 }
 
-#[inline(never)]
-fn migrate_legacy_level_state(ai: &AccountInfo) -> Result<LevelState> {
-    let mut data = ai.try_borrow_mut_data()?;
-    let len = data.len();
-    if len < 8 {
-        return err!(CustomError::AccountCastError);
+// Size the ATA program allocates for a recipient ATA of `mint` (Token-2022: the account
+// extensions the mint requires, plus ImmutableOwner).
+fn recipient_ata_space(mint: &AccountInfo) -> Result<usize> {
+    if mint.owner != &anchor_spl::token_2022::ID {
+        return Ok(spl_token_2022::state::Account::LEN);
     }
-    let mut idx: usize = 8;
+    let data = mint.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    let mint_types = state.get_extension_types()?;
+    let mut types = ExtensionType::get_required_init_account_extensions(&mint_types);
+    types.push(ExtensionType::ImmutableOwner);
+    Ok(ExtensionType::try_calculate_account_len::<spl_token_2022::state::Account>(&types)?)
+}
 
-    fn read_u8_safe(data: &[u8], idx: &mut usize, len: usize) -> u8 {
-        if *idx + 1 <= len {
-            let v = data[*idx];
-            *idx += 1;
-            v
-        } else {
-            0
-        }
+// One payout leg: lamports to `wallet`, or tokens to the wallet's ATA, which the
+// payer creates if missing. With a transfer fee the leg is grossed up so the
+// recipient nets `amount` (`net_of_fee`), or sent as-is and the recipient bears it.
+// Returns the rent fronted for a created ATA.
+fn pay_recipient<'info>(
+    payer: &AccountInfo<'info>,
+    sys: &AccountInfo<'info>,
+    token: Option<&TokenPayment<'info>>,
+    wallet: &AccountInfo<'info>,
+    recipient: usize,
+    amount: u64,
+    net_of_fee: bool,
+) -> Result<u64> {
+    if amount == 0 {
+        return Ok(0);
     }
-    fn read_i64_safe(data: &[u8], idx: &mut usize, len: usize) -> i64 {
-        if *idx + 8 <= len {
-            let mut buf = [0u8; 8];
-            buf.copy_from_slice(&data[*idx..*idx + 8]);
-            *idx += 8;
-            i64::from_le_bytes(buf)
-        } else {
-            0
-        }
+    let Some(t) = token else {
+        safe_transfer(payer, wallet, sys, amount)?;
+        return Ok(0);
+    };
+
+    let ata = &t.recipients[recipient];
+    require_keys_eq!(
+        *ata.key,
+        get_associated_token_address_with_program_id(wallet.key, t.mint.key, t.token_program.key),
+        CustomError::InvalidTokenAccount
+    );
+    let created_rent = if ata.data_is_empty() {
+        let cpi = associated_token::Create {
+            payer: payer.clone(),
+            associated_token: ata.clone(),
+            authority: wallet.clone(),
+            mint: t.mint.clone(),
+            system_program: sys.clone(),
+            token_program: t.token_program.clone(),
+        };
+        associated_token::create_idempotent(CpiContext::new(t.associated_token_program.clone(), cpi))?;
+        ata.lamports()
+    } else {
+        0
+    };
+
+    let gross = gross_leg_amount(t.transfer_fee.as_ref(), amount, net_of_fee)?;
+    let cpi = TransferChecked {
+        from: t.payer_token.clone(),
+        mint: t.mint.clone(),
+        to: ata.clone(),
+        authority: payer.clone(),
+    };
+    token_interface::transfer_checked(CpiContext::new(t.token_program.clone(), cpi), gross, t.decimals)?;
+    Ok(created_rent)
+}
+
+// Tokens sent for one leg: grossed up so the recipient nets `amount` (`net_of_fee`),
+// or `amount` as-is when the recipient bears the fee.
+fn gross_leg_amount(fee: Option<&TransferFee>, amount: u64, net_of_fee: bool) -> Result<u64> {
+    match fee {
+        Some(fee) if net_of_fee && amount > 0 => fee
+            .calculate_pre_fee_amount(amount)
+            .ok_or_else(|| error!(CustomError::Overflow)),
+        _ => Ok(amount),
     }
-    fn read_u64_safe(data: &[u8], idx: &mut usize, len: usize) -> u64 {
-        if *idx + 8 <= len {
-            let mut buf = [0u8; 8];
-            buf.copy_from_slice(&data[*idx..*idx + 8]);
-            *idx += 8;
-            u64::from_le_bytes(buf)
-        } else {
-            0
+}
+
+// Owner/refs net their exact share; the fees grossing up their legs come out of the
+// treasury share. Returns (treasury share left to send, fees paid on the legs).
+fn treasury_after_leg_fees(
+    payout: &PayoutBreakdown,
+    fee: Option<&TransferFee>,
+) -> Result<(u64, u64)> {
+    let mut fees = 0u64;
+    for amount in [
+        payout.owner_amount,
+        payout.ref1_amount,
+        payout.ref2_amount,
+        payout.ref3_amount,
+    ] {
+        let leg_fee = gross_leg_amount(fee, amount, true)? - amount;
+        fees = fees.checked_add(leg_fee).ok_or(CustomError::Overflow)?;
+    }
+    let treasury_amount = payout
+        .treasury_amount
+        .checked_sub(fees)
+        .ok_or(CustomError::TransferFeeExceedsTreasuryShare)?;
+    Ok((treasury_amount, fees))
+}
+
+// Pays the treasury share and settles bookkeeping rent (see RentSettlement).
+// The treasury bears its own transfer fee (it receives the share minus the fee).
+fn settle_activation<'info>(
+    payer: &AccountInfo<'info>,
+    treasury: &AccountInfo<'info>,
+    rent_reserve: Option<&AccountInfo<'info>>,
+    rent_reserve_bump: Option<u8>,
+    sys: &AccountInfo<'info>,
+    token: Option<&TokenPayment<'info>>,
+    treasury_amount: u64,
+    bookkeeping_rent: u64,
+) -> Result<()> {
+    let reserve = rent_reserve.zip(rent_reserve_bump);
+    let reserve_available = reserve.map(|(r, _)| rent_reserve_available(r)).transpose()?;
+
+    let plan = if token.is_none() {
+        let plan =
+            plan_rent_settlement(false, treasury_amount, bookkeeping_rent, reserve_available)?;
+        safe_transfer(payer, treasury, sys, treasury_amount - plan.from_treasury_share)?;
+        plan
+    } else {
+        let ata_rent =
+            pay_recipient(payer, sys, token, treasury, PAY_TREASURY, treasury_amount, false)?;
+        let bookkeeping_rent = bookkeeping_rent.saturating_add(ata_rent);
+        plan_rent_settlement(true, treasury_amount, bookkeeping_rent, reserve_available)?
+    };
+
+    match reserve {
+        Some((reserve, bump)) => {
+            refund_from_rent_reserve(payer, reserve, bump, sys, plan.from_reserve)
         }
+        None => Ok(()),
     }
-    fn read_pubkey_safe(data: &[u8], idx: &mut usize, len: usize) -> Pubkey {
-        if *idx + 32 <= len {
-            let mut buf = [0u8; 32];
-            buf.copy_from_slice(&data[*idx..*idx + 32]);
+}
+
+fn derive_level_state_pda(player_pda: &Pubkey, level: u8) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"lvl", player_pda.as_ref(), &[level]], &crate::ID)
+}
+
+// Tolerates pre-next_nonce Player accounts (missing trailing fields decode as zero).
+fn read_player(ai: &AccountInfo) -> Result<Player> {
+    let data = ai.try_borrow_data()?;
+    decode_player_padded(&data)
+}
+
+pub fn decode_player_padded(data: &[u8]) -> Result<Player> {
+    let mut buf = data.to_vec();
+    if buf.len() < 8 + Player::SIZE {
+        buf.resize(8 + Player::SIZE, 0);
+    }
+    let mut slice: &[u8] = &buf;
+    let player = Player::try_deserialize(&mut slice)
+        .map_err(|_| error!(CustomError::AccountCastError))?;
+    Ok(player)
+}
+
+// Activation takes the Player unchecked so pre-next_nonce layouts still activate: the
+// account is grown first (payer funds the top-up), then decoded and written back.
+fn load_player_grown<'info>(
+    ai: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    sys: &AccountInfo<'info>,
+) -> Result<Player> {
+    realloc_if_needed(ai, payer, sys, 8 + Player::SIZE)?;
+    read_player(ai)
+}
+
+fn write_player(ai: &AccountInfo, player: &Player) -> Result<()> {
+    let mut data = ai.try_borrow_mut_data()?;
+    let mut cursor = Cursor::new(&mut data[..]);
+    player.try_serialize(&mut cursor)
+}
+
+// Replay protection: either a fresh TxGuard PDA (legacy, while cfg.legacy_tx_guard)
+// or `nonce == player.next_nonce`, which is then bumped.
+fn consume_nonce(
+    player: &mut Player,
+    cfg: &ConfigV3,
+    tx_guard: Option<&mut Account<TxGuard>>,
+    tx_guard_bump: Option<u8>,
+    nonce: u64,
+    now: i64,
+) -> Result<()> {
+    if let Some(tg) = tx_guard {
+        require!(cfg.legacy_tx_guard, CustomError::LegacyTxGuardDisabled);
+        tg.nonce = nonce;
+        tg.executed_at = now;
+        tg.bump = tx_guard_bump.unwrap_or_default();
+        return Ok(());
+    }
+
+    require_eq!(nonce, player.next_nonce, CustomError::StaleNonce);
+    player.next_nonce = player.next_nonce.checked_add(1).ok_or(CustomError::Overflow)?;
+    Ok(())
+}
+
+// Older (shorter) ConfigV3 layouts: missing trailing fields decode as zero.
+pub fn decode_config_padded(data: &[u8]) -> Result<ConfigV3> {
+    let mut buf = data.to_vec();
+    if buf.len() < 8 + ConfigV3::SIZE {
+        buf.resize(8 + ConfigV3::SIZE, 0);
+    }
+    let mut slice: &[u8] = &buf;
+    ConfigV3::try_deserialize(&mut slice).map_err(|_| error!(CustomError::AccountCastError))
+}
+
+// LevelPools from before event_seq: the missing trailing field decodes as zero.
+fn decode_level_pool_padded(data: &[u8]) -> Result<LevelPool> {
+    let mut buf = data.to_vec();
+    if buf.len() < 8 + LevelPool::SIZE {
+        buf.resize(8 + LevelPool::SIZE, 0);
+    }
+    let mut slice: &[u8] = &buf;
+    LevelPool::try_deserialize(&mut slice).map_err(|_| error!(CustomError::AccountCastError))
+}
+
+// Activation's LevelPool: created on the level's first activation, a pre-event_seq pool
+// grown in place (payer funds the top-up). Returns the pool and whether it was created.
+fn load_level_pool_grown<'info>(
+    ai: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    sys: &AccountInfo<'info>,
+    config: &Pubkey,
+    level: u8,
+    bump: u8,
+) -> Result<(LevelPool, bool)> {
+    if ai.data_is_empty() {
+        let space = 8 + LevelPool::SIZE;
+        let seeds: &[&[u8]] = &[b"level_pool_v1", config.as_ref(), &[level], &[bump]];
+        create_pda_account(ai, payer, sys, space, seeds)?;
+        let pool = LevelPool {
+            config: *config,
+            level,
+            bump,
+            head_page: None,
+            tail_page: None,
+            total_enqueued: 0,
+            total_dequeued: 0,
+            event_seq: 0,
+        };
+        return Ok((pool, true));
+    }
+    require_keys_eq!(*ai.owner, crate::ID, CustomError::AccountCastError);
+    realloc_if_needed(ai, payer, sys, 8 + LevelPool::SIZE)?;
+    let pool = decode_level_pool_padded(&ai.try_borrow_data()?)?;
+    Ok((pool, false))
+}
+
+fn write_level_pool(ai: &AccountInfo, pool: &LevelPool) -> Result<()> {
+    let mut data = ai.try_borrow_mut_data()?;
+    let mut cursor = Cursor::new(&mut data[..]);
+    pool.try_serialize(&mut cursor)
+}
+
+// StatsShards from before event_seq: the missing trailing field decodes as zero.
+fn decode_stats_shard_padded(data: &[u8]) -> Result<StatsShard> {
+    let mut buf = data.to_vec();
+    if buf.len() < 8 + StatsShard::SIZE {
+        buf.resize(8 + StatsShard::SIZE, 0);
+    }
+    let mut slice: &[u8] = &buf;
+    StatsShard::try_deserialize(&mut slice).map_err(|_| error!(CustomError::AccountCastError))
+}
+
+// Registration's StatsShard, a pre-event_seq shard grown in place (payer funds the top-up).
+fn load_stats_shard_grown<'info>(
+    ai: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    sys: &AccountInfo<'info>,
+) -> Result<StatsShard> {
+    require_keys_eq!(*ai.owner, crate::ID, CustomError::InvalidStatsShard);
+    realloc_if_needed(ai, payer, sys, 8 + StatsShard::SIZE)?;
+    let shard = decode_stats_shard_padded(&ai.try_borrow_data()?)?;
+    Ok(shard)
+}
+
+fn write_stats_shard(ai: &AccountInfo, shard: &StatsShard) -> Result<()> {
+    let mut data = ai.try_borrow_mut_data()?;
+    let mut cursor = Cursor::new(&mut data[..]);
+    shard.try_serialize(&mut cursor)
+}
+
+fn tx_guard_pda(kind: u8, authority: &Pubkey, player: &Pubkey, level: u8, nonce: u64) -> Result<Pubkey> {
+    let nonce_bytes = nonce.to_le_bytes();
+    let (pda, _) = match kind {
+        TX_GUARD_ACTIVATE => Pubkey::find_program_address(
+            &[b"tx", player.as_ref(), b"\x01", &[level], &nonce_bytes],
+            &crate::ID,
+        ),
+        TX_GUARD_RECYCLE => Pubkey::find_program_address(
+            &[b"tx", player.as_ref(), b"\x02", &[level], &nonce_bytes],
+            &crate::ID,
+        ),
+        TX_GUARD_REGISTER => Pubkey::find_program_address(
+            &[b"tx", b"register", authority.as_ref(), &nonce_bytes],
+            &crate::ID,
+        ),
+        _ => return err!(CustomError::InvalidTxGuardKind),
+    };
+    Ok(pda)
+}
+
+// `ai` must be the player's LevelState for level - 1, already activated.
+fn require_previous_level(player_key: &Pubkey, level: u8, ai: &AccountInfo) -> Result<()> {
+    let (expected, _) = derive_level_state_pda(player_key, level - 1);
+    require_keys_eq!(*ai.key, expected, CustomError::PreviousLevelRequired);
+    require!(
+        ai.owner == &crate::ID && !ai.data_is_empty(),
+        CustomError::PreviousLevelRequired
+    );
+
+    let ls = peek_level_state(ai)?;
+    require!(ls.activated_at > 0, CustomError::PreviousLevelRequired);
+    Ok(())
+}
+
+// Current payout wallet (LevelState.authority) when the upline has `level` open.
+// Uplines are Player PDAs; sign-ups from before that (and orphans) store the seed wallet,
+// which only ever maps to ["player", wallet]. A rotated wallet is never an upline.
+fn open_level_wallet<'info>(
+    upline: &Pubkey,
+    level: u8,
+    level_state_ai: &AccountInfo<'info>,
+) -> Result<Option<Pubkey>> {
+    if level_state_ai.owner != &crate::ID {
+        return Ok(None);
+    }
+    if level_state_ai.data_is_empty() {
+        return Ok(None);
+    }
+
+    // Uplines that haven't touched this level since the queue-membership upgrade still
+    // hold the legacy layout: read it without rewriting (the account is read-only here)
+    let ls = peek_level_state(level_state_ai)?;
+
+    let (expected_pda, _) = derive_level_state_pda(&ls.player, level);
+    if level_state_ai.key != &expected_pda {
+        return Ok(None);
+    }
+
+    let is_upline = ls.player == *upline || {
+        let (player_pda, _) = Pubkey::find_program_address(&[b"player", upline.as_ref()], &crate::ID);
+        ls.player == player_pda
+    };
+
+    Ok((is_upline && ls.level == level && ls.activated_at > 0).then_some(ls.authority))
+}
+
+fn resolve_ref_with_level_or_admin<'info>(
+    upline_key: &Pubkey,
+    level: u8,
+    level_state_ai_opt: Option<&AccountInfo<'info>>,
+    cfg_admin: &Pubkey,
+) -> Pubkey {
+    if let Some(ai) = level_state_ai_opt {
+        if let Ok(Some(wallet)) = open_level_wallet(upline_key, level, ai) {
+            return wallet;
+        }
+    }
+    *cfg_admin
+}
+
+// Owner uplines (admin when owner Player is missing), each falling back to admin if its level is closed.
+fn resolve_refs<'info>(
+    cfg: &ConfigV3,
+    owner_player: Option<&Player>,
+    level: u8,
+    ref_level_states: [Option<&AccountInfo<'info>>; 3],
+) -> [Pubkey; 3] {
+    let uplines = owner_player
+        .map(|op| [op.upline1, op.upline2, op.upline3])
+        .unwrap_or([cfg.admin; 3]);
+    let [ls1, ls2, ls3] = ref_level_states;
+    [
+        resolve_ref_with_level_or_admin(&uplines[0], level, ls1, &cfg.admin),
+        resolve_ref_with_level_or_admin(&uplines[1], level, ls2, &cfg.admin),
+        resolve_ref_with_level_or_admin(&uplines[2], level, ls3, &cfg.admin),
+    ]
+}
+
+// =============================================================
+// 5.1) UNIVERSAL LEVELSTATE MIGRATION
+// =============================================================
+
+#[inline(never)]
+fn load_or_migrate_level_state(ai: &AccountInfo) -> Result<LevelState> {
+    {
+        let data = ai.try_borrow_data()?;
+        let mut slice: &[u8] = &data;
+        if let Ok(ls) = LevelState::try_deserialize(&mut slice) {
+            return Ok(ls);
+        }
+    }
+    migrate_legacy_level_state(ai)
+}
+
+// Read-only variant for views: decodes legacy layouts without rewriting the account.
+fn peek_level_state(ai: &AccountInfo) -> Result<LevelState> {
+    let data = ai.try_borrow_data()?;
+    let mut slice: &[u8] = &data;
+    if let Ok(ls) = LevelState::try_deserialize(&mut slice) {
+        return Ok(ls);
+    }
+    require!(
+        data.starts_with(LevelState::DISCRIMINATOR),
+        CustomError::AccountCastError
+    );
+    decode_legacy_level_state(&data)
+}
+
+#[inline(never)]
+fn migrate_legacy_level_state(ai: &AccountInfo) -> Result<LevelState> {
+    let mut data = ai.try_borrow_mut_data()?;
+    let owner_ls = decode_legacy_level_state(&data)?;
+
+    {
+        let mut cursor = Cursor::new(&mut data[..]);
+        owner_ls
+            .try_serialize(&mut cursor)
+            .map_err(|_| error!(CustomError::AccountCastError))?;
+    }
+    drop(data);
+
+    let data2 = ai.try_borrow_data()?;
+    let mut slice2: &[u8] = &data2;
+    let ls2 = LevelState::try_deserialize(&mut slice2)
+        .map_err(|_| error!(CustomError::AccountCastError))?;
+    Ok(ls2)
+}
+
+#[inline(never)]
+pub fn decode_legacy_level_state(data: &[u8]) -> Result<LevelState> {
+    let len = data.len();
+    if len < 8 {
+        return err!(CustomError::AccountCastError);
+    }
+    let mut idx: usize = 8;
+
+    fn read_u8_safe(data: &[u8], idx: &mut usize, len: usize) -> u8 {
+        if *idx < len {
+            let v = data[*idx];
+            *idx += 1;
+            v
+        } else {
+            0
+        }
+    }
+    fn read_i64_safe(data: &[u8], idx: &mut usize, len: usize) -> i64 {
+        if *idx + 8 <= len {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&data[*idx..*idx + 8]);
+            *idx += 8;
+            i64::from_le_bytes(buf)
+        } else {
+            0
+        }
+    }
+    fn read_u64_safe(data: &[u8], idx: &mut usize, len: usize) -> u64 {
+        if *idx + 8 <= len {
+            let mut buf = [0u8; 8];
+            buf.copy_from_slice(&data[*idx..*idx + 8]);
+            *idx += 8;
+            u64::from_le_bytes(buf)
+        } else {
+            0
+        }
+    }
+    fn read_pubkey_safe(data: &[u8], idx: &mut usize, len: usize) -> Pubkey {
+        if *idx + 32 <= len {
+            let mut buf = [0u8; 32];
+            buf.copy_from_slice(&data[*idx..*idx + 32]);
             *idx += 32;
             Pubkey::new_from_array(buf)
         } else {
@@ -476,26 +1506,32 @@ fn migrate_legacy_level_state(ai: &AccountInfo) -> Result<LevelState> {
         }
     }
 
-    let player = read_pubkey_safe(&data, &mut idx, len);
-    let authority = read_pubkey_safe(&data, &mut idx, len);
-    let level = read_u8_safe(&data, &mut idx, len);
-    let bump = read_u8_safe(&data, &mut idx, len);
-    let activated_at = read_i64_safe(&data, &mut idx, len);
-    let cycles = read_u64_safe(&data, &mut idx, len);
-    let slots_filled = read_u64_safe(&data, &mut idx, len);
+    let player = read_pubkey_safe(data, &mut idx, len);
+    let authority = read_pubkey_safe(data, &mut idx, len);
+    let level = read_u8_safe(data, &mut idx, len);
+    let bump = read_u8_safe(data, &mut idx, len);
+    let activated_at = read_i64_safe(data, &mut idx, len);
+    let cycles = read_u64_safe(data, &mut idx, len);
+    let slots_filled = read_u64_safe(data, &mut idx, len);
 
     let head_page = if idx < len {
-        read_option_pubkey_safe(&data, &mut idx, len)
+        read_option_pubkey_safe(data, &mut idx, len)
     } else {
         None
     };
     let tail_page = if idx < len {
-        read_option_pubkey_safe(&data, &mut idx, len)
+        read_option_pubkey_safe(data, &mut idx, len)
+    } else {
+        None
+    };
+    let queue_page = if idx < len {
+        read_option_pubkey_safe(data, &mut idx, len)
     } else {
         None
     };
+    let queue_pos = read_u64_safe(data, &mut idx, len);
 
-    let owner_ls = LevelState {
+    Ok(LevelState {
         player,
         authority,
         level,
@@ -505,21 +1541,9 @@ fn migrate_legacy_level_state(ai: &AccountInfo) -> Result<LevelState> {
         slots_filled,
         head_page,
         tail_page,
-    };
-
-    {
-        let mut cursor = Cursor::new(&mut data[..]);
-        owner_ls
-            .try_serialize(&mut cursor)
-            .map_err(|_| error!(CustomError::AccountCastError))?;
-    }
-    drop(data);
-
-    let data2 = ai.try_borrow_data()?;
-    let mut slice2: &[u8] = &data2;
-    let ls2 = LevelState::try_deserialize(&mut slice2)
-        .map_err(|_| error!(CustomError::AccountCastError))?;
-    Ok(ls2)
+        queue_page,
+        queue_pos,
+    })
 }
 
 // =============================================================
@@ -569,30 +1593,45 @@ fn create_queue_page<'info>(
     Ok((page_pda, bump_page))
 }
 
+#[inline(always)]
+fn page_is_full(len: usize) -> bool {
+    len >= QUEUE_PAGE_CAPACITY_DEFAULT
+}
+
 fn enqueue_into_tail<'info>(
     level: u8,
-    pool: &mut Account<'info, LevelPool>,
+    pool_key: Pubkey,
+    pool: &mut LevelPool,
     payer: &Signer<'info>,
     system_program_acc: &Program<'info, System>,
     // provided accounts:
     tail_page_ai: &AccountInfo<'info>,
     new_page_ai: &AccountInfo<'info>,
     player_pda: Pubkey,
+    member_ls: &mut LevelState,
     used_new_page: &mut bool,
+    events: &EventSink<'info>,
 ) -> Result<()> {
-    let pool_key = pool.key();
+    // LevelState is the authoritative membership record (any page, not only tail)
+    require!(member_ls.queue_page.is_none(), CustomError::AlreadyInQueue);
+
     let tail_key = pool.tail_page.ok_or(CustomError::QueueIsEmpty)?;
 
+    // After a rollover in this tx the freshly created page is the tail
+    let tail_page_ai = if *used_new_page { new_page_ai } else { tail_page_ai };
     require_keys_eq!(tail_key, *tail_page_ai.key, CustomError::QueuePageKeyMismatch);
 
     let mut tail_page = read_queue_page(tail_page_ai)?;
 
-    // Skip if player already in this page (prevents duplicates)
-    if tail_page.players.contains(&player_pda) {
+    // Legacy entry (queued before membership tracking): adopt it instead of duplicating
+    if let Some(idx) = tail_page.players.iter().position(|p| *p == player_pda) {
+        let behind = (tail_page.players.len() - idx) as u64;
+        member_ls.queue_page = Some(tail_key);
+        member_ls.queue_pos = pool.total_enqueued.saturating_sub(behind);
         return Ok(());
     }
 
-    if tail_page.players.len() >= QUEUE_PAGE_CAPACITY_DEFAULT {
+    if page_is_full(tail_page.players.len()) {
         require!(!*used_new_page, CustomError::RolloverNeedsSecondNewPage);
         *used_new_page = true;
 
@@ -620,21 +1659,28 @@ fn enqueue_into_tail<'info>(
         tail_page.next_page = Some(new_pda);
         write_queue_page(tail_page_ai, &mut tail_page)?;
 
+        member_ls.queue_page = Some(new_pda);
+        member_ls.queue_pos = pool.total_enqueued;
+
         pool.tail_page = Some(new_pda);
         pool.total_enqueued = pool.total_enqueued.saturating_add(1);
 
-        emit!(QueuePageCreated {
+        events.emit(QueuePageCreated {
             level,
             pool: pool_key,
             page_index: new_index,
             page: new_pda,
-        });
-        emit!(Enqueued {
+            schema_version: EVENT_SCHEMA_VERSION,
+            event_seq: next_event_seq(&mut pool.event_seq)?,
+        })?;
+        events.emit(Enqueued {
             player: player_pda,
             level,
             page_index: new_index,
             position_in_page: 0,
-        });
+            schema_version: EVENT_SCHEMA_VERSION,
+            event_seq: next_event_seq(&mut pool.event_seq)?,
+        })?;
 
         return Ok(());
     }
@@ -643,43 +1689,245 @@ fn enqueue_into_tail<'info>(
     let pos = (tail_page.players.len() - 1) as u32;
     write_queue_page(tail_page_ai, &mut tail_page)?;
 
+    member_ls.queue_page = Some(tail_key);
+    member_ls.queue_pos = pool.total_enqueued;
+
     pool.total_enqueued = pool.total_enqueued.saturating_add(1);
-    emit!(Enqueued {
+    events.emit(Enqueued {
         player: player_pda,
         level,
         page_index: tail_page.page_index,
         position_in_page: pos,
-    });
+        schema_version: EVENT_SCHEMA_VERSION,
+        event_seq: next_event_seq(&mut pool.event_seq)?,
+    })
+}
+
+// Removes the head owner (players[0]) and clears their membership record.
+fn dequeue_head_owner<'info>(
+    pool: &mut LevelPool,
+    head_page_ai: &AccountInfo<'info>,
+    head_key: Pubkey,
+    head_page: &mut QueuePage,
+    owner_ls: &mut LevelState,
+) -> Result<()> {
+    head_page.players.remove(0);
+    if head_page.players.is_empty() {
+        if let Some(next) = head_page.next_page {
+            pool.head_page = Some(next);
+        } else {
+            pool.head_page = Some(head_key);
+            pool.tail_page = Some(head_key);
+        }
+    }
+    write_queue_page(head_page_ai, head_page)?;
+    pool.total_dequeued = pool.total_dequeued.saturating_add(1);
 
+    owner_ls.queue_page = None;
+    owner_ls.queue_pos = 0;
     Ok(())
 }
 
 // =============================================================
-// 6.1) FIRST ACTIVATION
+// 6.0) PAYOUT PLAN (shared by activation and quote_activation)
 // =============================================================
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PayoutBreakdown {
+    pub owner_wallet: Pubkey,
+    pub owner_amount: u64,
+    pub ref1_wallet: Pubkey,
+    pub ref1_amount: u64,
+    pub ref2_wallet: Pubkey,
+    pub ref2_amount: u64,
+    pub ref3_wallet: Pubkey,
+    pub ref3_amount: u64,
+    pub treasury_amount: u64,
+}
+
+impl PayoutBreakdown {
+    /// First activation of a level: 100% -> treasury.
+    pub fn treasury_only(price: u64) -> Self {
+        Self {
+            treasury_amount: price,
+            ..Default::default()
+        }
+    }
+
+    /// Self-owner activation: 100% -> treasury, the owner (the activator's wallet) gets 0.
+    pub fn self_owner(price: u64, owner_wallet: Pubkey) -> Self {
+        Self {
+            owner_wallet,
+            ..Self::treasury_only(price)
+        }
+    }
+
+    /// Normal activation: owner / ref1-3 / treasury split.
+    /// Refs resolved to admin or treasury are collapsed into the treasury share.
+    pub fn split(price: u64, cfg: &ConfigV3, owner_wallet: Pubkey, refs: [Pubkey; 3]) -> Self {
+        let owner_amount = calc_share(price, PERC_OWNER);
+        let ref_raw = [
+            calc_share(price, PERC_REF1),
+            calc_share(price, PERC_REF2),
+            calc_share(price, PERC_REF3),
+        ];
+
+        let sum_non_treas = owner_amount
+            .saturating_add(ref_raw[0])
+            .saturating_add(ref_raw[1])
+            .saturating_add(ref_raw[2]);
+        let mut treasury_amount = price.saturating_sub(sum_non_treas);
+
+        let mut ref_amounts = [0u64; 3];
+        for i in 0..3 {
+            if refs[i] == cfg.treasury || refs[i] == cfg.admin {
+                treasury_amount = treasury_amount.saturating_add(ref_raw[i]);
+            } else {
+                ref_amounts[i] = ref_raw[i];
+            }
+        }
+
+        Self {
+            owner_wallet,
+            owner_amount,
+            ref1_wallet: refs[0],
+            ref1_amount: ref_amounts[0],
+            ref2_wallet: refs[1],
+            ref2_amount: ref_amounts[1],
+            ref3_wallet: refs[2],
+            ref3_amount: ref_amounts[2],
+            treasury_amount,
+        }
+    }
+}
+
+/// Why a ref share went to the treasury instead of the upline.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayoutRedirect {
+    #[default]
+    None,
+    /// Upline hasn't activated this level (or its LevelState wasn't passed)
+    UplineLevelClosed,
+    /// Upline is the admin or the treasury (also: owner Player missing)
+    AdminOrTreasury,
+}
+
+// Mirrors resolve_refs + PayoutBreakdown::split: refs resolved to admin/treasury collapse
+// into the treasury share; tell a closed upline level apart from an admin/treasury upline.
+fn payout_redirects(
+    cfg: &ConfigV3,
+    owner_player: Option<&Player>,
+    refs: [Pubkey; 3],
+) -> [(Pubkey, PayoutRedirect); 3] {
+    let uplines = owner_player
+        .map(|op| [op.upline1, op.upline2, op.upline3])
+        .unwrap_or([cfg.admin; 3]);
+    let collapsed = |k: &Pubkey| *k == cfg.admin || *k == cfg.treasury;
+    std::array::from_fn(|i| {
+        if !collapsed(&refs[i]) {
+            (refs[i], PayoutRedirect::None)
+        } else if collapsed(&uplines[i]) {
+            (refs[i], PayoutRedirect::AdminOrTreasury)
+        } else {
+            (uplines[i], PayoutRedirect::UplineLevelClosed)
+        }
+    })
+}
+
+// Separate frame: keeps the event off process_*_activation's SBF stack.
 #[inline(never)]
-fn process_first_activation<'info>(
-    accounts: &mut ActivateLevelV3<'info>,
+fn emit_payout_distributed(
+    accounts: &ActivateLevelV3,
+    pool: &mut LevelPool,
+    events: &EventSink,
     level: u8,
     price: u64,
+    payout: &PayoutBreakdown,
+    redirects: [(Pubkey, PayoutRedirect); 3],
+    treasury_amount: u64,
+    bookkeeping_rent: u64,
+    is_self_owner: bool,
 ) -> Result<()> {
-    let payer = &accounts.authority;
-    let system_program_acc = &accounts.system_program;
-    let cfg = &accounts.config_v3;
-    let sys = system_program_acc.to_account_info();
-
-    require_keys_eq!(accounts.treasury_account.key(), cfg.treasury);
-    require_system_wallet(&accounts.treasury_account.to_account_info())?;
+    let raw = [PERC_REF1, PERC_REF2, PERC_REF3];
+    let ref_amounts = [payout.ref1_amount, payout.ref2_amount, payout.ref3_amount];
+    events.emit(PayoutDistributed {
+        activator: accounts.authority.key(),
+        player: accounts.player.key(),
+        level,
+        owner: payout.owner_wallet,
+        owner_amount: payout.owner_amount,
+        refs: redirects.map(|(k, _)| k),
+        ref_amounts,
+        redirected_amounts: std::array::from_fn(|i| match redirects[i].1 {
+            PayoutRedirect::None => 0,
+            _ => calc_share(price, raw[i]),
+        }),
+        redirect_reasons: redirects.map(|(_, r)| r),
+        treasury_amount,
+        bookkeeping_rent,
+        is_self_owner,
+        timestamp: accounts.level_state.activated_at,
+        schema_version: EVENT_SCHEMA_VERSION,
+        event_seq: next_event_seq(&mut pool.event_seq)?,
+    })
+}
 
-    let page_index: u32 = 0;
-    let (page_pda, bump_page) = create_queue_page(
-        payer,
-        system_program_acc,
-        &accounts.level_pool.key(),
-        page_index,
-        &accounts.new_page,
-    )?;
+// Queue owner's payout wallet, read from the owner Player at payout time (it follows
+// rotate_authority / complete_recovery); an orphaned LevelState keeps its recorded wallet.
+fn owner_payout_wallet(owner_player: Option<&Player>, owner_ls: &LevelState) -> Pubkey {
+    owner_player.map_or(owner_ls.authority, |p| p.authority)
+}
+
+#[inline(always)]
+fn recycle_threshold(cfg: &ConfigV3) -> u64 {
+    // X3 matrix requires minimum 3 slots. Runtime protection against future config changes.
+    cfg.slots_to_recycle.max(3) as u64
+}
+
+// Owner's slots_filled after one more fill, and whether that completes the cycle.
+fn next_owner_slots(owner_ls: &LevelState, cfg: &ConfigV3) -> Result<(u64, bool)> {
+    require!(owner_ls.activated_at > 0, CustomError::LevelNotActivated);
+
+    let threshold = recycle_threshold(cfg);
+    require!(owner_ls.slots_filled < threshold, CustomError::SlotsAlreadyFull);
+
+    let filled = owner_ls
+        .slots_filled
+        .checked_add(1)
+        .ok_or(CustomError::Overflow)?;
+    Ok((filled, filled == threshold))
+}
+
+// =============================================================
+// 6.1) FIRST ACTIVATION
+// =============================================================
+
+#[inline(never)]
+fn process_first_activation<'info>(
+    accounts: &mut ActivateLevelV3<'info>,
+    pool: &mut LevelPool,
+    level: u8,
+    price: u64,
+    pool_created: bool,
+    rent_reserve_bump: Option<u8>,
+    token: Option<&TokenPayment<'info>>,
+    events: &EventSink<'info>,
+) -> Result<()> {
+    let payer = &accounts.authority;
+    let system_program_acc = &accounts.system_program;
+    let cfg = &accounts.config_v3;
+    let sys = system_program_acc.to_account_info();
+
+    require_treasury_account(cfg, &accounts.treasury_account)?;
+
+    let page_index: u32 = 0;
+    let (page_pda, bump_page) = create_queue_page(
+        payer,
+        system_program_acc,
+        &accounts.level_pool.key(),
+        page_index,
+        &accounts.new_page,
+    )?;
 
     let mut first_page = QueuePage {
         bump: bump_page,
@@ -690,31 +1938,61 @@ fn process_first_activation<'info>(
     };
     write_queue_page(&accounts.new_page, &mut first_page)?;
 
-    accounts.level_pool.head_page = Some(page_pda);
-    accounts.level_pool.tail_page = Some(page_pda);
-    accounts.level_pool.total_enqueued = accounts.level_pool.total_enqueued.saturating_add(1);
+    accounts.level_state.queue_page = Some(page_pda);
+    accounts.level_state.queue_pos = pool.total_enqueued;
+
+    pool.head_page = Some(page_pda);
+    pool.tail_page = Some(page_pda);
+    pool.total_enqueued = pool.total_enqueued.saturating_add(1);
 
-    emit!(QueuePageCreated {
+    events.emit(QueuePageCreated {
         level,
         pool: accounts.level_pool.key(),
         page_index,
         page: page_pda,
-    });
-    emit!(Enqueued {
+        schema_version: EVENT_SCHEMA_VERSION,
+        event_seq: next_event_seq(&mut pool.event_seq)?,
+    })?;
+    events.emit(Enqueued {
         player: accounts.player.key(),
         level,
         page_index,
         position_in_page: 0,
-    });
+        schema_version: EVENT_SCHEMA_VERSION,
+        event_seq: next_event_seq(&mut pool.event_seq)?,
+    })?;
+
+    // Page 0 (and the LevelPool, if created in this tx) is bookkeeping rent
+    let rent = Rent::get()?;
+    let mut bookkeeping_rent = rent.minimum_balance(QueuePage::space_default());
+    if pool_created {
+        bookkeeping_rent = bookkeeping_rent.saturating_add(rent.minimum_balance(8 + LevelPool::SIZE));
+    }
 
-    safe_transfer(
-        &payer.to_account_info(),
-        &accounts.treasury_account.to_account_info(),
-        &sys,
+    let payout = PayoutBreakdown::treasury_only(price);
+    emit_payout_distributed(
+        accounts,
+        pool,
+        events,
+        level,
         price,
+        &payout,
+        [(Pubkey::default(), PayoutRedirect::None); 3],
+        payout.treasury_amount,
+        bookkeeping_rent,
+        false,
     )?;
-
-    Ok(())
+    let reserve_ai = accounts.rent_reserve.as_ref().map(|r| r.to_account_info());
+    settle_activation(
+        &accounts.authority.to_account_info(),
+        &accounts.treasury_account.to_account_info(),
+        reserve_ai.as_ref(),
+        rent_reserve_bump,
+        &sys,
+        token,
+        payout.treasury_amount,
+        bookkeeping_rent,
+    )
 }
 
 // =============================================================
@@ -724,9 +2002,13 @@ fn process_first_activation<'info>(
 #[inline(never)]
 fn process_existing_activation<'info>(
     accounts: &mut ActivateLevelV3<'info>,
+    pool: &mut LevelPool,
     level: u8,
     price: u64,
     now: i64,
+    rent_reserve_bump: Option<u8>,
+    token: Option<&TokenPayment<'info>>,
+    events: &EventSink<'info>,
 ) -> Result<()> {
     let cfg = &accounts.config_v3;
     let payer = &accounts.authority;
@@ -735,11 +2017,10 @@ fn process_existing_activation<'info>(
     let payer_ai = payer.to_account_info();
 
     require_keys_eq!(accounts.admin_account.key(), cfg.admin);
-    require_keys_eq!(accounts.treasury_account.key(), cfg.treasury);
     require_system_wallet(&accounts.admin_account.to_account_info())?;
-    require_system_wallet(&accounts.treasury_account.to_account_info())?;
+    require_treasury_account(cfg, &accounts.treasury_account)?;
 
-    let head_key = accounts.level_pool.head_page.ok_or(CustomError::QueueIsEmpty)?;
+    let head_key = pool.head_page.ok_or(CustomError::QueueIsEmpty)?;
     require_keys_eq!(head_key, accounts.head_page.key(), CustomError::QueuePageKeyMismatch);
 
     let head_ai = accounts.head_page.to_account_info();
@@ -747,11 +2028,13 @@ fn process_existing_activation<'info>(
     require!(!head_page.players.is_empty(), CustomError::QueueIsEmpty);
 
     let owner_player_pda = head_page.players[0];
-    emit!(DequeuedOwner {
+    events.emit(DequeuedOwner {
         owner_player: owner_player_pda,
         level,
         page_index: head_page.page_index,
-    });
+        schema_version: EVENT_SCHEMA_VERSION,
+        event_seq: next_event_seq(&mut pool.event_seq)?,
+    })?;
 
     require_keys_eq!(accounts.owner_player.key(), owner_player_pda);
 
@@ -760,17 +2043,18 @@ fn process_existing_activation<'info>(
     let (owner_ls_pda, owner_ls_bump) = derive_level_state_pda(&owner_player_pda, level);
     require_keys_eq!(accounts.owner_level_state.key(), owner_ls_pda);
 
+    // Rent the activator fronts for accounts that aren't theirs (settled at the end)
     let owner_ls_ai = accounts.owner_level_state.to_account_info();
-    if owner_ls_ai.data_is_empty() {
-        let space: usize = 8 + LevelState::SIZE;
+    let owner_ls_space: usize = 8 + LevelState::SIZE;
+    let mut bookkeeping_rent = if owner_ls_ai.data_is_empty() {
         let rent = Rent::get()?;
-        let lamports = rent.minimum_balance(space);
+        let lamports = rent.minimum_balance(owner_ls_space);
 
         let ix = system_instruction::create_account(
             &payer.key(),
             &owner_ls_pda,
             lamports,
-            space as u64,
+            owner_ls_space as u64,
             &crate::ID,
         );
         invoke_signed(
@@ -787,7 +2071,13 @@ fn process_existing_activation<'info>(
                 &[owner_ls_bump],
             ]],
         )?;
-    }
+        lamports
+    } else {
+        // Legacy layouts are shorter than the current LevelState (queue membership fields)
+        let top_up = realloc_top_up(&owner_ls_ai, owner_ls_space)?;
+        realloc_if_needed(&owner_ls_ai, &payer_ai, &sys, owner_ls_space)?;
+        top_up
+    };
 
     let mut owner_ls = load_or_migrate_level_state(&owner_ls_ai)?;
 
@@ -810,7 +2100,10 @@ fn process_existing_activation<'info>(
         owner_ls.bump = owner_ls_bump;
         owner_ls.head_page = None;
         owner_ls.tail_page = None;
+        owner_ls.queue_page = None;
+        owner_ls.queue_pos = 0;
     }
+    owner_ls.authority = owner_payout_wallet(owner_player_opt.as_ref(), &owner_ls);
 
     require_keys_eq!(accounts.owner_wallet.key(), owner_ls.authority);
     require_system_wallet(&accounts.owner_wallet.to_account_info())?;
@@ -824,35 +2117,29 @@ fn process_existing_activation<'info>(
     // This is INTENTIONAL anti-gaming protection.
     // ============================================================
     if is_self_owner {
-        safe_transfer(
-            &payer_ai,
-            &accounts.treasury_account.to_account_info(),
-            &sys,
-            price,
+        let payout = PayoutBreakdown::self_owner(price, owner_ls.authority);
+
+        dequeue_head_owner(
+            pool,
+            &accounts.head_page,
+            head_key,
+            &mut head_page,
+            &mut owner_ls,
         )?;
 
-        head_page.players.remove(0);
-        if head_page.players.is_empty() {
-            if let Some(next) = head_page.next_page {
-                accounts.level_pool.head_page = Some(next);
-            } else {
-                accounts.level_pool.head_page = Some(head_key);
-                accounts.level_pool.tail_page = Some(head_key);
-            }
-        }
-        write_queue_page(&accounts.head_page, &mut head_page)?;
-        accounts.level_pool.total_dequeued = accounts.level_pool.total_dequeued.saturating_add(1);
-
         let mut used_new_page = false;
         enqueue_into_tail(
             level,
-            &mut accounts.level_pool,
+            accounts.level_pool.key(),
+            pool,
             payer,
             system_program_acc,
             &accounts.tail_page,
             &accounts.new_page,
             owner_player_pda,
+            &mut owner_ls,
             &mut used_new_page,
+            events,
         )?;
 
         {
@@ -861,7 +2148,37 @@ fn process_existing_activation<'info>(
             owner_ls.try_serialize(&mut cursor)?;
         }
 
-        return Ok(());
+        // owner_level_state aliases level_state here; Anchor re-serializes level_state on exit
+        accounts.level_state.queue_page = owner_ls.queue_page;
+        accounts.level_state.queue_pos = owner_ls.queue_pos;
+
+        if used_new_page {
+            bookkeeping_rent = bookkeeping_rent
+                .saturating_add(Rent::get()?.minimum_balance(QueuePage::space_default()));
+        }
+        emit_payout_distributed(
+            accounts,
+            pool,
+            events,
+            level,
+            price,
+            &payout,
+            [(Pubkey::default(), PayoutRedirect::None); 3],
+            payout.treasury_amount,
+            bookkeeping_rent,
+            true,
+        )?;
+        let reserve_ai = accounts.rent_reserve.as_ref().map(|r| r.to_account_info());
+        return settle_activation(
+            &payer_ai,
+            &accounts.treasury_account.to_account_info(),
+            reserve_ai.as_ref(),
+            rent_reserve_bump,
+            &sys,
+            token,
+            payout.treasury_amount,
+            bookkeeping_rent,
+        );
     }
 
     // ============================================================
@@ -869,126 +2186,74 @@ fn process_existing_activation<'info>(
     // X3 Logic: Owner stays at head until 3 slots are filled
     // ============================================================
 
-    require!(owner_ls.activated_at > 0, CustomError::LevelNotActivated);
-
-    // X3 matrix requires minimum 3 slots. Runtime protection against future config changes.
-    let threshold = cfg.slots_to_recycle.max(3) as u64;
-    require!(owner_ls.slots_filled < threshold, CustomError::SlotsAlreadyFull);
-
     // Increment slots for owner
-    owner_ls.slots_filled = owner_ls
-        .slots_filled
-        .checked_add(1)
-        .ok_or(CustomError::Overflow)?;
+    let (slots_filled, should_recycle) = next_owner_slots(&owner_ls, cfg)?;
+    owner_ls.slots_filled = slots_filled;
 
-    emit!(SlotsFilledN {
+    events.emit(SlotsFilledN {
         owner: owner_ls.authority,
         level: owner_ls.level,
         cycles: owner_ls.cycles,
         filled: owner_ls.slots_filled as u8,
-    });
-
-    // Resolve referrers
-    let (u1, u2, u3) = owner_player_opt
-        .as_ref()
-        .map(|op| (op.upline1, op.upline2, op.upline3))
-        .unwrap_or((cfg.admin, cfg.admin, cfg.admin));
-
-    let ref1 = resolve_ref_with_level_or_admin(
-        &u1,
-        level,
-        accounts.ref1_level_state.as_ref().map(|a| a),
-        &cfg.admin,
-    );
-    let ref2 = resolve_ref_with_level_or_admin(
-        &u2,
-        level,
-        accounts.ref2_level_state.as_ref().map(|a| a),
-        &cfg.admin,
-    );
-    let ref3 = resolve_ref_with_level_or_admin(
-        &u3,
+        schema_version: EVENT_SCHEMA_VERSION,
+        event_seq: next_event_seq(&mut pool.event_seq)?,
+    })?;
+
+    // Resolve referrers + calculate payouts
+    let refs = resolve_refs(
+        cfg,
+        owner_player_opt.as_ref(),
         level,
-        accounts.ref3_level_state.as_ref().map(|a| a),
-        &cfg.admin,
+        [
+            accounts.ref1_level_state.as_ref(),
+            accounts.ref2_level_state.as_ref(),
+            accounts.ref3_level_state.as_ref(),
+        ],
     );
+    let payout = PayoutBreakdown::split(price, cfg, owner_ls.authority, refs);
+    let redirects = payout_redirects(cfg, owner_player_opt.as_ref(), refs);
 
-    // Calculate payouts
-    let amt_owner = calc_share(price, PERC_OWNER);
-    let amt_ref1_raw = calc_share(price, PERC_REF1);
-    let amt_ref2_raw = calc_share(price, PERC_REF2);
-    let amt_ref3_raw = calc_share(price, PERC_REF3);
-
-    let sum_non_treas = amt_owner
-        .saturating_add(amt_ref1_raw)
-        .saturating_add(amt_ref2_raw)
-        .saturating_add(amt_ref3_raw);
-    let mut amt_treas = price.saturating_sub(sum_non_treas);
-
-    require_keys_eq!(accounts.ref1_account.key(), ref1);
-    require_keys_eq!(accounts.ref2_account.key(), ref2);
-    require_keys_eq!(accounts.ref3_account.key(), ref3);
+    require_keys_eq!(accounts.ref1_account.key(), payout.ref1_wallet);
+    require_keys_eq!(accounts.ref2_account.key(), payout.ref2_wallet);
+    require_keys_eq!(accounts.ref3_account.key(), payout.ref3_wallet);
 
     require_system_wallet(&accounts.ref1_account.to_account_info())?;
     require_system_wallet(&accounts.ref2_account.to_account_info())?;
     require_system_wallet(&accounts.ref3_account.to_account_info())?;
 
-    let mut amt_ref1 = amt_ref1_raw;
-    let mut amt_ref2 = amt_ref2_raw;
-    let mut amt_ref3 = amt_ref3_raw;
-
-    // If referrer is admin/treasury, redirect to treasury
-    if ref1 == cfg.treasury || ref1 == cfg.admin {
-        amt_treas = amt_treas.saturating_add(amt_ref1);
-        amt_ref1 = 0;
-    }
-    if ref2 == cfg.treasury || ref2 == cfg.admin {
-        amt_treas = amt_treas.saturating_add(amt_ref2);
-        amt_ref2 = 0;
-    }
-    if ref3 == cfg.treasury || ref3 == cfg.admin {
-        amt_treas = amt_treas.saturating_add(amt_ref3);
-        amt_ref3 = 0;
-    }
-
-    // Execute transfers
-    safe_transfer(&payer_ai, &accounts.owner_wallet.to_account_info(), &sys, amt_owner)?;
-
-    if amt_ref1 > 0 {
-        safe_transfer(&payer_ai, &accounts.ref1_account.to_account_info(), &sys, amt_ref1)?;
-    }
-    if amt_ref2 > 0 {
-        safe_transfer(&payer_ai, &accounts.ref2_account.to_account_info(), &sys, amt_ref2)?;
-    }
-    if amt_ref3 > 0 {
-        safe_transfer(&payer_ai, &accounts.ref3_account.to_account_info(), &sys, amt_ref3)?;
+    // Execute transfers (recipient ATAs created in token mode count as bookkeeping rent).
+    // Owner/refs net their exact share; Token-2022 transfer fees come out of the treasury share.
+    let (treasury_amount, _) =
+        treasury_after_leg_fees(&payout, token.and_then(|t| t.transfer_fee.as_ref()))?;
+    for (wallet, recipient, amount) in [
+        (&accounts.owner_wallet, PAY_OWNER, payout.owner_amount),
+        (&accounts.ref1_account, PAY_REF1, payout.ref1_amount),
+        (&accounts.ref2_account, PAY_REF2, payout.ref2_amount),
+        (&accounts.ref3_account, PAY_REF3, payout.ref3_amount),
+    ] {
+        let ata_rent =
+            pay_recipient(&payer_ai, &sys, token, &wallet.to_account_info(), recipient, amount, true)?;
+        bookkeeping_rent = bookkeeping_rent.saturating_add(ata_rent);
     }
-    safe_transfer(&payer_ai, &accounts.treasury_account.to_account_info(), &sys, amt_treas)?;
+    // Treasury share is settled last, once the queue rollover (if any) is known
 
     // ============================================================
     // X3 RECYCLE LOGIC:
     // Owner moves to back of queue ONLY after 3 slots (threshold)
     // This is the core X3 matrix behavior
     // ============================================================
-    let should_recycle = owner_ls.slots_filled == threshold;
-
     if should_recycle {
         owner_ls.cycles = owner_ls.cycles.saturating_add(1);
         owner_ls.slots_filled = 0;
-        emit!(LevelRecycled {
+        events.emit(LevelRecycled {
             owner: owner_ls.authority,
             level: owner_ls.level,
             cycles: owner_ls.cycles,
             lamports: price,
             timestamp: now,
-        });
-    }
-
-    // Save owner's LevelState
-    {
-        let mut writable = accounts.owner_level_state.try_borrow_mut_data()?;
-        let mut cursor = Cursor::new(&mut writable[..]);
-        owner_ls.try_serialize(&mut cursor)?;
+            schema_version: EVENT_SCHEMA_VERSION,
+            event_seq: next_event_seq(&mut pool.event_seq)?,
+        })?;
     }
 
     let mut used_new_page = false;
@@ -999,44 +2264,80 @@ fn process_existing_activation<'info>(
     // This ensures true X3 behavior (owner receives 3 slots first)
     // ============================================================
     if should_recycle {
-        head_page.players.remove(0);
-        if head_page.players.is_empty() {
-            if let Some(next) = head_page.next_page {
-                accounts.level_pool.head_page = Some(next);
-            } else {
-                accounts.level_pool.head_page = Some(head_key);
-                accounts.level_pool.tail_page = Some(head_key);
-            }
-        }
-        write_queue_page(&accounts.head_page, &mut head_page)?;
-        accounts.level_pool.total_dequeued = accounts.level_pool.total_dequeued.saturating_add(1);
+        dequeue_head_owner(
+            pool,
+            &accounts.head_page,
+            head_key,
+            &mut head_page,
+            &mut owner_ls,
+        )?;
 
         // Owner returns to end of queue after completing cycle
         enqueue_into_tail(
             level,
-            &mut accounts.level_pool,
+            accounts.level_pool.key(),
+            pool,
             payer,
             system_program_acc,
             &accounts.tail_page,
             &accounts.new_page,
             owner_player_pda,
+            &mut owner_ls,
             &mut used_new_page,
+            events,
         )?;
     }
 
-    // Activator always joins queue (if not already in tail page)
+    // Save owner's LevelState (slots/cycles + queue membership)
+    {
+        let mut writable = accounts.owner_level_state.try_borrow_mut_data()?;
+        let mut cursor = Cursor::new(&mut writable[..]);
+        owner_ls.try_serialize(&mut cursor)?;
+    }
+
+    // Activator always joins queue (duplicates rejected via LevelState membership)
+    let player_key = accounts.player.key();
     enqueue_into_tail(
         level,
-        &mut accounts.level_pool,
+        accounts.level_pool.key(),
+        pool,
         payer,
         system_program_acc,
         &accounts.tail_page,
         &accounts.new_page,
-        accounts.player.key(),
+        player_key,
+        &mut accounts.level_state,
         &mut used_new_page,
+        events,
     )?;
 
-    Ok(())
+    if used_new_page {
+        bookkeeping_rent = bookkeeping_rent
+            .saturating_add(Rent::get()?.minimum_balance(QueuePage::space_default()));
+    }
+    emit_payout_distributed(
+        accounts,
+        pool,
+        events,
+        level,
+        price,
+        &payout,
+        redirects,
+        treasury_amount,
+        bookkeeping_rent,
+        false,
+    )?;
+    let reserve_ai = accounts.rent_reserve.as_ref().map(|r| r.to_account_info());
+    settle_activation(
+        &payer_ai,
+        &accounts.treasury_account.to_account_info(),
+        reserve_ai.as_ref(),
+        rent_reserve_bump,
+        &sys,
+        token,
+        treasury_amount,
+        bookkeeping_rent,
+    )
 }
 
 // =============================================================
@@ -1083,265 +2384,2139 @@ pub fn handle_init_config_v3(
     cfg.max_levels = max_levels;
     cfg.bump = ctx.bumps.config_v3;
     cfg.version = 3;
-    cfg.version_minor = 12;
+    cfg.version_minor = CONFIG_VERSION_MINOR;
+    cfg.tx_guard_close_delay = MIN_TX_GUARD_CLOSE_DELAY;
+    cfg.legacy_tx_guard = true;
+    cfg.use_treasury_vault = false;
+    cfg.treasury_epoch_limit = 0;
+    cfg.first_activation_delay_exempt = false;
+    cfg.require_sequential_levels = false;
+    cfg.payment_mint = Pubkey::default();
+    cfg.token_level_prices = [0; 16];
+    cfg.usd_price_feed = Pubkey::default();
+    cfg.usd_level_prices_cents = [0; 16];
+    cfg.usd_feed_max_age = DEFAULT_USD_FEED_MAX_AGE;
+    cfg.usd_feed_max_conf_bps = DEFAULT_USD_FEED_MAX_CONF_BPS;
+    cfg.usd_feed_id = PYTH_SOL_USD_FEED_ID;
 
     let stats = &mut ctx.accounts.global_stats;
     stats.total_players = 0;
     stats.last_player = Pubkey::default();
     stats.bump = ctx.bumps.global_stats;
 
-    emit!(ConfigInitialized {
+    event_sink!(ctx.accounts, ctx.bumps).emit(ConfigInitialized {
         admin,
         treasury,
         timestamp: Clock::get()?.unix_timestamp,
-    });
-    Ok(())
+        schema_version: EVENT_SCHEMA_VERSION,
+    })
 }
 
-pub fn handle_create_player_v3(ctx: Context<CreatePlayerV3>, referrer: Option<Pubkey>) -> Result<()> {
-    let player = &mut ctx.accounts.player;
-    let cfg = &ctx.accounts.config_v3;
-    let stats = &mut ctx.accounts.global_stats;
+pub fn handle_migrate_config_v3(ctx: Context<MigrateConfigV3>) -> Result<()> {
+    let cfg_ai = ctx.accounts.config_v3.to_account_info();
+    require_keys_eq!(*cfg_ai.owner, crate::ID, CustomError::AccountCastError);
 
-    player.authority = ctx.accounts.authority.key();
-    player.bump = ctx.bumps.player;
-    player.created_at = Clock::get()?.unix_timestamp;
-    player.games_played = 0;
+    let mut cfg = {
+        let data = cfg_ai.try_borrow_data()?;
+        decode_config_padded(&data)?
+    };
+    require_keys_eq!(ctx.accounts.admin.key(), cfg.admin, CustomError::Unauthorized);
 
-    if let Some(r) = referrer {
-        if let Some(ref_ai) = ctx.accounts.referrer_player.as_ref() {
-            require_keys_eq!(ref_ai.key(), r, CustomError::Unauthorized);
-            let data = ref_ai.try_borrow_data()?;
-            let mut slice: &[u8] = &data;
-            let ref_player =
-                Player::try_deserialize(&mut slice).map_err(|_| error!(CustomError::AccountCastError))?;
+    realloc_if_needed(
+        &cfg_ai,
+        &ctx.accounts.admin.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        8 + ConfigV3::SIZE,
+    )?;
 
-            player.upline1 = ref_player.authority;
-            player.upline2 = ref_player.upline1;
-            player.upline3 = ref_player.upline2;
-        } else {
-            player.upline1 = r;
-            player.upline2 = cfg.admin;
-            player.upline3 = cfg.admin;
-        }
-    } else {
-        if stats.total_players == 0 {
-            player.upline1 = cfg.admin;
-            player.upline2 = cfg.admin;
-            player.upline3 = cfg.admin;
-        } else {
-            let prev = stats.last_player;
-            player.upline1 = prev;
-            player.upline2 = cfg.admin;
-            player.upline3 = cfg.admin;
+    if cfg.version_minor < 13 {
+        cfg.tx_guard_close_delay = MIN_TX_GUARD_CLOSE_DELAY;
+    }
+    if cfg.version_minor < 14 {
+        // Existing clients still send TxGuard PDAs: open the transition window
+        cfg.legacy_tx_guard = true;
+    }
+    // < 15: use_treasury_vault / treasury_epoch_limit default to off / 0 (zero-filled)
+    // < 16: first_activation_delay_exempt defaults to off (zero-filled)
+    // < 17: require_sequential_levels defaults to off (zero-filled)
+    // < 18: payment_mint defaults to lamports (zero-filled)
+    if cfg.version_minor < 19 {
+        // usd_price_feed stays off (zero-filled)
+        cfg.usd_feed_max_age = DEFAULT_USD_FEED_MAX_AGE;
+        cfg.usd_feed_max_conf_bps = DEFAULT_USD_FEED_MAX_CONF_BPS;
+    }
+    if cfg.version_minor < 20 {
+        cfg.usd_feed_id = PYTH_SOL_USD_FEED_ID;
+    }
+    cfg.version_minor = CONFIG_VERSION_MINOR;
+
+    let mut data = cfg_ai.try_borrow_mut_data()?;
+    let mut cursor = Cursor::new(&mut data[..]);
+    cfg.try_serialize(&mut cursor)?;
+    Ok(())
+}
+
+// No level has enqueued anyone yet. `pools`: the LevelPool PDAs of levels 1..=MAX_LEVEL in
+// order; pools that don't exist yet count as empty.
+fn require_no_queues(cfg_key: &Pubkey, pools: &[AccountInfo]) -> Result<()> {
+    require_eq!(pools.len(), MAX_LEVEL as usize, CustomError::PaymentModeLocked);
+    for (level, ai) in (1..=MAX_LEVEL).zip(pools) {
+        let (expected, _) =
+            Pubkey::find_program_address(&[b"level_pool_v1", cfg_key.as_ref(), &[level]], &crate::ID);
+        require_keys_eq!(*ai.key, expected, CustomError::PaymentModeLocked);
+        if ai.owner != &crate::ID || ai.data_is_empty() {
+            continue;
         }
+        let pool = decode_level_pool_padded(&ai.try_borrow_data()?)?;
+        require!(pool.total_enqueued == 0, CustomError::PaymentModeLocked);
     }
+    Ok(())
+}
 
-    stats.last_player = player.authority;
-    stats.total_players = stats.total_players.saturating_add(1);
+pub fn handle_update_config_v3(ctx: Context<UpdateConfigV3>, args: ConfigV3Update) -> Result<()> {
+    let cfg = &mut ctx.accounts.config_v3;
 
-    emit!(PlayerCreated {
-        authority: player.authority,
-        timestamp: player.created_at,
-    });
+    let currency_change = args.payment_mint.is_some_and(|m| m != cfg.payment_mint)
+        || args.token_level_prices.is_some_and(|p| p != cfg.token_level_prices)
+        || args.usd_price_feed.is_some_and(|f| f != cfg.usd_price_feed);
+    if currency_change {
+        require_no_queues(&cfg.key(), ctx.remaining_accounts)?;
+    }
+
+    if let Some(delay) = args.tx_guard_close_delay {
+        cfg.tx_guard_close_delay = delay.max(MIN_TX_GUARD_CLOSE_DELAY);
+    }
+    if let Some(legacy) = args.legacy_tx_guard {
+        cfg.legacy_tx_guard = legacy;
+    }
+    if let Some(use_vault) = args.use_treasury_vault {
+        cfg.use_treasury_vault = use_vault;
+    }
+    if let Some(limit) = args.treasury_epoch_limit {
+        cfg.treasury_epoch_limit = limit;
+    }
+    if let Some(exempt) = args.first_activation_delay_exempt {
+        cfg.first_activation_delay_exempt = exempt;
+    }
+    if let Some(sequential) = args.require_sequential_levels {
+        cfg.require_sequential_levels = sequential;
+    }
+    if let Some(mint) = args.payment_mint {
+        cfg.payment_mint = mint;
+    }
+    if let Some(prices) = args.token_level_prices {
+        cfg.token_level_prices = prices;
+    }
+    if let Some(feed) = args.usd_price_feed {
+        cfg.usd_price_feed = feed;
+    }
+    if let Some(cents) = args.usd_level_prices_cents {
+        cfg.usd_level_prices_cents = cents;
+    }
+    if let Some(max_age) = args.usd_feed_max_age {
+        cfg.usd_feed_max_age = max_age;
+    }
+    if let Some(max_conf) = args.usd_feed_max_conf_bps {
+        cfg.usd_feed_max_conf_bps = max_conf;
+    }
+    if let Some(feed_id) = args.usd_feed_id {
+        cfg.usd_feed_id = feed_id;
+    }
+    require!(!(cfg.token_mode() && cfg.usd_mode()), CustomError::WrongPaymentMode);
     Ok(())
 }
 
-pub fn handle_activate_level_v3(
-    mut ctx: Context<ActivateLevelV3>,
-    level: u8,
-    price_lamports: u64,
-    nonce: u64,
+pub fn handle_init_treasury_vault(ctx: Context<InitTreasuryVault>) -> Result<()> {
+    let vault = &mut ctx.accounts.treasury_vault;
+    vault.bump = ctx.bumps.treasury_vault;
+    vault.epoch = Clock::get()?.epoch;
+    vault.withdrawn_in_epoch = 0;
+    vault.total_withdrawn = 0;
+    Ok(())
+}
+
+// Admin-only; capped at cfg.treasury_epoch_limit per epoch, vault stays rent-exempt.
+pub fn handle_withdraw_treasury(
+    ctx: Context<WithdrawTreasury>,
+    amount: u64,
+    destination: Pubkey,
 ) -> Result<()> {
-    let accounts = &mut ctx.accounts;
+    require_keys_eq!(ctx.accounts.destination.key(), destination, CustomError::Unauthorized);
+    require_system_wallet(&ctx.accounts.destination.to_account_info())?;
 
-    // Auth + bounds
-    require_keys_eq!(
-        accounts.player.authority,
-        accounts.authority.key(),
-        CustomError::Unauthorized
-    );
-    require!(level >= 1 && level <= MAX_LEVEL, CustomError::InvalidLevel);
+    let clock = Clock::get()?;
+    let limit = ctx.accounts.config_v3.treasury_epoch_limit;
+    let vault = &mut ctx.accounts.treasury_vault;
 
-    let cfg = &accounts.config_v3;
-    let expected = expected_price_from_cfg(level, cfg);
-    require!(expected > 0 && price_lamports == expected, CustomError::InvalidPrice);
+    if vault.epoch != clock.epoch {
+        vault.epoch = clock.epoch;
+        vault.withdrawn_in_epoch = 0;
+    }
+    let withdrawn = vault
+        .withdrawn_in_epoch
+        .checked_add(amount)
+        .ok_or(CustomError::Overflow)?;
+    require!(withdrawn <= limit, CustomError::TreasuryEpochLimitExceeded);
+
+    let vault_ai = vault.to_account_info();
+    let available = vault_ai
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(vault_ai.data_len()));
+    require!(amount <= available, CustomError::InsufficientTreasuryFunds);
+
+    vault.sub_lamports(amount)?;
+    ctx.accounts.destination.add_lamports(amount)?;
+
+    vault.withdrawn_in_epoch = withdrawn;
+    vault.total_withdrawn = vault.total_withdrawn.saturating_add(amount);
+
+    let epoch = vault.epoch;
+    event_sink!(ctx.accounts, ctx.bumps).emit(TreasuryWithdrawn {
+        destination,
+        amount,
+        epoch,
+        withdrawn_in_epoch: withdrawn,
+        timestamp: clock.unix_timestamp,
+        schema_version: EVENT_SCHEMA_VERSION,
+    })
+}
 
-    // =========================================================
-    // CRITICAL FIX (SPV Block I-2):
-    // Reject repeated activation of already active level
-    // BEFORE any transfer/enqueue/CPI/queue mutations.
-    // =========================================================
+pub fn handle_set_treasury_split(
+    ctx: Context<SetTreasurySplit>,
+    beneficiaries: Vec<Pubkey>,
+    bps: Vec<u16>,
+) -> Result<()> {
     require!(
-        accounts.level_state.activated_at == 0,
-        CustomError::AlreadyActivated
+        !beneficiaries.is_empty()
+            && beneficiaries.len() <= MAX_TREASURY_BENEFICIARIES
+            && beneficiaries.len() == bps.len()
+            && beneficiaries.iter().all(|b| *b != Pubkey::default()),
+        CustomError::InvalidTreasurySplit
     );
+    let sum: u64 = bps.iter().map(|b| *b as u64).sum();
+    require_eq!(sum, BPS_TOTAL, CustomError::InvalidTreasurySplit);
+
+    let split = &mut ctx.accounts.treasury_split;
+    split.bump = ctx.bumps.treasury_split;
+    split.count = beneficiaries.len() as u8;
+    split.beneficiaries = [Pubkey::default(); MAX_TREASURY_BENEFICIARIES];
+    split.bps = [0; MAX_TREASURY_BENEFICIARIES];
+    split.beneficiaries[..beneficiaries.len()].copy_from_slice(&beneficiaries);
+    split.bps[..bps.len()].copy_from_slice(&bps);
+    Ok(())
+}
 
-    let now = Clock::get()?.unix_timestamp;
-
-    if cfg.min_entry_delay > 0 {
-        let since = now
-            .checked_sub(accounts.player.created_at)
-            .ok_or(CustomError::Overflow)?;
-        require!(
-            (since as u64) >= (cfg.min_entry_delay as u64),
-            CustomError::MinEntryDelay
-        );
+// Permissionless: splits the vault balance above rent across TreasurySplit by bps, capped
+// by what cfg.treasury_epoch_limit still allows this epoch (shared with withdraw_treasury).
+// remaining_accounts: the beneficiary wallets in TreasurySplit order (writable).
+// Rounding dust stays in the vault for the next run.
+pub fn handle_distribute_treasury<'info>(
+    ctx: Context<'_, '_, 'info, 'info, DistributeTreasury<'info>>,
+) -> Result<()> {
+    let split = &ctx.accounts.treasury_split;
+    let count = split.count as usize;
+    require_eq!(ctx.remaining_accounts.len(), count, CustomError::InvalidTreasurySplit);
+
+    let clock = Clock::get()?;
+    let limit = ctx.accounts.config_v3.treasury_epoch_limit;
+    let vault = &mut ctx.accounts.treasury_vault;
+    if vault.epoch != clock.epoch {
+        vault.epoch = clock.epoch;
+        vault.withdrawn_in_epoch = 0;
     }
-
-    // TxGuard (replay protection)
-    {
-        let tg = &mut accounts.tx_guard;
-        tg.nonce = nonce;
-        tg.executed_at = now;
-        tg.bump = ctx.bumps.tx_guard;
+    let allowance = limit.saturating_sub(vault.withdrawn_in_epoch);
+    require!(allowance > 0, CustomError::TreasuryEpochLimitExceeded);
+
+    let vault_ai = vault.to_account_info();
+    let available = vault_ai
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(vault_ai.data_len()))
+        .min(allowance);
+
+    let mut distributed: u64 = 0;
+    for (i, ai) in ctx.remaining_accounts.iter().enumerate() {
+        require_keys_eq!(*ai.key, split.beneficiaries[i], CustomError::InvalidTreasurySplit);
+        require_system_wallet(ai)?;
+
+        let share = ((available as u128) * (split.bps[i] as u128) / (BPS_TOTAL as u128)) as u64;
+        if share == 0 {
+            continue;
+        }
+        ai.add_lamports(share)?;
+        distributed = distributed.checked_add(share).ok_or(CustomError::Overflow)?;
     }
+    vault_ai.sub_lamports(distributed)?;
+
+    let vault = &mut ctx.accounts.treasury_vault;
+    vault.withdrawn_in_epoch = vault.withdrawn_in_epoch.saturating_add(distributed);
+    vault.total_withdrawn = vault.total_withdrawn.saturating_add(distributed);
+
+    let split = &mut ctx.accounts.treasury_split;
+    split.total_distributed = split.total_distributed.saturating_add(distributed);
+
+    let beneficiaries = split.count;
+    event_sink!(ctx.accounts, ctx.bumps).emit(TreasuryDistributed {
+        amount: distributed,
+        beneficiaries,
+        timestamp: clock.unix_timestamp,
+        schema_version: EVENT_SCHEMA_VERSION,
+    })
+}
 
-    // LevelState activator init (activated_at only once)
-    {
-        let ls_me = &mut accounts.level_state;
+// Moves a Player to `new_authority`: Player.authority, the AuthorityLink lookup and
+// every LevelState passed in `level_states` (legacy layouts are grown, payer funds).
+fn apply_rotation<'info>(
+    player: &mut Player,
+    player_key: Pubkey,
+    new_authority: Pubkey,
+    link: &mut AuthorityLink,
+    link_bump: u8,
+    level_states: &[AccountInfo<'info>],
+    payer: &AccountInfo<'info>,
+    sys: &AccountInfo<'info>,
+) -> Result<Pubkey> {
+    require!(
+        new_authority != Pubkey::default() && new_authority != player.authority,
+        CustomError::InvalidNewAuthority
+    );
+    // A wallet can be linked to one Player only
+    require!(
+        link.player == Pubkey::default() || link.player == player_key,
+        CustomError::InvalidNewAuthority
+    );
+    link.player = player_key;
+    link.bump = link_bump;
+
+    let old_authority = player.authority;
+    player.authority = new_authority;
+
+    // Every level, so no LevelState keeps paying ref shares to the old wallet
+    require_eq!(level_states.len(), MAX_LEVEL as usize, CustomError::LevelStatesIncomplete);
+    for (level, ai) in (1..=MAX_LEVEL).zip(level_states) {
+        let (expected, _) = derive_level_state_pda(&player_key, level);
+        require_keys_eq!(*ai.key, expected, CustomError::LevelStatesIncomplete);
+        if ai.data_is_empty() {
+            continue;
+        }
+        require_keys_eq!(*ai.owner, crate::ID, CustomError::AccountCastError);
+        realloc_if_needed(ai, payer, sys, 8 + LevelState::SIZE)?;
 
-        // (activated_at guaranteed == 0 from guard above)
-        ls_me.player = accounts.player.key();
-        ls_me.authority = accounts.authority.key();
-        ls_me.level = level;
-        ls_me.bump = ctx.bumps.level_state;
-        ls_me.activated_at = now;
-        ls_me.cycles = 0;
-        ls_me.slots_filled = 0;
-        ls_me.head_page = None;
-        ls_me.tail_page = None;
+        let mut ls = load_or_migrate_level_state(ai)?;
+        require_keys_eq!(ls.player, player_key, CustomError::Unauthorized);
 
-        emit!(LevelActivated {
-            owner: ls_me.authority,
-            level,
-            lamports: expected,
-            timestamp: now,
-        });
+        ls.authority = new_authority;
+        let mut data = ai.try_borrow_mut_data()?;
+        let mut cursor = Cursor::new(&mut data[..]);
+        ls.try_serialize(&mut cursor)?;
     }
+    Ok(old_authority)
+}
 
-    // LevelPool init/check (state only)
-    if accounts.level_pool.level == 0 {
-        accounts.level_pool.config = accounts.config_v3.key();
-        accounts.level_pool.level = level;
-        accounts.level_pool.bump = ctx.bumps.level_pool;
-        accounts.level_pool.head_page = None;
-        accounts.level_pool.tail_page = None;
-        accounts.level_pool.total_enqueued = 0;
-        accounts.level_pool.total_dequeued = 0;
-    }
-    require_eq!(accounts.level_pool.level, level, CustomError::InvalidLevel);
-    require_keys_eq!(accounts.level_pool.config, accounts.config_v3.key());
+// Current authority signs; remaining_accounts: the Player's LevelState PDAs of levels
+// 1..=MAX_LEVEL in order (writable; levels never activated may not exist yet).
+pub fn handle_rotate_authority<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RotateAuthority<'info>>,
+    new_authority: Pubkey,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    let player_key = accounts.player.key();
+    let old_authority = apply_rotation(
+        &mut accounts.player,
+        player_key,
+        new_authority,
+        &mut accounts.authority_link,
+        ctx.bumps.authority_link,
+        ctx.remaining_accounts,
+        &accounts.authority.to_account_info(),
+        &accounts.system_program.to_account_info(),
+    )?;
 
-    // Branch: first activation creates first page, 100% -> treasury
-    if accounts.level_pool.head_page.is_none() {
-        return process_first_activation(accounts, level, expected);
+    // A rotation supersedes any guardian recovery in flight
+    if let Some(recovery) = accounts.recovery.as_mut() {
+        recovery.pending_authority = Pubkey::default();
+        recovery.unlock_at = 0;
     }
 
-    // Branch: existing queue (normal payouts with X3 logic)
-    process_existing_activation(accounts, level, expected, now)
+    event_sink!(accounts, ctx.bumps).emit(AuthorityRotated {
+        player: player_key,
+        old_authority,
+        new_authority,
+        by_guardian: false,
+        timestamp: Clock::get()?.unix_timestamp,
+        schema_version: EVENT_SCHEMA_VERSION,
+    })
 }
 
-// legacy placeholders (keep IDL stable)
-pub fn handle_recycle_level_v3(
-    _ctx: Context<RecycleLevelV3>,
-    _level: u8,
-    _price_lamports: u64,
-    _nonce: u64,
+// guardian = None disables recovery; also cancels a pending recovery.
+pub fn handle_set_guardian(
+    ctx: Context<SetGuardian>,
+    guardian: Option<Pubkey>,
+    delay: u32,
 ) -> Result<()> {
+    let recovery = &mut ctx.accounts.recovery;
+    recovery.player = ctx.accounts.player.key();
+    recovery.guardian = guardian.unwrap_or_default();
+    recovery.delay = delay.max(MIN_RECOVERY_DELAY);
+    recovery.pending_authority = Pubkey::default();
+    recovery.unlock_at = 0;
+    recovery.bump = ctx.bumps.recovery;
+    Ok(())
+}
+
+pub fn handle_start_recovery(ctx: Context<StartRecovery>, new_authority: Pubkey) -> Result<()> {
+    require!(new_authority != Pubkey::default(), CustomError::InvalidNewAuthority);
+
+    let recovery = &mut ctx.accounts.recovery;
+    let unlock_at = Clock::get()?
+        .unix_timestamp
+        .checked_add(recovery.delay as i64)
+        .ok_or(CustomError::Overflow)?;
+    recovery.pending_authority = new_authority;
+    recovery.unlock_at = unlock_at;
+
+    let event = RecoveryStarted {
+        player: recovery.player,
+        guardian: recovery.guardian,
+        new_authority,
+        unlock_at,
+        schema_version: EVENT_SCHEMA_VERSION,
+    };
+    event_sink!(ctx.accounts, ctx.bumps).emit(event)
+}
+
+pub fn handle_cancel_recovery(ctx: Context<CancelRecovery>) -> Result<()> {
+    let recovery = &mut ctx.accounts.recovery;
+    require!(recovery.pending_authority != Pubkey::default(), CustomError::RecoveryNotPending);
+    recovery.pending_authority = Pubkey::default();
+    recovery.unlock_at = 0;
+    Ok(())
+}
+
+// Guardian signs after the timelock; remaining_accounts as in rotate_authority.
+pub fn handle_complete_recovery<'info>(
+    ctx: Context<'_, '_, 'info, 'info, CompleteRecovery<'info>>,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    let now = Clock::get()?.unix_timestamp;
+
+    let new_authority = accounts.recovery.pending_authority;
+    require!(new_authority != Pubkey::default(), CustomError::RecoveryNotPending);
+    require!(now >= accounts.recovery.unlock_at, CustomError::RecoveryTimelocked);
+
+    let player_key = accounts.player.key();
+    let old_authority = apply_rotation(
+        &mut accounts.player,
+        player_key,
+        new_authority,
+        &mut accounts.authority_link,
+        ctx.bumps.authority_link,
+        ctx.remaining_accounts,
+        &accounts.guardian.to_account_info(),
+        &accounts.system_program.to_account_info(),
+    )?;
+
+    accounts.recovery.pending_authority = Pubkey::default();
+    accounts.recovery.unlock_at = 0;
+
+    event_sink!(accounts, ctx.bumps).emit(AuthorityRotated {
+        player: player_key,
+        old_authority,
+        new_authority,
+        by_guardian: true,
+        timestamp: now,
+        schema_version: EVENT_SCHEMA_VERSION,
+    })
+}
+
+// Orphan sign-ups (no referrer) hang under the previous player of the same shard;
+// before the shard has anyone, fall back to the legacy GlobalStats.last_player.
+fn orphan_upline(shard: &StatsShard, global: &GlobalStats) -> Option<Pubkey> {
+    if shard.total_players > 0 {
+        Some(shard.last_player)
+    } else if global.total_players > 0 {
+        Some(global.last_player)
+    } else {
+        None
+    }
+}
+
+fn assign_uplines(
+    player: &mut Player,
+    cfg: &ConfigV3,
+    referrer: Option<Pubkey>,
+    referrer_ai: Option<&AccountInfo>,
+    orphan: Option<Pubkey>,
+) -> Result<()> {
+    if let Some(r) = referrer {
+        if let Some(ref_ai) = referrer_ai {
+            require_keys_eq!(ref_ai.key(), r, CustomError::Unauthorized);
+            let ref_player = read_player(ref_ai)?;
+
+            // The Player PDA, not its wallet: stays correct across rotate_authority
+            player.upline1 = r;
+            player.upline2 = ref_player.upline1;
+            player.upline3 = ref_player.upline2;
+        } else {
+            player.upline1 = r;
+            player.upline2 = cfg.admin;
+            player.upline3 = cfg.admin;
+        }
+    } else {
+        player.upline1 = orphan.unwrap_or(cfg.admin);
+        player.upline2 = cfg.admin;
+        player.upline3 = cfg.admin;
+    }
+    Ok(())
+}
+
+fn record_player_in_shard(shard: &mut StatsShard, authority: Pubkey) {
+    shard.last_player = authority;
+    shard.total_players = shard.total_players.saturating_add(1);
+}
+
+pub fn handle_create_player_v3(ctx: Context<CreatePlayerV3>, referrer: Option<Pubkey>) -> Result<()> {
+    let shard_ai = ctx.accounts.stats_shard.to_account_info();
+    let mut shard = load_stats_shard_grown(
+        &shard_ai,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+    )?;
+    let player = &mut ctx.accounts.player;
+    let cfg = &ctx.accounts.config_v3;
+
+    player.authority = ctx.accounts.authority.key();
+    player.bump = ctx.bumps.player;
+    player.created_at = Clock::get()?.unix_timestamp;
+    player.games_played = 0;
+    player.next_nonce = 0;
+
+    let orphan = orphan_upline(&shard, &ctx.accounts.global_stats);
+    assign_uplines(
+        player,
+        cfg,
+        referrer,
+        ctx.accounts.referrer_player.as_ref(),
+        orphan,
+    )?;
+    record_player_in_shard(&mut shard, player.authority);
+
+    let event = PlayerCreated {
+        authority: player.authority,
+        timestamp: player.created_at,
+        schema_version: EVENT_SCHEMA_VERSION,
+        shard: shard.shard,
+        event_seq: next_event_seq(&mut shard.event_seq)?,
+    };
+    write_stats_shard(&shard_ai, &shard)?;
+    event_sink!(ctx.accounts, ctx.bumps).emit(event)
+}
+
+// Charges the current level price; reverts only if it is above `max_price_lamports`.
+pub fn handle_activate_level_v3(
+    ctx: Context<ActivateLevelV3>,
+    level: u8,
+    max_price_lamports: u64,
+    nonce: u64,
+) -> Result<()> {
+    // Auth: the signer pays for their own Player
+    let signer = ctx.accounts.authority.key();
+    activate_level_core(ctx.accounts, &ctx.bumps, signer, level, max_price_lamports, nonce, false, None)?;
+    Ok(())
+}
+
+// USD mode: the lamport price is derived from the feed at execution time. `quoted_lamports`
+// (e.g. from quote_activation) may be exceeded by at most `max_slippage_bps`.
+// Args keep activate_level_v3's (level, price, nonce) prefix for the shared account seeds.
+pub fn handle_activate_level_usd(
+    ctx: Context<ActivateLevelV3>,
+    level: u8,
+    quoted_lamports: u64,
+    nonce: u64,
+    max_slippage_bps: u16,
+) -> Result<()> {
+    require!(ctx.accounts.config_v3.usd_mode(), CustomError::WrongPaymentMode);
+    let max_price = (quoted_lamports as u128) * (BPS_TOTAL as u128 + max_slippage_bps as u128)
+        / BPS_TOTAL as u128;
+    let max_price = u64::try_from(max_price).unwrap_or(u64::MAX);
+    let signer = ctx.accounts.authority.key();
+    activate_level_core(ctx.accounts, &ctx.bumps, signer, level, max_price, nonce, false, None)?;
+    Ok(())
+}
+
+// Token mode: same flow as activate_level_v3, price and payouts in cfg.payment_mint
+// (SPL Token or Token-2022; transfer-hook mints are not supported).
+// Missing recipient ATAs are created by the activator (rent refunded from the reserve
+// when it is funded).
+pub fn handle_activate_level_token(
+    ctx: Context<ActivateLevelToken>,
+    level: u8,
+    max_price: u64,
+    nonce: u64,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    // Vault withdrawals are lamports-only: the token share goes to cfg.treasury's ATA
+    require!(
+        !accounts.activate.config_v3.use_treasury_vault,
+        CustomError::InvalidTreasury
+    );
+
+    let mint_ai = accounts.mint.to_account_info();
+    let token = TokenPayment {
+        transfer_fee: mint_transfer_fee(&mint_ai)?,
+        mint: mint_ai,
+        decimals: accounts.mint.decimals,
+        payer_token: accounts.payer_token.to_account_info(),
+        token_program: accounts.token_program.to_account_info(),
+        associated_token_program: accounts.associated_token_program.to_account_info(),
+        recipients: [
+            accounts.owner_token.to_account_info(),
+            accounts.ref1_token.to_account_info(),
+            accounts.ref2_token.to_account_info(),
+            accounts.ref3_token.to_account_info(),
+            accounts.treasury_token.to_account_info(),
+        ],
+    };
+    let signer = accounts.activate.authority.key();
+    activate_level_core(
+        &mut accounts.activate,
+        &ctx.bumps.activate,
+        signer,
+        level,
+        max_price,
+        nonce,
+        false,
+        Some(&token),
+    )?;
+    Ok(())
+}
+
+// Sponsored activation: `activate.authority` is the payer, `activate.player` the beneficiary.
+// Consent: the beneficiary wallet co-signs, or has a GiftOptIn for this payer (or any payer).
+pub fn handle_activate_level_for(
+    ctx: Context<ActivateLevelFor>,
+    level: u8,
+    max_price_lamports: u64,
+    nonce: u64,
+) -> Result<()> {
+    let accounts = ctx.accounts;
+    let payer = accounts.activate.authority.key();
+    let beneficiary = accounts.beneficiary.key();
+
+    let opted_in = accounts
+        .gift_opt_in
+        .as_ref()
+        .is_some_and(|o| o.sponsor == Pubkey::default() || o.sponsor == payer);
+    require!(accounts.beneficiary.is_signer || opted_in, CustomError::GiftNotAccepted);
+
+    // The beneficiary must control the Player (checked in core)
+    let price = activate_level_core(
+        &mut accounts.activate,
+        &ctx.bumps.activate,
+        beneficiary,
+        level,
+        max_price_lamports,
+        nonce,
+        false,
+        None,
+    )?;
+
+    // Core wrote the (grown) LevelPool; continue its event stream
+    let pool_ai = accounts.activate.level_pool.to_account_info();
+    let mut pool = decode_level_pool_padded(&pool_ai.try_borrow_data()?)?;
+    event_sink!(accounts.activate, ctx.bumps.activate).emit(LevelGifted {
+        payer,
+        beneficiary,
+        player: accounts.activate.player.key(),
+        level,
+        lamports: price,
+        timestamp: accounts.activate.level_state.activated_at,
+        schema_version: EVENT_SCHEMA_VERSION,
+        event_seq: next_event_seq(&mut pool.event_seq)?,
+    })?;
+    write_level_pool(&pool_ai, &pool)
+}
+
+pub fn handle_set_gift_opt_in(ctx: Context<SetGiftOptIn>, sponsor: Option<Pubkey>) -> Result<()> {
+    let opt_in = &mut ctx.accounts.gift_opt_in;
+    opt_in.player = ctx.accounts.player.key();
+    opt_in.sponsor = sponsor.unwrap_or_default();
+    opt_in.bump = ctx.bumps.gift_opt_in;
+    Ok(())
+}
+
+pub fn handle_revoke_gift_opt_in(_ctx: Context<RevokeGiftOptIn>) -> Result<()> {
+    Ok(())
+}
+
+// Shared by activate_level_v3 / activate_level_for / register_and_activate: `authority`
+// pays price + rent, the level is credited to `player`, whose authority must be
+// `player_authority` (the signer, or the gift beneficiary).
+// Returns the price charged (current level price, at most `max_price`).
+fn activate_level_core<'info>(
+    accounts: &mut ActivateLevelV3<'info>,
+    bumps: &ActivateLevelV3Bumps,
+    player_authority: Pubkey,
+    level: u8,
+    max_price: u64,
+    nonce: u64,
+    skip_entry_delay: bool,
+    token: Option<&TokenPayment<'info>>,
+) -> Result<u64> {
+    // Bounds
+    require!((1..=MAX_LEVEL).contains(&level), CustomError::InvalidLevel);
+
+    let player_ai = accounts.player.to_account_info();
+    let mut player = load_player_grown(
+        &player_ai,
+        &accounts.authority.to_account_info(),
+        &accounts.system_program.to_account_info(),
+    )?;
+    require_keys_eq!(player.authority, player_authority, CustomError::Unauthorized);
+
+    let cfg = &accounts.config_v3;
+    require!(token.is_some() == cfg.token_mode(), CustomError::WrongPaymentMode);
+    let price_feed = accounts.price_feed.as_ref().map(|f| f.to_account_info());
+    let expected = level_price(level, cfg, price_feed.as_ref())?;
+    require!(expected > 0, CustomError::InvalidPrice);
+    require!(expected <= max_price, CustomError::PriceSlippageExceeded);
+
+    // =========================================================
+    // CRITICAL FIX (SPV Block I-2):
+    // Reject repeated activation of already active level
+    // BEFORE any transfer/enqueue/CPI/queue mutations.
+    // =========================================================
+    require!(
+        accounts.level_state.activated_at == 0,
+        CustomError::AlreadyActivated
+    );
+
+    if cfg.require_sequential_levels && level > 1 {
+        let prev = accounts
+            .previous_level_state
+            .as_ref()
+            .ok_or(CustomError::PreviousLevelRequired)?;
+        require_previous_level(&accounts.player.key(), level, prev)?;
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let events = event_sink!(accounts, bumps);
+
+    if cfg.min_entry_delay > 0 && !skip_entry_delay {
+        let since = now
+            .checked_sub(player.created_at)
+            .ok_or(CustomError::Overflow)?;
+        require!(
+            (since as u64) >= (cfg.min_entry_delay as u64),
+            CustomError::MinEntryDelay
+        );
+    }
+
+    // Replay protection (Player.next_nonce, or legacy TxGuard)
+    consume_nonce(
+        &mut player,
+        &accounts.config_v3,
+        accounts.tx_guard.as_mut(),
+        bumps.tx_guard,
+        nonce,
+        now,
+    )?;
+    write_player(&player_ai, &player)?;
+
+    // LevelPool: created on the level's first activation, older layouts grown
+    let pool_ai = accounts.level_pool.to_account_info();
+    let (mut pool, pool_created) = load_level_pool_grown(
+        &pool_ai,
+        &accounts.authority.to_account_info(),
+        &accounts.system_program.to_account_info(),
+        &accounts.config_v3.key(),
+        level,
+        bumps.level_pool,
+    )?;
+    require_eq!(pool.level, level, CustomError::InvalidLevel);
+    require_keys_eq!(pool.config, accounts.config_v3.key());
+
+    // LevelState activator init (activated_at only once)
+    {
+        let ls_me = &mut accounts.level_state;
+
+        // (activated_at guaranteed == 0 from guard above)
+        ls_me.player = accounts.player.key();
+        ls_me.authority = player.authority;
+        ls_me.level = level;
+        ls_me.bump = bumps.level_state;
+        ls_me.activated_at = now;
+        ls_me.cycles = 0;
+        ls_me.slots_filled = 0;
+        ls_me.head_page = None;
+        ls_me.tail_page = None;
+        ls_me.queue_page = None;
+        ls_me.queue_pos = 0;
+
+        events.emit(LevelActivated {
+            owner: ls_me.authority,
+            level,
+            lamports: expected,
+            timestamp: now,
+            actual_price: expected,
+            schema_version: EVENT_SCHEMA_VERSION,
+            event_seq: next_event_seq(&mut pool.event_seq)?,
+            max_price,
+        })?;
+    }
+
+    if pool.head_page.is_none() {
+        // Branch: first activation creates first page, 100% -> treasury
+        process_first_activation(
+            accounts,
+            &mut pool,
+            level,
+            expected,
+            pool_created,
+            bumps.rent_reserve,
+            token,
+            &events,
+        )?;
+    } else {
+        // Branch: existing queue (normal payouts with X3 logic)
+        process_existing_activation(
+            accounts,
+            &mut pool,
+            level,
+            expected,
+            now,
+            bumps.rent_reserve,
+            token,
+            &events,
+        )?;
+    }
+    write_level_pool(&pool_ai, &pool)?;
+    Ok(expected)
+}
+
+// legacy placeholders (keep IDL stable)
+pub fn handle_recycle_level_v3(
+    _ctx: Context<RecycleLevelV3>,
+    _level: u8,
+    _price_lamports: u64,
+    _nonce: u64,
+) -> Result<()> {
+    Ok(())
+}
+pub fn handle_claim_slot(_ctx: Context<ClaimSlot>) -> Result<()> {
     Ok(())
 }
-pub fn handle_claim_slot(_ctx: Context<ClaimSlot>) -> Result<()> {
-    Ok(())
-}
 pub fn handle_enqueue_player(_ctx: Context<EnqueuePlayer>) -> Result<()> {
     Ok(())
 }
-pub fn handle_dequeue_owner(_ctx: Context<DequeueOwner>) -> Result<Pubkey> {
-    Ok(Pubkey::default())
+pub fn handle_dequeue_owner(_ctx: Context<DequeueOwner>) -> Result<Pubkey> {
+    Ok(Pubkey::default())
+}
+
+pub fn handle_register_player(
+    ctx: Context<RegisterPlayer>,
+    referrer: Option<Pubkey>,
+    nonce: u64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let accounts = ctx.accounts;
+    let shard_ai = accounts.stats_shard.to_account_info();
+    let mut shard = load_stats_shard_grown(
+        &shard_ai,
+        &accounts.authority.to_account_info(),
+        &accounts.system_program.to_account_info(),
+    )?;
+    init_registered_player(
+        &mut accounts.player,
+        accounts.authority.key(),
+        ctx.bumps.player,
+        now,
+        &accounts.config_v3,
+        &mut shard,
+        &accounts.global_stats,
+        referrer,
+        accounts.referrer_player.as_ref(),
+        &event_sink!(accounts, ctx.bumps),
+    )?;
+    write_stats_shard(&shard_ai, &shard)?;
+
+    // New Player starts at next_nonce 0, so the nonce path requires nonce == 0
+    consume_nonce(
+        &mut accounts.player,
+        &accounts.config_v3,
+        accounts.tx_guard.as_mut(),
+        ctx.bumps.tx_guard,
+        nonce,
+        now,
+    )
+}
+
+// Validates the next ActivateLevelV3 account group (same constraints as the instruction,
+// incl. init_if_needed CPIs) from a remaining_accounts slice. Boxed: keeps the SBF stack small.
+fn load_activation_group<'info>(
+    accounts: &mut &'info [AccountInfo<'info>],
+    level: u8,
+    max_price_lamports: u64,
+    nonce: u64,
+) -> Result<(Box<ActivateLevelV3<'info>>, ActivateLevelV3Bumps)> {
+    let mut bumps = ActivateLevelV3Bumps::default();
+    let mut reallocs = BTreeSet::new();
+    let ix_data = [&[level][..], &max_price_lamports.to_le_bytes(), &nonce.to_le_bytes()].concat();
+    let group = ActivateLevelV3::try_accounts(&crate::ID, accounts, &ix_data, &mut bumps, &mut reallocs)?;
+    Ok((Box::new(group), bumps))
+}
+
+// 1..=MAX_BATCH_LEVELS distinct levels, one max price per level
+fn check_batch_args(levels: &[u8], max_prices: &[u64]) -> Result<()> {
+    require!(
+        !levels.is_empty() && levels.len() <= MAX_BATCH_LEVELS,
+        CustomError::InvalidBatch
+    );
+    require_eq!(max_prices.len(), levels.len(), CustomError::InvalidBatch);
+    for (i, level) in levels.iter().enumerate() {
+        require!(!levels[..i].contains(level), CustomError::InvalidBatch);
+    }
+    Ok(())
+}
+
+// Several levels for one Player in one instruction; a failing level reverts them all.
+// remaining_accounts: one full activate_level_v3 account list per level (incl. the trailing
+// rent_reserve / previous_level_state / price_feed slots), in `levels` order. Level i consumes
+// nonce + i and is capped by max_prices[i] (as max_price_lamports in activate_level_v3).
+// Not available in USD mode (no quoted price per level).
+pub fn handle_activate_levels_batch<'info>(
+    ctx: Context<'_, '_, 'info, 'info, ActivateLevelsBatch<'info>>,
+    levels: Vec<u8>,
+    max_prices: Vec<u64>,
+    nonce: u64,
+) -> Result<()> {
+    check_batch_args(&levels, &max_prices)?;
+    require!(!ctx.accounts.config_v3.usd_mode(), CustomError::WrongPaymentMode);
+
+    let player_key = ctx.accounts.player.key();
+    let authority_key = ctx.accounts.authority.key();
+    let mut rest: &'info [AccountInfo<'info>] = ctx.remaining_accounts;
+
+    for (i, (&level, &max_price)) in levels.iter().zip(&max_prices).enumerate() {
+        let level_nonce = nonce.checked_add(i as u64).ok_or(CustomError::Overflow)?;
+
+        // Each group re-reads the Player written by the previous one (next_nonce)
+        let (mut activation, bumps) =
+            load_activation_group(&mut rest, level, max_price, level_nonce)?;
+        require_keys_eq!(activation.player.key(), player_key, CustomError::InvalidBatch);
+        require_keys_eq!(activation.authority.key(), authority_key, CustomError::InvalidBatch);
+
+        activate_level_core(
+            &mut activation,
+            &bumps,
+            authority_key,
+            level,
+            max_price,
+            level_nonce,
+            false,
+            None,
+        )?;
+        activation.exit(&crate::ID)?;
+    }
+    require!(rest.is_empty(), CustomError::InvalidBatch);
+    Ok(())
+}
+
+fn init_registered_player(
+    player: &mut Player,
+    authority: Pubkey,
+    bump: u8,
+    now: i64,
+    cfg: &ConfigV3,
+    shard: &mut StatsShard,
+    global: &GlobalStats,
+    referrer: Option<Pubkey>,
+    referrer_ai: Option<&AccountInfo>,
+    events: &EventSink,
+) -> Result<()> {
+    player.authority = authority;
+    player.bump = bump;
+    player.created_at = now;
+    player.games_played = 0;
+    player.next_nonce = 0;
+
+    let orphan = orphan_upline(shard, global);
+    assign_uplines(player, cfg, referrer, referrer_ai, orphan)?;
+    record_player_in_shard(shard, player.authority);
+
+    events.emit(PlayerCreated {
+        authority: player.authority,
+        timestamp: player.created_at,
+        schema_version: EVENT_SCHEMA_VERSION,
+        shard: shard.shard,
+        event_seq: next_event_seq(&mut shard.event_seq)?,
+    })
+}
+
+// Sign-up + first activation in one instruction, only when the entry delay can't block it.
+// remaining_accounts: the activate_level_v3 account list (same order), validated as strictly
+// as ActivateLevelV3; its player/authority must be the Player created here and the signer.
+// `nonce` is the new Player's first nonce (0), consumed by the activation.
+pub fn handle_register_and_activate<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RegisterAndActivate<'info>>,
+    referrer: Option<Pubkey>,
+    level: u8,
+    max_price_lamports: u64,
+    nonce: u64,
+) -> Result<()> {
+    let cfg = &ctx.accounts.config_v3;
+    require!(
+        cfg.min_entry_delay == 0 || cfg.first_activation_delay_exempt,
+        CustomError::MinEntryDelay
+    );
+
+    let now = Clock::get()?.unix_timestamp;
+    let accounts = ctx.accounts;
+    let shard_ai = accounts.stats_shard.to_account_info();
+    let mut shard = load_stats_shard_grown(
+        &shard_ai,
+        &accounts.authority.to_account_info(),
+        &accounts.system_program.to_account_info(),
+    )?;
+    init_registered_player(
+        &mut accounts.player,
+        accounts.authority.key(),
+        ctx.bumps.player,
+        now,
+        &accounts.config_v3,
+        &mut shard,
+        &accounts.global_stats,
+        referrer,
+        accounts.referrer_player.as_ref(),
+        &event_sink!(accounts, ctx.bumps),
+    )?;
+    write_stats_shard(&shard_ai, &shard)?;
+    // Flush so the activation group deserializes the new Player
+    accounts.player.exit(&crate::ID)?;
+
+    let mut group: &'info [AccountInfo<'info>] = ctx.remaining_accounts;
+    let (mut activation, bumps) = load_activation_group(&mut group, level, max_price_lamports, nonce)?;
+
+    require_keys_eq!(activation.player.key(), accounts.player.key(), CustomError::Unauthorized);
+    require_keys_eq!(activation.authority.key(), accounts.authority.key(), CustomError::Unauthorized);
+
+    let authority_key = accounts.authority.key();
+    activate_level_core(&mut activation, &bumps, authority_key, level, max_price_lamports, nonce, true, None)?;
+    activation.exit(&crate::ID)?;
+
+    // The activation wrote Player.next_nonce; don't let the outer copy overwrite it on exit
+    accounts.player.reload()
+}
+
+// Returns TxGuard rent to its authority once the guard is older than
+// cfg.tx_guard_close_delay (never below MIN_TX_GUARD_CLOSE_DELAY).
+pub fn handle_close_tx_guard(
+    ctx: Context<CloseTxGuard>,
+    kind: u8,
+    level: u8,
+    nonce: u64,
+) -> Result<()> {
+    let authority = ctx.accounts.authority.key();
+    let expected = tx_guard_pda(kind, &authority, &ctx.accounts.player.key(), level, nonce)?;
+    require_keys_eq!(ctx.accounts.tx_guard.key(), expected, CustomError::Unauthorized);
+
+    let tg = &ctx.accounts.tx_guard;
+    require_eq!(tg.nonce, nonce, CustomError::Unauthorized);
+
+    let delay = ctx.accounts.config_v3.tx_guard_close_delay.max(MIN_TX_GUARD_CLOSE_DELAY);
+    let now = Clock::get()?.unix_timestamp;
+    let age = now.checked_sub(tg.executed_at).ok_or(CustomError::Overflow)?;
+    require!(age >= delay as i64, CustomError::TxGuardTooYoung);
+
+    ctx.accounts
+        .tx_guard
+        .close(ctx.accounts.authority.to_account_info())
+}
+
+// Grows a pre-next_nonce Player account (authority pays the rent top-up). Activation grows
+// it on its own; the other instructions that take `Account<Player>` need this first.
+pub fn handle_upgrade_player(ctx: Context<UpgradePlayer>) -> Result<()> {
+    let player_ai = ctx.accounts.player.to_account_info();
+    require_keys_eq!(*player_ai.owner, crate::ID, CustomError::AccountCastError);
+
+    let player = read_player(&player_ai)?;
+    require_keys_eq!(player.authority, ctx.accounts.authority.key(), CustomError::Unauthorized);
+
+    realloc_if_needed(
+        &player_ai,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        8 + Player::SIZE,
+    )?;
+
+    let mut data = player_ai.try_borrow_mut_data()?;
+    let mut cursor = Cursor::new(&mut data[..]);
+    player.try_serialize(&mut cursor)?;
+    Ok(())
+}
+
+pub fn handle_init_stats_shard(ctx: Context<InitStatsShard>, shard: u8) -> Result<()> {
+    require!(shard < STATS_SHARD_COUNT, CustomError::InvalidStatsShard);
+    require_keys_eq!(ctx.accounts.admin.key(), ctx.accounts.config_v3.admin, CustomError::Unauthorized);
+
+    let s = &mut ctx.accounts.stats_shard;
+    s.shard = shard;
+    s.total_players = 0;
+    s.last_player = Pubkey::default();
+    s.bump = ctx.bumps.stats_shard;
+    s.event_seq = 0;
+    Ok(())
+}
+
+// LevelPool / StatsShard created before event_seq are 8 bytes short. Activation and sign-up
+// grow them on their own; these grow them ahead of time (payer funds the rent top-up,
+// event_seq = 0). Permissionless and idempotent.
+pub fn handle_upgrade_level_pool(ctx: Context<UpgradeLevelPool>, _level: u8) -> Result<()> {
+    let pool_ai = ctx.accounts.level_pool.to_account_info();
+    require_keys_eq!(*pool_ai.owner, crate::ID, CustomError::AccountCastError);
+    realloc_if_needed(
+        &pool_ai,
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        8 + LevelPool::SIZE,
+    )
+}
+
+pub fn handle_upgrade_stats_shard(ctx: Context<UpgradeStatsShard>, _shard: u8) -> Result<()> {
+    let shard_ai = ctx.accounts.stats_shard.to_account_info();
+    require_keys_eq!(*shard_ai.owner, crate::ID, CustomError::AccountCastError);
+    realloc_if_needed(
+        &shard_ai,
+        &ctx.accounts.payer.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        8 + StatsShard::SIZE,
+    )
+}
+
+// =============================================================
+// 8) VIEWS (read-only, result via return data)
+// =============================================================
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct QueuePosition {
+    /// Absolute enqueue sequence number within the LevelPool
+    pub position: u64,
+    pub page_index: u32,
+    /// Players in front of this one (0 = current head owner)
+    pub ahead: u64,
+    /// Slot fills the current head owner still needs before recycling
+    pub head_slots_remaining: u64,
+}
+
+pub fn handle_get_queue_position(ctx: Context<GetQueuePosition>, level: u8) -> Result<QueuePosition> {
+    require!((1..=MAX_LEVEL).contains(&level), CustomError::InvalidLevel);
+    let accounts = &ctx.accounts;
+
+    let ls = peek_level_state(&accounts.level_state)?;
+
+    let pool = decode_level_pool_padded(&accounts.level_pool.try_borrow_data()?)?;
+    let head_key = pool.head_page.ok_or(CustomError::QueueIsEmpty)?;
+    require_keys_eq!(head_key, accounts.head_page.key(), CustomError::QueuePageKeyMismatch);
+    let head_page = read_queue_page(&accounts.head_page)?;
+    let head_owner = *head_page.players.first().ok_or(CustomError::QueueIsEmpty)?;
+
+    let (page, ahead) = match ls.queue_page {
+        Some(queue_page_key) => {
+            require_keys_eq!(
+                queue_page_key,
+                accounts.queue_page.key(),
+                CustomError::QueuePageKeyMismatch
+            );
+            let page = read_queue_page(&accounts.queue_page)?;
+            (page, ls.queue_pos.saturating_sub(pool.total_dequeued))
+        }
+        // Queued before membership tracking: locate the player in the page the caller named
+        None => {
+            let page = read_legacy_member_page(
+                &accounts.queue_page,
+                &accounts.level_pool.key(),
+            )?;
+            let idx = page
+                .players
+                .iter()
+                .position(|p| *p == accounts.player.key())
+                .ok_or(CustomError::NotInQueue)?;
+            let ahead = legacy_queue_ahead(&head_page, &page, idx)?;
+            (page, ahead)
+        }
+    };
+
+    let (head_ls_pda, _) = derive_level_state_pda(&head_owner, level);
+    require_keys_eq!(accounts.head_level_state.key(), head_ls_pda);
+
+    let head_slots_filled = if accounts.head_level_state.data_is_empty() {
+        0
+    } else {
+        require_keys_eq!(*accounts.head_level_state.owner, crate::ID, CustomError::AccountCastError);
+        peek_level_state(&accounts.head_level_state)?.slots_filled
+    };
+    let threshold = recycle_threshold(&accounts.config_v3);
+
+    Ok(QueuePosition {
+        position: pool.total_dequeued.saturating_add(ahead),
+        page_index: page.page_index,
+        ahead,
+        head_slots_remaining: threshold.saturating_sub(head_slots_filled),
+    })
+}
+
+// QueuePage of `level_pool` at its canonical PDA (the caller-supplied page is not
+// referenced by a legacy LevelState, so check it belongs to this queue).
+fn read_legacy_member_page(ai: &AccountInfo, level_pool: &Pubkey) -> Result<QueuePage> {
+    require_keys_eq!(*ai.owner, crate::ID, CustomError::QueuePageKeyMismatch);
+    let page = read_queue_page(ai)?;
+    require_keys_eq!(page.level_pool, *level_pool, CustomError::QueuePageKeyMismatch);
+    let seeds = QueuePage::seeds(level_pool, page.page_index);
+    let (pda, _) = Pubkey::find_program_address(&[&seeds[0], &seeds[1], &seeds[2]], &crate::ID);
+    require_keys_eq!(pda, *ai.key, CustomError::QueuePageKeyMismatch);
+    Ok(page)
+}
+
+// Players in front of `page.players[idx]`: the head page's remainder, every full page
+// between the two, then the player's own index. Pages only roll over once full, so
+// everything strictly between head and `page` holds QUEUE_PAGE_CAPACITY_DEFAULT players.
+fn legacy_queue_ahead(head_page: &QueuePage, page: &QueuePage, idx: usize) -> Result<u64> {
+    if page.page_index == head_page.page_index {
+        return Ok(idx as u64);
+    }
+    require!(page.page_index > head_page.page_index, CustomError::NotInQueue);
+    let between = u64::from(page.page_index - head_page.page_index - 1);
+    between
+        .checked_mul(QUEUE_PAGE_CAPACITY_DEFAULT as u64)
+        .and_then(|n| n.checked_add(head_page.players.len() as u64))
+        .and_then(|n| n.checked_add(idx as u64))
+        .ok_or_else(|| error!(CustomError::Overflow))
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ActivationQuote {
+    pub level: u8,
+    pub price: u64,
+    pub is_first_activation: bool,
+    pub is_self_owner: bool,
+    /// Head owner's slots_filled after this activation and whether it recycles them
+    pub owner_slots_filled: u64,
+    pub owner_recycles: bool,
+    pub payout: PayoutBreakdown,
+    /// Legacy TxGuard path only (0 once cfg.legacy_tx_guard is off)
+    pub rent_tx_guard: u64,
+    pub rent_level_state: u64,
+    pub rent_level_pool: u64,
+    pub creates_owner_level_state: bool,
+    /// Creation rent, or realloc top-up for a legacy-sized owner LevelState
+    pub rent_owner_level_state: u64,
+    pub creates_queue_page: bool,
+    pub rent_queue_page: u64,
+    /// Lamports the activator ends up paying: price (lamports mode), rent for their own
+    /// accounts, realloc top-ups and whatever bookkeeping rent isn't reimbursed
+    pub total_lamports: u64,
+    /// Bookkeeping rent (LevelPool, owner LevelState, queue page, recipient ATAs) netted
+    /// out of the treasury share, and the part refunded from the rent reserve PDA
+    pub rent_from_treasury_share: u64,
+    pub rent_from_reserve: u64,
+    /// Default = lamports; otherwise price/payout are in this mint's base units
+    pub payment_mint: Pubkey,
+    /// Token-2022 fees grossing up the owner/ref legs (out of the treasury share)
+    pub transfer_fees: u64,
+    /// Treasury share actually sent: payout.treasury_amount - transfer_fees
+    pub treasury_amount: u64,
+    /// Token mode: rent for recipient ATAs the activator creates (bookkeeping rent)
+    pub rent_recipient_atas: u64,
+    /// Player, LevelPool and (register_and_activate) StatsShard grown to the current
+    /// layout; not reimbursed
+    pub rent_realloc_top_ups: u64,
+    /// Token mode: bookkeeping rent the reserve can't refund
+    pub rent_payer_bears: u64,
+}
+
+// Legacy GlobalStats (frozen at the switch to shards) + every StatsShard.
+// remaining_accounts: all STATS_SHARD_COUNT shard PDAs in shard order
+// (uninitialized shards count as 0).
+pub fn handle_get_total_players<'info>(
+    ctx: Context<'_, '_, 'info, 'info, GetTotalPlayers<'info>>,
+) -> Result<u64> {
+    require_eq!(
+        ctx.remaining_accounts.len(),
+        STATS_SHARD_COUNT as usize,
+        CustomError::InvalidStatsShard
+    );
+
+    let mut total = ctx.accounts.global_stats.total_players;
+    for (i, ai) in ctx.remaining_accounts.iter().enumerate() {
+        let (expected, _) =
+            Pubkey::find_program_address(&[b"stats_shard_v1", &[i as u8]], &crate::ID);
+        require_keys_eq!(*ai.key, expected, CustomError::InvalidStatsShard);
+        if ai.data_is_empty() {
+            continue;
+        }
+        require_keys_eq!(*ai.owner, crate::ID, CustomError::InvalidStatsShard);
+
+        let shard = decode_stats_shard_padded(&ai.try_borrow_data()?)?;
+        total = total.checked_add(shard.total_players).ok_or(CustomError::Overflow)?;
+    }
+    Ok(total)
+}
+
+// Mirrors handle_activate_level_v3 / activate_level_token without mutating anything; the
+// payout split, slot/recycle decision, transfer fees and rent settlement come from the
+// same helpers used on execution.
+pub fn handle_quote_activation(ctx: Context<QuoteActivation>, level: u8) -> Result<ActivationQuote> {
+    require!((1..=MAX_LEVEL).contains(&level), CustomError::InvalidLevel);
+    let accounts = &ctx.accounts;
+    let cfg = &accounts.config_v3;
+    let rent = Rent::get()?;
+    let now = Clock::get()?.unix_timestamp;
+
+    let price = level_price(level, cfg, accounts.price_feed.as_ref())?;
+    require!(price > 0, CustomError::InvalidPrice);
+
+    let mut quote = ActivationQuote {
+        level,
+        price,
+        payment_mint: cfg.payment_mint,
+        rent_tx_guard: if cfg.legacy_tx_guard {
+            rent.minimum_balance(8 + TxGuard::SIZE)
+        } else {
+            0
+        },
+        rent_realloc_top_ups: realloc_top_up(&accounts.player, 8 + Player::SIZE)?,
+        ..Default::default()
+    };
+    if let Some(shard) = accounts.stats_shard.as_ref() {
+        quote.rent_realloc_top_ups = quote
+            .rent_realloc_top_ups
+            .saturating_add(realloc_top_up(shard, 8 + StatsShard::SIZE)?);
+    }
+
+    // Activator LevelState (init_if_needed, must not be active yet)
+    if accounts.level_state.data_is_empty() {
+        quote.rent_level_state = rent.minimum_balance(8 + LevelState::SIZE);
+    } else {
+        let ls = peek_level_state(&accounts.level_state)?;
+        require!(ls.activated_at == 0, CustomError::AlreadyActivated);
+    }
+
+    // LevelPool (created on first activation, older layouts grown)
+    let pool: Option<LevelPool> = if accounts.level_pool.data_is_empty() {
+        quote.rent_level_pool = rent.minimum_balance(8 + LevelPool::SIZE);
+        None
+    } else {
+        quote.rent_realloc_top_ups = quote
+            .rent_realloc_top_ups
+            .saturating_add(realloc_top_up(&accounts.level_pool, 8 + LevelPool::SIZE)?);
+        Some(decode_level_pool_padded(&accounts.level_pool.try_borrow_data()?)?)
+    };
+
+    let head_key = match pool.as_ref().and_then(|p| p.head_page) {
+        Some(k) => k,
+        None => {
+            // First activation: page 0 is created, 100% -> treasury
+            quote.is_first_activation = true;
+            quote.creates_queue_page = true;
+            quote.rent_queue_page = rent.minimum_balance(QueuePage::space_default());
+            quote.payout = PayoutBreakdown::treasury_only(price);
+            return finish_quote(accounts, quote);
+        }
+    };
+    let pool = pool.ok_or(CustomError::QueueIsEmpty)?;
+
+    require_keys_eq!(head_key, accounts.head_page.key(), CustomError::QueuePageKeyMismatch);
+    let head_page = read_queue_page(&accounts.head_page)?;
+    let owner_player_pda = *head_page.players.first().ok_or(CustomError::QueueIsEmpty)?;
+    require_keys_eq!(accounts.owner_player.key(), owner_player_pda);
+
+    let (owner_ls_pda, _) = derive_level_state_pda(&owner_player_pda, level);
+    require_keys_eq!(accounts.owner_level_state.key(), owner_ls_pda);
+
+    let owner_player_opt: Option<Player> = if accounts.owner_player.data_is_empty() {
+        None
+    } else {
+        Some(read_player(&accounts.owner_player)?)
+    };
+
+    let owner_ls_space: usize = 8 + LevelState::SIZE;
+    let mut owner_ls = if accounts.owner_level_state.data_is_empty() {
+        quote.creates_owner_level_state = true;
+        quote.rent_owner_level_state = rent.minimum_balance(owner_ls_space);
+        None
+    } else {
+        quote.rent_owner_level_state = realloc_top_up(&accounts.owner_level_state, owner_ls_space)?;
+        Some(peek_level_state(&accounts.owner_level_state)?)
+    }
+    .filter(|ls| ls.authority != Pubkey::default())
+    .unwrap_or_else(|| LevelState {
+        // Same defaults execution writes for a missing owner LevelState
+        player: owner_player_pda,
+        authority: owner_player_opt
+            .as_ref()
+            .map(|p| p.authority)
+            .unwrap_or_default(),
+        level,
+        bump: 0,
+        activated_at: now,
+        cycles: 0,
+        slots_filled: 0,
+        head_page: None,
+        tail_page: None,
+        queue_page: None,
+        queue_pos: 0,
+    });
+    owner_ls.authority = owner_payout_wallet(owner_player_opt.as_ref(), &owner_ls);
+
+    quote.is_self_owner = owner_player_pda == accounts.player.key();
+
+    let (removes_head, enqueues) = if quote.is_self_owner {
+        quote.owner_slots_filled = owner_ls.slots_filled;
+        quote.payout = PayoutBreakdown::self_owner(price, owner_ls.authority);
+        (true, 1usize)
+    } else {
+        let (slots_filled, should_recycle) = next_owner_slots(&owner_ls, cfg)?;
+        owner_ls.slots_filled = slots_filled;
+        quote.owner_slots_filled = slots_filled;
+        quote.owner_recycles = should_recycle;
+
+        let refs = resolve_refs(
+            cfg,
+            owner_player_opt.as_ref(),
+            level,
+            [
+                accounts.ref1_level_state.as_ref(),
+                accounts.ref2_level_state.as_ref(),
+                accounts.ref3_level_state.as_ref(),
+            ],
+        );
+        quote.payout = PayoutBreakdown::split(price, cfg, owner_ls.authority, refs);
+        (should_recycle, if should_recycle { 2 } else { 1 })
+    };
+
+    // Queue page rollover: replay the enqueues against the tail page length
+    let tail_key = pool.tail_page.ok_or(CustomError::QueueIsEmpty)?;
+    require_keys_eq!(tail_key, accounts.tail_page.key(), CustomError::QueuePageKeyMismatch);
+    let mut tail_len = read_queue_page(&accounts.tail_page)?.players.len();
+    if removes_head && tail_key == head_key {
+        tail_len = tail_len.saturating_sub(1);
+    }
+    for _ in 0..enqueues {
+        if page_is_full(tail_len) {
+            quote.creates_queue_page = true;
+            tail_len = 1;
+        } else {
+            tail_len += 1;
+        }
+    }
+    if quote.creates_queue_page {
+        quote.rent_queue_page = rent.minimum_balance(QueuePage::space_default());
+    }
+
+    finish_quote(accounts, quote)
+}
+
+// Transfer fees, recipient ATAs and rent settlement, as settle_activation would run them.
+fn finish_quote(accounts: &QuoteActivation, mut quote: ActivationQuote) -> Result<ActivationQuote> {
+    let cfg = &accounts.config_v3;
+    let token_mode = cfg.token_mode();
+
+    if token_mode {
+        let mint = accounts.mint.as_ref().ok_or(CustomError::WrongPaymentMode)?;
+        let fee = mint_transfer_fee(mint)?;
+        (quote.treasury_amount, quote.transfer_fees) =
+            treasury_after_leg_fees(&quote.payout, fee.as_ref())?;
+
+        let ata_rent = Rent::get()?.minimum_balance(recipient_ata_space(mint)?);
+        let payout = &quote.payout;
+        for (wallet, amount, ata) in [
+            (payout.owner_wallet, payout.owner_amount, &accounts.owner_token),
+            (payout.ref1_wallet, payout.ref1_amount, &accounts.ref1_token),
+            (payout.ref2_wallet, payout.ref2_amount, &accounts.ref2_token),
+            (payout.ref3_wallet, payout.ref3_amount, &accounts.ref3_token),
+            (cfg.treasury, quote.treasury_amount, &accounts.treasury_token),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            let ata = ata.as_ref().ok_or(CustomError::InvalidTokenAccount)?;
+            require_keys_eq!(
+                ata.key(),
+                get_associated_token_address_with_program_id(&wallet, mint.key, mint.owner),
+                CustomError::InvalidTokenAccount
+            );
+            if ata.data_is_empty() {
+                quote.rent_recipient_atas = quote.rent_recipient_atas.saturating_add(ata_rent);
+            }
+        }
+    } else {
+        quote.treasury_amount = quote.payout.treasury_amount;
+    }
+
+    let bookkeeping_rent = quote
+        .rent_level_pool
+        .saturating_add(quote.rent_owner_level_state)
+        .saturating_add(quote.rent_queue_page)
+        .saturating_add(quote.rent_recipient_atas);
+    let reserve = accounts
+        .rent_reserve
+        .as_ref()
+        .map(|r| rent_reserve_available(r))
+        .transpose()?;
+    let plan = plan_rent_settlement(token_mode, quote.treasury_amount, bookkeeping_rent, reserve)?;
+    quote.rent_from_treasury_share = plan.from_treasury_share;
+    quote.rent_from_reserve = plan.from_reserve;
+    quote.rent_payer_bears = plan.payer_bears;
+
+    let price_lamports = if token_mode { 0 } else { quote.price };
+    quote.total_lamports = price_lamports
+        .saturating_add(quote.rent_tx_guard)
+        .saturating_add(quote.rent_level_state)
+        .saturating_add(quote.rent_realloc_top_ups)
+        .saturating_add(quote.rent_payer_bears);
+    Ok(quote)
+}
+
+// =============================================================
+// 11) CONTEXTS
+// =============================================================
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct InitializeConfigV3<'info> {
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + ConfigV3::SIZE,
+        seeds = [b"config_v3_new"],
+        bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + GlobalStats::SIZE,
+        seeds = [b"global_stats_v1"],
+        bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CreatePlayerV3<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Player::SIZE,
+        seeds = [b"player", authority.key().as_ref()],
+        bump
+    )]
+    pub player: Account<'info, Player>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    // Read-only: legacy last_player fallback for orphan sign-ups
+    #[account(
+        seeds = [b"global_stats_v1"],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: StatsShard PDA, pre-event_seq shards are grown in the handler
+    #[account(
+        mut,
+        seeds = [b"stats_shard_v1".as_ref(), &[stats_shard_for(&authority.key())]],
+        bump
+    )]
+    pub stats_shard: UncheckedAccount<'info>,
+
+    /// CHECK:
+    pub referrer_player: Option<AccountInfo<'info>>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: wallets that rotate_authority linked to a Player can't register another one
+    #[account(
+        seeds = [AUTHORITY_LINK_SEED, authority.key().as_ref()],
+        bump,
+        constraint = authority_link.data_is_empty() @ CustomError::WalletLinkedToPlayer
+    )]
+    pub authority_link: UncheckedAccount<'info>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(level: u8, _max_price_lamports: u64, nonce: u64)]
+pub struct ActivateLevelV3<'info> {
+    /// CHECK: Player of any layout (decoded in activate_level_core, older ones grown)
+    #[account(mut, owner = crate::ID)]
+    pub player: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + LevelState::SIZE,
+        seeds = [b"lvl", player.key().as_ref(), &[level]],
+        bump
+    )]
+    pub level_state: Account<'info, LevelState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TxGuard::SIZE,
+        seeds = [b"tx", player.key().as_ref(), b"\x01", &[level], &nonce.to_le_bytes()],
+        bump
+    )]
+    // Legacy replay protection (None = Player.next_nonce path)
+    pub tx_guard: Option<Account<'info, TxGuard>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    /// CHECK: LevelPool PDA, created on the level's first activation; pre-event_seq pools
+    /// are grown in activate_level_core
+    #[account(
+        mut,
+        seeds = [b"level_pool_v1", config_v3.key().as_ref(), &[level]],
+        bump
+    )]
+    pub level_pool: UncheckedAccount<'info>,
+
+    /// CHECK: never credited (admin refs collapse into treasury)
+    pub admin_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub treasury_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref1_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref2_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref3_account: UncheckedAccount<'info>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub tail_page: AccountInfo<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub new_page: AccountInfo<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub head_page: AccountInfo<'info>,
+
+    /// CHECK: read-only (uplines)
+    pub owner_player: AccountInfo<'info>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub owner_level_state: AccountInfo<'info>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub owner_wallet: UncheckedAccount<'info>,
+
+    /// CHECK:
+    pub ref1_level_state: Option<AccountInfo<'info>>,
+    /// CHECK:
+    pub ref2_level_state: Option<AccountInfo<'info>>,
+    /// CHECK:
+    pub ref3_level_state: Option<AccountInfo<'info>>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: system-owned lamport reserve (optional trailing, so older clients may omit it)
+    #[account(mut, seeds = [RENT_RESERVE_SEED], bump)]
+    pub rent_reserve: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: player's LevelState for level - 1; verified when cfg.require_sequential_levels
+    pub previous_level_state: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: cfg.usd_price_feed, required in USD mode (validated in level_price)
+    pub price_feed: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ActivateLevelToken<'info> {
+    // Same accounts as activate_level_v3 (ref/owner/treasury slots are the wallets)
+    pub activate: ActivateLevelV3<'info>,
+
+    #[account(
+        address = activate.config_v3.payment_mint @ CustomError::WrongPaymentMode,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = activate.authority,
+        token::token_program = token_program
+    )]
+    pub payer_token: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: ATA(owner_wallet, mint), created if missing
+    #[account(mut)]
+    pub owner_token: UncheckedAccount<'info>,
+    /// CHECK: ATA(ref1_account, mint); unused when ref1's share collapses into treasury
+    #[account(mut)]
+    pub ref1_token: UncheckedAccount<'info>,
+    /// CHECK: ATA(ref2_account, mint)
+    #[account(mut)]
+    pub ref2_token: UncheckedAccount<'info>,
+    /// CHECK: ATA(ref3_account, mint)
+    #[account(mut)]
+    pub ref3_token: UncheckedAccount<'info>,
+    /// CHECK: ATA(cfg.treasury, mint)
+    #[account(mut)]
+    pub treasury_token: UncheckedAccount<'info>,
+
+    // SPL Token or Token-2022 (must own the mint)
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct ActivateLevelFor<'info> {
+    // Same accounts as activate_level_v3; `authority` is the payer, `player` the beneficiary
+    pub activate: ActivateLevelV3<'info>,
+
+    /// CHECK: beneficiary wallet (== player.authority); signer unless gift_opt_in covers the payer
+    pub beneficiary: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [GIFT_OPT_IN_SEED, activate.player.key().as_ref()],
+        bump = gift_opt_in.bump
+    )]
+    pub gift_opt_in: Option<Account<'info, GiftOptIn>>,
+}
+
+#[derive(Accounts)]
+pub struct SetGiftOptIn<'info> {
+    #[account(
+        constraint = player.authority == authority.key() @ CustomError::Unauthorized
+    )]
+    pub player: Account<'info, Player>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + GiftOptIn::SIZE,
+        seeds = [GIFT_OPT_IN_SEED, player.key().as_ref()],
+        bump
+    )]
+    pub gift_opt_in: Account<'info, GiftOptIn>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeGiftOptIn<'info> {
+    #[account(
+        constraint = player.authority == authority.key() @ CustomError::Unauthorized
+    )]
+    pub player: Account<'info, Player>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        close = authority,
+        seeds = [GIFT_OPT_IN_SEED, player.key().as_ref()],
+        bump = gift_opt_in.bump
+    )]
+    pub gift_opt_in: Account<'info, GiftOptIn>,
+}
+
+#[derive(Accounts)]
+#[instruction(level: u8, _max_price_lamports: u64, nonce: u64)]
+pub struct RecycleLevelV3<'info> {
+    #[account(mut)]
+    pub player: Account<'info, Player>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TxGuard::SIZE,
+        seeds = [b"tx", player.key().as_ref(), b"\x02", &[level], &nonce.to_le_bytes()],
+        bump
+    )]
+    pub tx_guard: Account<'info, TxGuard>,
+
+    #[account(
+        mut,
+        seeds = [b"lvl", player.key().as_ref(), &[level]],
+        bump = level_state.bump,
+        has_one = authority
+    )]
+    pub level_state: Account<'info, LevelState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub admin_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub treasury_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref1_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref2_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref3_account: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimSlot<'info> {
+    #[account(
+        mut,
+        seeds = [b"lvl", owner_player.key().as_ref(), &[level_state.level]],
+        bump = level_state.bump
+    )]
+    pub level_state: Account<'info, LevelState>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub owner_player: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub activator: Signer<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub admin_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub treasury_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref1_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref2_account: UncheckedAccount<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub ref3_account: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnqueuePlayer<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"lvl", player.key().as_ref(), &[level_state.level]],
+        bump = level_state.bump
+    )]
+    pub level_state: Account<'info, LevelState>,
+
+    pub player: Account<'info, Player>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub tail_page: AccountInfo<'info>,
+    /// CHECK:
+    #[account(mut)]
+    pub new_page: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
-pub fn handle_register_player(
-    ctx: Context<RegisterPlayer>,
-    referrer: Option<Pubkey>,
-    nonce: u64,
-) -> Result<()> {
-    let tg = &mut ctx.accounts.tx_guard;
-    tg.nonce = nonce;
-    tg.executed_at = Clock::get()?.unix_timestamp;
-    tg.bump = ctx.bumps.tx_guard;
+#[derive(Accounts)]
+pub struct DequeueOwner<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        seeds = [b"lvl", player.key().as_ref(), &[level_state.level]],
+        bump = level_state.bump
+    )]
+    pub level_state: Account<'info, LevelState>,
 
-    let player = &mut ctx.accounts.player;
-    let cfg = &ctx.accounts.config_v3;
-    let stats = &mut ctx.accounts.global_stats;
+    pub player: Account<'info, Player>,
 
-    player.authority = ctx.accounts.authority.key();
-    player.bump = ctx.bumps.player;
-    player.created_at = Clock::get()?.unix_timestamp;
-    player.games_played = 0;
+    #[account(mut)]
+    pub authority: Signer<'info>,
 
-    if let Some(r) = referrer {
-        if let Some(ref_ai) = ctx.accounts.referrer_player.as_ref() {
-            require_keys_eq!(ref_ai.key(), r, CustomError::Unauthorized);
-            let data = ref_ai.try_borrow_data()?;
-            let mut slice: &[u8] = &data;
-            let ref_player =
-                Player::try_deserialize(&mut slice).map_err(|_| error!(CustomError::AccountCastError))?;
+    /// CHECK:
+    #[account(mut)]
+    pub head_page: AccountInfo<'info>,
+}
 
-            player.upline1 = ref_player.authority;
-            player.upline2 = ref_player.upline1;
-            player.upline3 = ref_player.upline2;
-        } else {
-            player.upline1 = r;
-            player.upline2 = cfg.admin;
-            player.upline3 = cfg.admin;
-        }
-    } else {
-        if stats.total_players == 0 {
-            player.upline1 = cfg.admin;
-            player.upline2 = cfg.admin;
-            player.upline3 = cfg.admin;
-        } else {
-            let prev = stats.last_player;
-            player.upline1 = prev;
-            player.upline2 = cfg.admin;
-            player.upline3 = cfg.admin;
-        }
-    }
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+#[instruction(referrer: Option<Pubkey>, nonce: u64)]
+pub struct RegisterPlayer<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Player::SIZE,
+        seeds = [b"player", authority.key().as_ref()],
+        bump
+    )]
+    pub player: Account<'info, Player>,
 
-    stats.last_player = player.authority;
-    stats.total_players = stats.total_players.saturating_add(1);
+    #[account(mut)]
+    pub authority: Signer<'info>,
 
-    emit!(PlayerCreated {
-        authority: player.authority,
-        timestamp: player.created_at,
-    });
-    Ok(())
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + TxGuard::SIZE,
+        seeds = [
+            b"tx".as_ref(),
+            b"register".as_ref(),
+            authority.key().as_ref(),
+            &nonce.to_le_bytes()
+        ],
+        bump
+    )]
+    // Legacy replay protection (None = nonce must be 0)
+    pub tx_guard: Option<Account<'info, TxGuard>>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    // Read-only: legacy last_player fallback for orphan sign-ups
+    #[account(
+        seeds = [b"global_stats_v1"],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+
+    /// CHECK: StatsShard PDA, pre-event_seq shards are grown in the handler
+    #[account(
+        mut,
+        seeds = [b"stats_shard_v1".as_ref(), &[stats_shard_for(&authority.key())]],
+        bump
+    )]
+    pub stats_shard: UncheckedAccount<'info>,
+
+    /// CHECK:
+    pub referrer_player: Option<AccountInfo<'info>>,
+
+    pub system_program: Program<'info, System>,
+
+    /// CHECK: wallets that rotate_authority linked to a Player can't register another one
+    #[account(
+        seeds = [AUTHORITY_LINK_SEED, authority.key().as_ref()],
+        bump,
+        constraint = authority_link.data_is_empty() @ CustomError::WalletLinkedToPlayer
+    )]
+    pub authority_link: UncheckedAccount<'info>,
 }
 
-// =============================================================
-// 11) CONTEXTS
-// =============================================================
+#[derive(Accounts)]
+#[instruction(level: u8)]
+pub struct GetQueuePosition<'info> {
+    /// CHECK: Player of any layout (only its key seeds level_state)
+    #[account(owner = crate::ID)]
+    pub player: UncheckedAccount<'info>,
+
+    /// CHECK: LevelState PDA of any layout (decoded with peek_level_state)
+    #[account(
+        owner = crate::ID,
+        seeds = [b"lvl", player.key().as_ref(), &[level]],
+        bump
+    )]
+    pub level_state: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    /// CHECK: LevelPool PDA of any layout (decoded in the handler)
+    #[account(
+        owner = crate::ID,
+        seeds = [b"level_pool_v1", config_v3.key().as_ref(), &[level]],
+        bump
+    )]
+    pub level_pool: UncheckedAccount<'info>,
+
+    /// CHECK: must equal level_state.queue_page (legacy members: the page holding the player)
+    pub queue_page: AccountInfo<'info>,
+    /// CHECK: must equal level_pool.head_page
+    pub head_page: AccountInfo<'info>,
+    /// CHECK: LevelState PDA of the head owner (may be uninitialized)
+    pub head_level_state: AccountInfo<'info>,
+}
 
 #[derive(Accounts)]
-pub struct InitializeConfigV3<'info> {
+#[instruction(level: u8)]
+pub struct QuoteActivation<'info> {
+    /// CHECK: Player of any layout (only its key is used)
+    #[account(owner = crate::ID)]
+    pub player: UncheckedAccount<'info>,
+
+    /// CHECK: activator LevelState PDA (may be uninitialized)
+    #[account(
+        seeds = [b"lvl", player.key().as_ref(), &[level]],
+        bump
+    )]
+    pub level_state: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    /// CHECK: LevelPool PDA (may be uninitialized)
+    #[account(
+        seeds = [b"level_pool_v1", config_v3.key().as_ref(), &[level]],
+        bump
+    )]
+    pub level_pool: UncheckedAccount<'info>,
+
+    /// CHECK: ignored on first activation
+    pub head_page: AccountInfo<'info>,
+    /// CHECK: ignored on first activation
+    pub tail_page: AccountInfo<'info>,
+    /// CHECK: ignored on first activation
+    pub owner_player: AccountInfo<'info>,
+    /// CHECK: ignored on first activation
+    pub owner_level_state: AccountInfo<'info>,
+
+    /// CHECK:
+    pub ref1_level_state: Option<AccountInfo<'info>>,
+    /// CHECK:
+    pub ref2_level_state: Option<AccountInfo<'info>>,
+    /// CHECK:
+    pub ref3_level_state: Option<AccountInfo<'info>>,
+
+    /// CHECK: cfg.usd_price_feed, required in USD mode (validated in level_price)
+    pub price_feed: Option<AccountInfo<'info>>,
+
+    /// CHECK: rent reserve PDA (its balance caps the refund), as passed to the activation
+    #[account(seeds = [RENT_RESERVE_SEED], bump)]
+    pub rent_reserve: Option<UncheckedAccount<'info>>,
+    /// CHECK: register_and_activate's StatsShard (only its realloc top-up is read)
+    #[account(owner = crate::ID)]
+    pub stats_shard: Option<UncheckedAccount<'info>>,
+
+    // Token mode: the payment mint and recipient ATAs, as passed to activate_level_token
+    /// CHECK: must be cfg.payment_mint
+    #[account(address = config_v3.payment_mint @ CustomError::WrongPaymentMode)]
+    pub mint: Option<UncheckedAccount<'info>>,
+    /// CHECK: ATA(owner wallet, mint)
+    pub owner_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: ATA(ref1 wallet, mint)
+    pub ref1_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: ATA(ref2 wallet, mint)
+    pub ref2_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: ATA(ref3 wallet, mint)
+    pub ref3_token: Option<UncheckedAccount<'info>>,
+    /// CHECK: ATA(cfg.treasury, mint)
+    pub treasury_token: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(shard: u8)]
+pub struct InitStatsShard<'info> {
     #[account(
         init,
-        payer = payer,
-        space = 8 + ConfigV3::SIZE,
+        payer = admin,
+        space = 8 + StatsShard::SIZE,
+        seeds = [b"stats_shard_v1".as_ref(), &[shard]],
+        bump
+    )]
+    pub stats_shard: Account<'info, StatsShard>,
+
+    #[account(
         seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(level: u8)]
+pub struct UpgradeLevelPool<'info> {
+    /// CHECK: resized manually (the pre-event_seq layout doesn't deserialize)
+    #[account(
+        mut,
+        seeds = [b"level_pool_v1", config_v3.key().as_ref(), &[level]],
         bump
     )]
+    pub level_pool: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
     pub config_v3: Account<'info, ConfigV3>,
 
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(shard: u8)]
+pub struct UpgradeStatsShard<'info> {
+    /// CHECK: resized manually (the pre-event_seq layout doesn't deserialize)
     #[account(
-        init,
-        payer = payer,
-        space = 8 + GlobalStats::SIZE,
-        seeds = [b"global_stats_v1"],
+        mut,
+        seeds = [b"stats_shard_v1".as_ref(), &[shard]],
         bump
     )]
-    pub global_stats: Account<'info, GlobalStats>,
+    pub stats_shard: UncheckedAccount<'info>,
 
     #[account(mut)]
     pub payer: Signer<'info>,
@@ -1349,270 +4524,309 @@ pub struct InitializeConfigV3<'info> {
 }
 
 #[derive(Accounts)]
-pub struct CreatePlayerV3<'info> {
+pub struct GetTotalPlayers<'info> {
     #[account(
-        init,
-        payer = authority,
-        space = 8 + Player::SIZE,
-        seeds = [b"player", authority.key().as_ref()],
+        seeds = [b"global_stats_v1"],
+        bump = global_stats.bump
+    )]
+    pub global_stats: Account<'info, GlobalStats>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateConfigV3<'info> {
+    /// CHECK: decoded manually (older layouts are shorter than ConfigV3::SIZE)
+    #[account(
+        mut,
+        seeds = [b"config_v3_new"],
         bump
     )]
+    pub config_v3: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfigV3<'info> {
+    #[account(
+        mut,
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTxGuard<'info> {
+    #[account(
+        constraint = player.authority == authority.key() @ CustomError::Unauthorized
+    )]
     pub player: Account<'info, Player>,
 
+    #[account(mut)]
+    pub tx_guard: Account<'info, TxGuard>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 
     #[account(
-        mut,
         seeds = [b"config_v3_new"],
         bump = config_v3.bump
     )]
     pub config_v3: Account<'info, ConfigV3>,
+}
 
+#[derive(Accounts)]
+pub struct UpgradePlayer<'info> {
+    /// CHECK: decoded manually (older layouts are shorter than Player::SIZE)
     #[account(
         mut,
-        seeds = [b"global_stats_v1"],
-        bump = global_stats.bump
+        seeds = [b"player", authority.key().as_ref()],
+        bump
     )]
-    pub global_stats: Account<'info, GlobalStats>,
-
-    /// CHECK:
-    pub referrer_player: Option<AccountInfo<'info>>,
+    pub player: UncheckedAccount<'info>,
 
+    #[account(mut)]
+    pub authority: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(level: u8, _price_lamports: u64, nonce: u64)]
-pub struct ActivateLevelV3<'info> {
-    #[account(mut)]
-    pub player: Account<'info, Player>,
-
+pub struct InitTreasuryVault<'info> {
     #[account(
-        init_if_needed,
-        payer = authority,
-        space = 8 + LevelState::SIZE,
-        seeds = [b"lvl", player.key().as_ref(), &[level]],
+        init,
+        payer = admin,
+        space = 8 + TreasuryVault::SIZE,
+        seeds = [TREASURY_VAULT_SEED],
         bump
     )]
-    pub level_state: Account<'info, LevelState>,
+    pub treasury_vault: Account<'info, TreasuryVault>,
 
     #[account(
-        init,
-        payer = authority,
-        space = 8 + TxGuard::SIZE,
-        seeds = [b"tx", player.key().as_ref(), b"\x01", &[level], &nonce.to_le_bytes()],
-        bump
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump,
+        has_one = admin @ CustomError::Unauthorized
     )]
-    pub tx_guard: Account<'info, TxGuard>,
+    pub config_v3: Account<'info, ConfigV3>,
 
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct WithdrawTreasury<'info> {
     #[account(
         mut,
-        seeds = [b"config_v3_new"],
-        bump = config_v3.bump
+        seeds = [TREASURY_VAULT_SEED],
+        bump = treasury_vault.bump
     )]
-    pub config_v3: Account<'info, ConfigV3>,
+    pub treasury_vault: Account<'info, TreasuryVault>,
 
     #[account(
-        init_if_needed,
-        payer = authority,
-        space = 8 + LevelPool::SIZE,
-        seeds = [b"level_pool_v1", config_v3.key().as_ref(), &[level]],
-        bump
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump,
+        has_one = admin @ CustomError::Unauthorized
     )]
-    pub level_pool: Account<'info, LevelPool>,
+    pub config_v3: Account<'info, ConfigV3>,
 
-    /// CHECK:
-    #[account(mut)]
-    pub admin_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub treasury_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref1_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref2_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref3_account: UncheckedAccount<'info>,
+    pub admin: Signer<'info>,
 
-    /// CHECK:
-    #[account(mut)]
-    pub tail_page: AccountInfo<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub new_page: AccountInfo<'info>,
-    /// CHECK:
+    /// CHECK: system wallet, must equal the `destination` argument
     #[account(mut)]
-    pub head_page: AccountInfo<'info>,
+    pub destination: UncheckedAccount<'info>,
+}
 
-    /// CHECK:
-    #[account(mut)]
-    pub owner_player: AccountInfo<'info>,
+#[derive(Accounts)]
+pub struct SetTreasurySplit<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + TreasurySplit::SIZE,
+        seeds = [TREASURY_SPLIT_SEED],
+        bump
+    )]
+    pub treasury_split: Account<'info, TreasurySplit>,
 
-    /// CHECK:
-    #[account(mut)]
-    pub owner_level_state: AccountInfo<'info>,
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump,
+        has_one = admin @ CustomError::Unauthorized
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
 
-    /// CHECK:
     #[account(mut)]
-    pub owner_wallet: UncheckedAccount<'info>,
-
-    /// CHECK:
-    pub ref1_level_state: Option<AccountInfo<'info>>,
-    /// CHECK:
-    pub ref2_level_state: Option<AccountInfo<'info>>,
-    /// CHECK:
-    pub ref3_level_state: Option<AccountInfo<'info>>,
-
+    pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
-#[instruction(level: u8, _price_lamports: u64, nonce: u64)]
-pub struct RecycleLevelV3<'info> {
-    #[account(mut)]
-    pub player: Account<'info, Player>,
-
+pub struct DistributeTreasury<'info> {
     #[account(
-        init,
-        payer = authority,
-        space = 8 + TxGuard::SIZE,
-        seeds = [b"tx", player.key().as_ref(), b"\x02", &[level], &nonce.to_le_bytes()],
-        bump
+        mut,
+        seeds = [TREASURY_SPLIT_SEED],
+        bump = treasury_split.bump
     )]
-    pub tx_guard: Account<'info, TxGuard>,
+    pub treasury_split: Account<'info, TreasurySplit>,
 
     #[account(
         mut,
-        seeds = [b"lvl", player.key().as_ref(), &[level]],
-        bump = level_state.bump,
-        has_one = authority
+        seeds = [TREASURY_VAULT_SEED],
+        bump = treasury_vault.bump
     )]
-    pub level_state: Account<'info, LevelState>,
-
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    pub treasury_vault: Account<'info, TreasuryVault>,
 
+    // Read-only: treasury_epoch_limit
     #[account(
         seeds = [b"config_v3_new"],
         bump = config_v3.bump
     )]
     pub config_v3: Account<'info, ConfigV3>,
-
-    /// CHECK:
-    #[account(mut)]
-    pub admin_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub treasury_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref1_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref2_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref3_account: UncheckedAccount<'info>,
-
-    pub system_program: Program<'info, System>,
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
-pub struct ClaimSlot<'info> {
+#[instruction(new_authority: Pubkey)]
+pub struct RotateAuthority<'info> {
     #[account(
         mut,
-        seeds = [b"lvl", owner_player.key().as_ref(), &[level_state.level]],
-        bump = level_state.bump
+        constraint = player.authority == authority.key() @ CustomError::Unauthorized
     )]
-    pub level_state: Account<'info, LevelState>,
+    pub player: Account<'info, Player>,
 
-    /// CHECK:
     #[account(mut)]
-    pub owner_player: AccountInfo<'info>,
+    pub authority: Signer<'info>,
 
-    #[account(mut)]
-    pub activator: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + AuthorityLink::SIZE,
+        seeds = [AUTHORITY_LINK_SEED, new_authority.as_ref()],
+        bump
+    )]
+    pub authority_link: Account<'info, AuthorityLink>,
 
+    /// CHECK: the new wallet must not own a Player of its own
     #[account(
-        seeds = [b"config_v3_new"],
-        bump = config_v3.bump
+        seeds = [b"player", new_authority.as_ref()],
+        bump,
+        constraint = new_authority_player.data_is_empty()
+            || new_authority_player.key() == player.key() @ CustomError::InvalidNewAuthority
     )]
-    pub config_v3: Account<'info, ConfigV3>,
+    pub new_authority_player: UncheckedAccount<'info>,
 
-    /// CHECK:
-    #[account(mut)]
-    pub admin_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub treasury_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref1_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref2_account: UncheckedAccount<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub ref3_account: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [AUTHORITY_RECOVERY_SEED, player.key().as_ref()],
+        bump = recovery.bump
+    )]
+    pub recovery: Option<Account<'info, AuthorityRecovery>>,
 
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct EnqueuePlayer<'info> {
+pub struct SetGuardian<'info> {
     #[account(
-        mut,
-        has_one = authority,
-        seeds = [b"lvl", player.key().as_ref(), &[level_state.level]],
-        bump = level_state.bump
+        constraint = player.authority == authority.key() @ CustomError::Unauthorized
     )]
-    pub level_state: Account<'info, LevelState>,
-
     pub player: Account<'info, Player>,
 
     #[account(mut)]
     pub authority: Signer<'info>,
 
-    /// CHECK:
-    #[account(mut)]
-    pub tail_page: AccountInfo<'info>,
-    /// CHECK:
-    #[account(mut)]
-    pub new_page: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + AuthorityRecovery::SIZE,
+        seeds = [AUTHORITY_RECOVERY_SEED, player.key().as_ref()],
+        bump
+    )]
+    pub recovery: Account<'info, AuthorityRecovery>,
 
     pub system_program: Program<'info, System>,
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
-pub struct DequeueOwner<'info> {
+pub struct StartRecovery<'info> {
     #[account(
         mut,
-        has_one = authority,
-        seeds = [b"lvl", player.key().as_ref(), &[level_state.level]],
-        bump = level_state.bump
+        seeds = [AUTHORITY_RECOVERY_SEED, recovery.player.as_ref()],
+        bump = recovery.bump,
+        constraint = recovery.guardian != Pubkey::default()
+            && recovery.guardian == guardian.key() @ CustomError::Unauthorized
     )]
-    pub level_state: Account<'info, LevelState>,
+    pub recovery: Account<'info, AuthorityRecovery>,
+
+    pub guardian: Signer<'info>,
+}
 
+#[derive(Accounts)]
+pub struct CancelRecovery<'info> {
+    #[account(
+        constraint = player.authority == authority.key() @ CustomError::Unauthorized
+    )]
     pub player: Account<'info, Player>,
 
-    #[account(mut)]
     pub authority: Signer<'info>,
 
-    /// CHECK:
+    #[account(
+        mut,
+        seeds = [AUTHORITY_RECOVERY_SEED, player.key().as_ref()],
+        bump = recovery.bump
+    )]
+    pub recovery: Account<'info, AuthorityRecovery>,
+}
+
+#[cfg_attr(feature = "event-cpi", event_cpi)]
+#[derive(Accounts)]
+pub struct CompleteRecovery<'info> {
+    #[account(mut, address = recovery.player)]
+    pub player: Account<'info, Player>,
+
+    #[account(
+        mut,
+        seeds = [AUTHORITY_RECOVERY_SEED, recovery.player.as_ref()],
+        bump = recovery.bump,
+        constraint = recovery.guardian != Pubkey::default()
+            && recovery.guardian == guardian.key() @ CustomError::Unauthorized
+    )]
+    pub recovery: Account<'info, AuthorityRecovery>,
+
     #[account(mut)]
-    pub head_page: AccountInfo<'info>,
+    pub guardian: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = guardian,
+        space = 8 + AuthorityLink::SIZE,
+        seeds = [AUTHORITY_LINK_SEED, recovery.pending_authority.as_ref()],
+        bump
+    )]
+    pub authority_link: Account<'info, AuthorityLink>,
+
+    /// CHECK: the new wallet must not own a Player of its own
+    #[account(
+        seeds = [b"player", recovery.pending_authority.as_ref()],
+        bump,
+        constraint = new_authority_player.data_is_empty()
+            || new_authority_player.key() == player.key() @ CustomError::InvalidNewAuthority
+    )]
+    pub new_authority_player: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
 }
 
+#[cfg_attr(feature = "event-cpi", event_cpi)]
 #[derive(Accounts)]
-#[instruction(referrer: Option<Pubkey>, nonce: u64)]
-pub struct RegisterPlayer<'info> {
+pub struct RegisterAndActivate<'info> {
     #[account(
         init,
         payer = authority,
@@ -1626,36 +4840,53 @@ pub struct RegisterPlayer<'info> {
     pub authority: Signer<'info>,
 
     #[account(
-        init,
-        payer = authority,
-        space = 8 + TxGuard::SIZE,
-        seeds = [
-            b"tx".as_ref(),
-            b"register".as_ref(),
-            authority.key().as_ref(),
-            &nonce.to_le_bytes()
-        ],
-        bump
-    )]
-    pub tx_guard: Account<'info, TxGuard>,
-
-    #[account(
-        mut,
         seeds = [b"config_v3_new"],
         bump = config_v3.bump
     )]
     pub config_v3: Account<'info, ConfigV3>,
 
     #[account(
-        mut,
         seeds = [b"global_stats_v1"],
         bump = global_stats.bump
     )]
     pub global_stats: Account<'info, GlobalStats>,
 
+    /// CHECK: StatsShard PDA, pre-event_seq shards are grown in the handler
+    #[account(
+        mut,
+        seeds = [b"stats_shard_v1".as_ref(), &[stats_shard_for(&authority.key())]],
+        bump
+    )]
+    pub stats_shard: UncheckedAccount<'info>,
+
     /// CHECK:
     pub referrer_player: Option<AccountInfo<'info>>,
 
     pub system_program: Program<'info, System>,
+
+    /// CHECK: wallets that rotate_authority linked to a Player can't register another one
+    #[account(
+        seeds = [AUTHORITY_LINK_SEED, authority.key().as_ref()],
+        bump,
+        constraint = authority_link.data_is_empty() @ CustomError::WalletLinkedToPlayer
+    )]
+    pub authority_link: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ActivateLevelsBatch<'info> {
+    /// CHECK: read-only here; each level group decodes (grows) it and checks its authority
+    #[account(owner = crate::ID)]
+    pub player: UncheckedAccount<'info>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"config_v3_new"],
+        bump = config_v3.bump
+    )]
+    pub config_v3: Account<'info, ConfigV3>,
 }
 
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1039 @@
+use super::*;
+use anchor_spl::token_2022::spl_token_2022::extension::{
+    BaseStateWithExtensionsMut, StateWithExtensionsMut,
+};
+
+// LevelState as written before queue_page / queue_pos existed
+fn legacy_level_state_data(
+    player: &Pubkey,
+    authority: &Pubkey,
+    level: u8,
+    activated_at: i64,
+) -> Vec<u8> {
+    let mut data = LevelState::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(player.as_ref());
+    data.extend_from_slice(authority.as_ref());
+    data.push(level);
+    data.push(255); // bump
+    data.extend_from_slice(&activated_at.to_le_bytes());
+    data.extend_from_slice(&2u64.to_le_bytes()); // cycles
+    data.extend_from_slice(&1u64.to_le_bytes()); // slots_filled
+    data.push(0); // head_page: None
+    data.push(0); // tail_page: None
+    data
+}
+
+#[test]
+fn ref_paid_through_legacy_level_state() {
+    let upline_wallet = Pubkey::new_unique();
+    let (upline_player, _) =
+        Pubkey::find_program_address(&[b"player", upline_wallet.as_ref()], &crate::ID);
+    let payout_wallet = Pubkey::new_unique();
+    let admin = Pubkey::new_unique();
+    let level = 3;
+
+    let (key, _) = derive_level_state_pda(&upline_player, level);
+    let owner = crate::ID;
+    let mut lamports = 1_000_000;
+    let mut data = legacy_level_state_data(&upline_player, &payout_wallet, level, 1_700_000_000);
+    assert!(data.len() < 8 + LevelState::SIZE);
+    let ai = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+
+    // Upline stored as the Player PDA, and as the seed wallet (pre-PDA sign-ups)
+    assert_eq!(
+        open_level_wallet(&upline_player, level, &ai).unwrap(),
+        Some(payout_wallet)
+    );
+    assert_eq!(
+        open_level_wallet(&upline_wallet, level, &ai).unwrap(),
+        Some(payout_wallet)
+    );
+    assert_eq!(
+        resolve_ref_with_level_or_admin(&upline_player, level, Some(&ai), &admin),
+        payout_wallet
+    );
+
+    // Read-only: the legacy account is left as it was
+    assert!(ai.data_len() < 8 + LevelState::SIZE);
+}
+
+#[test]
+fn ref_closed_or_foreign_level_state_falls_back_to_admin() {
+    let upline_player = Pubkey::new_unique();
+    let admin = Pubkey::new_unique();
+    let level = 2;
+    let (key, _) = derive_level_state_pda(&upline_player, level);
+    let owner = crate::ID;
+
+    // Not activated
+    let mut lamports = 1;
+    let mut data = legacy_level_state_data(&upline_player, &Pubkey::new_unique(), level, 0);
+    let ai = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    assert_eq!(
+        resolve_ref_with_level_or_admin(&upline_player, level, Some(&ai), &admin),
+        admin
+    );
+
+    // Someone else's LevelState
+    let other = Pubkey::new_unique();
+    let (other_key, _) = derive_level_state_pda(&other, level);
+    let mut lamports = 1;
+    let mut data = legacy_level_state_data(&other, &Pubkey::new_unique(), level, 1);
+    let ai = AccountInfo::new(
+        &other_key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    assert_eq!(
+        resolve_ref_with_level_or_admin(&upline_player, level, Some(&ai), &admin),
+        admin
+    );
+
+    // Wrong discriminator at the right address
+    let mut lamports = 1;
+    let mut data = legacy_level_state_data(&upline_player, &Pubkey::new_unique(), level, 1);
+    data[..8].copy_from_slice(Player::DISCRIMINATOR);
+    let ai = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    assert_eq!(
+        resolve_ref_with_level_or_admin(&upline_player, level, Some(&ai), &admin),
+        admin
+    );
+}
+
+fn queue_page(level_pool: &Pubkey, page_index: u32, players: usize) -> QueuePage {
+    QueuePage {
+        bump: 255,
+        level_pool: *level_pool,
+        page_index,
+        next_page: None,
+        players: (0..players).map(|_| Pubkey::new_unique()).collect(),
+    }
+}
+
+#[test]
+fn legacy_member_counts_players_across_pages() {
+    let pool = Pubkey::new_unique();
+    let head = queue_page(&pool, 4, 10);
+
+    // Same page as the head owner
+    assert_eq!(legacy_queue_ahead(&head, &head, 0).unwrap(), 0);
+    assert_eq!(legacy_queue_ahead(&head, &head, 7).unwrap(), 7);
+
+    // Next page: the head page's remainder first
+    let next = queue_page(&pool, 5, 3);
+    assert_eq!(legacy_queue_ahead(&head, &next, 2).unwrap(), 12);
+
+    // Two full pages in between
+    let far = queue_page(&pool, 8, 1);
+    let full = QUEUE_PAGE_CAPACITY_DEFAULT as u64;
+    assert_eq!(legacy_queue_ahead(&head, &far, 0).unwrap(), 10 + 3 * full);
+
+    // A page already dequeued past
+    let old = queue_page(&pool, 3, 1);
+    assert!(legacy_queue_ahead(&head, &old, 0).is_err());
+}
+
+#[test]
+fn legacy_member_page_must_belong_to_the_pool() {
+    let pool = Pubkey::new_unique();
+    let page = queue_page(&pool, 2, 1);
+    let seeds = QueuePage::seeds(&pool, 2);
+    let (key, _) = Pubkey::find_program_address(&[&seeds[0], &seeds[1], &seeds[2]], &crate::ID);
+    let owner = crate::ID;
+    let mut data = Vec::new();
+    page.try_serialize(&mut data).unwrap();
+
+    let mut lamports = 1;
+    let mut buf = data.clone();
+    let ai = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut buf,
+        &owner,
+        false,
+        0,
+    );
+    assert_eq!(read_legacy_member_page(&ai, &pool).unwrap().page_index, 2);
+    // Another level's pool
+    assert!(read_legacy_member_page(&ai, &Pubkey::new_unique()).is_err());
+
+    // Right contents at a non-canonical address
+    let stray = Pubkey::new_unique();
+    let mut lamports = 1;
+    let mut buf = data.clone();
+    let ai = AccountInfo::new(
+        &stray,
+        false,
+        false,
+        &mut lamports,
+        &mut buf,
+        &owner,
+        false,
+        0,
+    );
+    assert!(read_legacy_member_page(&ai, &pool).is_err());
+
+    // Not owned by the program
+    let foreign = Pubkey::new_unique();
+    let mut lamports = 1;
+    let mut buf = data;
+    let ai = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut buf,
+        &foreign,
+        false,
+        0,
+    );
+    assert!(read_legacy_member_page(&ai, &pool).is_err());
+}
+
+#[test]
+fn rent_settlement_lamports_mode() {
+    // Netted out of the treasury share first
+    let plan = plan_rent_settlement(false, 1_000, 600, None).unwrap();
+    assert_eq!(
+        plan,
+        RentSettlement {
+            from_treasury_share: 600,
+            from_reserve: 0,
+            payer_bears: 0
+        }
+    );
+
+    // Remainder from the reserve, which must cover all of it
+    let plan = plan_rent_settlement(false, 1_000, 1_500, Some(500)).unwrap();
+    assert_eq!(
+        plan,
+        RentSettlement {
+            from_treasury_share: 1_000,
+            from_reserve: 500,
+            payer_bears: 0
+        }
+    );
+    assert!(plan_rent_settlement(false, 1_000, 1_500, Some(499)).is_err());
+    assert!(plan_rent_settlement(false, 1_000, 1_500, None).is_err());
+}
+
+#[test]
+fn rent_settlement_token_mode() {
+    // Nothing netted out of a token share; the reserve refunds what it holds
+    let plan = plan_rent_settlement(true, 1_000, 600, Some(10_000)).unwrap();
+    assert_eq!(
+        plan,
+        RentSettlement {
+            from_treasury_share: 0,
+            from_reserve: 600,
+            payer_bears: 0
+        }
+    );
+    let plan = plan_rent_settlement(true, 1_000, 600, Some(250)).unwrap();
+    assert_eq!(
+        plan,
+        RentSettlement {
+            from_treasury_share: 0,
+            from_reserve: 250,
+            payer_bears: 350
+        }
+    );
+    let plan = plan_rent_settlement(true, 1_000, 600, None).unwrap();
+    assert_eq!(
+        plan,
+        RentSettlement {
+            from_treasury_share: 0,
+            from_reserve: 0,
+            payer_bears: 600
+        }
+    );
+}
+
+fn transfer_fee(bps: u16, maximum_fee: u64) -> TransferFee {
+    TransferFee {
+        epoch: 0.into(),
+        maximum_fee: maximum_fee.into(),
+        transfer_fee_basis_points: bps.into(),
+    }
+}
+
+#[test]
+fn leg_fees_come_out_of_the_treasury_share() {
+    let payout = PayoutBreakdown {
+        owner_amount: 7_000,
+        ref1_amount: 1_000,
+        ref2_amount: 0,
+        ref3_amount: 333,
+        treasury_amount: 1_667,
+        ..Default::default()
+    };
+    assert_eq!(treasury_after_leg_fees(&payout, None).unwrap(), (1_667, 0));
+
+    let fee = transfer_fee(100, u64::MAX);
+    let legs = [7_000u64, 1_000, 0, 333];
+    let expected_fees: u64 = legs
+        .iter()
+        .map(|&a| gross_leg_amount(Some(&fee), a, true).unwrap() - a)
+        .sum();
+    // Each grossed-up leg nets the recipient exactly its share
+    for a in legs {
+        let gross = gross_leg_amount(Some(&fee), a, true).unwrap();
+        assert_eq!(gross - fee.calculate_fee(gross).unwrap(), a);
+    }
+    assert_eq!(
+        treasury_after_leg_fees(&payout, Some(&fee)).unwrap(),
+        (1_667 - expected_fees, expected_fees)
+    );
+
+    // The treasury's own leg is sent as-is
+    assert_eq!(gross_leg_amount(Some(&fee), 1_000, false).unwrap(), 1_000);
+
+    // Fees larger than the treasury share
+    let steep = transfer_fee(5_000, u64::MAX);
+    assert!(treasury_after_leg_fees(&payout, Some(&steep)).is_err());
+}
+
+#[test]
+fn recipient_ata_space_matches_the_token_program() {
+    let owner = anchor_spl::token::ID;
+    let key = Pubkey::new_unique();
+    let mut lamports = 1;
+    let mut data = vec![0u8; spl_token_2022::state::Mint::LEN];
+    let mint = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    assert_eq!(recipient_ata_space(&mint).unwrap(), 165);
+
+    // Token-2022 mint with a transfer fee: TransferFeeAmount + ImmutableOwner on the ATA
+    let space = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+        ExtensionType::TransferFeeConfig,
+    ])
+    .unwrap();
+    let mut data = vec![0u8; space];
+    {
+        let mut state =
+            StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data)
+                .unwrap();
+        state.init_extension::<TransferFeeConfig>(true).unwrap();
+        state.base.is_initialized = true;
+        state.base.decimals = 6;
+        state.pack_base();
+        state.init_account_type().unwrap();
+    }
+    let owner = anchor_spl::token_2022::ID;
+    let mut lamports = 1;
+    let mint = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    assert_eq!(recipient_ata_space(&mint).unwrap(), 165 + 1 + (4 + 8) + 4);
+}
+
+fn level_state_data(player: &Pubkey, authority: &Pubkey, level: u8) -> Vec<u8> {
+    let ls = LevelState {
+        player: *player,
+        authority: *authority,
+        level,
+        bump: 255,
+        activated_at: 1_700_000_000,
+        cycles: 0,
+        slots_filled: 0,
+        head_page: None,
+        tail_page: None,
+        queue_page: None,
+        queue_pos: 0,
+    };
+    // Allocated at full size (None options serialize short)
+    let mut data = Vec::new();
+    ls.try_serialize(&mut data).unwrap();
+    data.resize(8 + LevelState::SIZE, 0);
+    data
+}
+
+#[test]
+fn rotation_rewrites_every_activated_level() {
+    let old_wallet = Pubkey::new_unique();
+    let new_wallet = Pubkey::new_unique();
+    let (player_key, _) =
+        Pubkey::find_program_address(&[b"player", old_wallet.as_ref()], &crate::ID);
+    let mut player = Player {
+        authority: old_wallet,
+        bump: 255,
+        created_at: 0,
+        games_played: 0,
+        upline1: Pubkey::default(),
+        upline2: Pubkey::default(),
+        upline3: Pubkey::default(),
+        next_nonce: 0,
+    };
+
+    // Levels 2 and 5 activated, the rest never created
+    let keys: Vec<Pubkey> = (1..=MAX_LEVEL)
+        .map(|l| derive_level_state_pda(&player_key, l).0)
+        .collect();
+    let mut lamports = vec![0u64; MAX_LEVEL as usize];
+    let mut data: Vec<Vec<u8>> = (1..=MAX_LEVEL)
+        .map(|l| match l {
+            2 | 5 => level_state_data(&player_key, &old_wallet, l),
+            _ => Vec::new(),
+        })
+        .collect();
+    let program = crate::ID;
+    let system = anchor_lang::system_program::ID;
+    let level_states: Vec<AccountInfo> = keys
+        .iter()
+        .zip(lamports.iter_mut())
+        .zip(data.iter_mut())
+        .map(|((k, l), d)| {
+            let owner = if d.is_empty() { &system } else { &program };
+            AccountInfo::new(k, false, true, l, d, owner, false, 0)
+        })
+        .collect();
+
+    let payer_key = Pubkey::new_unique();
+    let mut payer_lamports = 0;
+    let mut payer_data = Vec::new();
+    let payer = AccountInfo::new(
+        &payer_key,
+        true,
+        true,
+        &mut payer_lamports,
+        &mut payer_data,
+        &system,
+        false,
+        0,
+    );
+    let mut sys_lamports = 0;
+    let mut sys_data = Vec::new();
+    let sys = AccountInfo::new(
+        &system,
+        false,
+        false,
+        &mut sys_lamports,
+        &mut sys_data,
+        &system,
+        true,
+        0,
+    );
+    let mut link = AuthorityLink {
+        player: Pubkey::default(),
+        bump: 0,
+    };
+
+    // Rejected attempts run on copies (a failed tx leaves the accounts untouched)
+
+    // A partial set (only the activated levels) is rejected
+    let partial = [level_states[1].clone(), level_states[4].clone()];
+    assert!(apply_rotation(
+        &mut player.clone(),
+        player_key,
+        new_wallet,
+        &mut link,
+        254,
+        &partial,
+        &payer,
+        &sys
+    )
+    .is_err());
+
+    // So is a full set out of order
+    let mut shuffled = level_states.clone();
+    shuffled.swap(1, 4);
+    assert!(apply_rotation(
+        &mut player.clone(),
+        player_key,
+        new_wallet,
+        &mut link,
+        254,
+        &shuffled,
+        &payer,
+        &sys
+    )
+    .is_err());
+
+    let old = apply_rotation(
+        &mut player,
+        player_key,
+        new_wallet,
+        &mut link,
+        254,
+        &level_states,
+        &payer,
+        &sys,
+    )
+    .unwrap();
+    assert_eq!(old, old_wallet);
+    assert_eq!(player.authority, new_wallet);
+    assert_eq!(link.player, player_key);
+    for i in [1usize, 4] {
+        assert_eq!(
+            peek_level_state(&level_states[i]).unwrap().authority,
+            new_wallet
+        );
+    }
+}
+
+#[test]
+fn owner_payout_follows_the_owner_player() {
+    let player_key = Pubkey::new_unique();
+    let recorded = Pubkey::new_unique();
+    let rotated = Pubkey::new_unique();
+    let data = level_state_data(&player_key, &recorded, 1);
+    let mut slice: &[u8] = &data;
+    let ls = LevelState::try_deserialize(&mut slice).unwrap();
+    let owner = Player {
+        authority: rotated,
+        bump: 255,
+        created_at: 0,
+        games_played: 0,
+        upline1: Pubkey::default(),
+        upline2: Pubkey::default(),
+        upline3: Pubkey::default(),
+        next_nonce: 0,
+    };
+    assert_eq!(owner_payout_wallet(Some(&owner), &ls), rotated);
+    // Orphaned LevelState (owner Player closed / never created)
+    assert_eq!(owner_payout_wallet(None, &ls), recorded);
+}
+
+#[test]
+fn batch_takes_one_max_price_per_level() {
+    assert!(check_batch_args(&[1, 2, 3], &[10, 20, 30]).is_ok());
+    // Missing / extra caps
+    assert!(check_batch_args(&[1, 2, 3], &[10, 20]).is_err());
+    assert!(check_batch_args(&[1], &[10, 20]).is_err());
+    // Duplicate level, empty and oversized batches
+    assert!(check_batch_args(&[2, 2], &[10, 10]).is_err());
+    assert!(check_batch_args(&[], &[]).is_err());
+    let levels: Vec<u8> = (1..=MAX_BATCH_LEVELS as u8 + 1).collect();
+    assert!(check_batch_args(&levels, &vec![1; levels.len()]).is_err());
+}
+
+// ActivateLevelV3 metas as the SDK builds them for `player` activating `level`
+fn activation_metas(
+    config: &Pubkey,
+    admin: &Pubkey,
+    treasury: &Pubkey,
+    level: u8,
+) -> Vec<AccountMeta> {
+    let player = Pubkey::new_unique();
+    let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &crate::ID).0;
+    crate::accounts::ActivateLevelV3 {
+        player,
+        level_state: pda(&[b"lvl", player.as_ref(), &[level]]),
+        tx_guard: None,
+        authority: Pubkey::new_unique(),
+        config_v3: *config,
+        level_pool: pda(&[b"level_pool_v1", config.as_ref(), &[level]]),
+        admin_account: *admin,
+        treasury_account: *treasury,
+        ref1_account: Pubkey::new_unique(),
+        ref2_account: Pubkey::new_unique(),
+        ref3_account: Pubkey::new_unique(),
+        tail_page: Pubkey::new_unique(),
+        new_page: Pubkey::new_unique(),
+        head_page: Pubkey::new_unique(),
+        owner_player: Pubkey::new_unique(),
+        owner_level_state: Pubkey::new_unique(),
+        owner_wallet: Pubkey::new_unique(),
+        ref1_level_state: None,
+        ref2_level_state: None,
+        ref3_level_state: None,
+        system_program: anchor_lang::system_program::ID,
+        rent_reserve: Some(pda(&[RENT_RESERVE_SEED])),
+        previous_level_state: None,
+        price_feed: None,
+        #[cfg(feature = "event-cpi")]
+        event_authority: pda(&[b"__event_authority"]),
+        #[cfg(feature = "event-cpi")]
+        program: crate::ID,
+    }
+    .to_account_metas(None)
+}
+
+fn write_locks(metas: &[AccountMeta]) -> std::collections::BTreeSet<Pubkey> {
+    metas
+        .iter()
+        .filter(|m| m.is_writable && m.pubkey != crate::ID)
+        .map(|m| m.pubkey)
+        .collect()
+}
+
+#[test]
+fn activations_on_different_levels_share_no_config_write_lock() {
+    let config = Pubkey::find_program_address(&[b"config_v3_new"], &crate::ID).0;
+    let admin = Pubkey::new_unique();
+    let treasury = Pubkey::new_unique();
+    let a = activation_metas(&config, &admin, &treasury, 1);
+    let b = activation_metas(&config, &admin, &treasury, 2);
+
+    for metas in [&a, &b] {
+        let cfg = metas.iter().find(|m| m.pubkey == config).unwrap();
+        assert!(!cfg.is_writable);
+        let adm = metas.iter().find(|m| m.pubkey == admin).unwrap();
+        assert!(!adm.is_writable);
+    }
+    // Only the credited treasury and the rent reserve serialize the two activations
+    let shared: Vec<Pubkey> = write_locks(&a)
+        .intersection(&write_locks(&b))
+        .copied()
+        .collect();
+    let reserve = Pubkey::find_program_address(&[RENT_RESERVE_SEED], &crate::ID).0;
+    let mut expected = vec![treasury, reserve];
+    expected.sort();
+    assert_eq!(shared, expected);
+}
+
+#[test]
+fn registration_reads_config_without_a_write_lock() {
+    let config = Pubkey::find_program_address(&[b"config_v3_new"], &crate::ID).0;
+    #[cfg(feature = "event-cpi")]
+    let event_authority = Pubkey::find_program_address(&[b"__event_authority"], &crate::ID).0;
+    let create = crate::accounts::CreatePlayerV3 {
+        player: Pubkey::new_unique(),
+        authority: Pubkey::new_unique(),
+        config_v3: config,
+        global_stats: Pubkey::new_unique(),
+        stats_shard: Pubkey::new_unique(),
+        referrer_player: None,
+        system_program: anchor_lang::system_program::ID,
+        authority_link: Pubkey::new_unique(),
+        #[cfg(feature = "event-cpi")]
+        event_authority,
+        #[cfg(feature = "event-cpi")]
+        program: crate::ID,
+    }
+    .to_account_metas(None);
+    let register = crate::accounts::RegisterPlayer {
+        player: Pubkey::new_unique(),
+        authority: Pubkey::new_unique(),
+        tx_guard: None,
+        config_v3: config,
+        global_stats: Pubkey::new_unique(),
+        stats_shard: Pubkey::new_unique(),
+        referrer_player: None,
+        system_program: anchor_lang::system_program::ID,
+        authority_link: Pubkey::new_unique(),
+        #[cfg(feature = "event-cpi")]
+        event_authority,
+        #[cfg(feature = "event-cpi")]
+        program: crate::ID,
+    }
+    .to_account_metas(None);
+    for metas in [create, register] {
+        assert!(!write_locks(&metas).contains(&config));
+    }
+}
+
+fn error_code<T>(result: Result<T>) -> u32 {
+    match result {
+        Err(Error::AnchorError(e)) => e.error_code_number,
+        Err(e) => panic!("unexpected error {e:?}"),
+        Ok(_) => panic!("expected an error"),
+    }
+}
+
+fn code(e: CustomError) -> u32 {
+    u32::from(e)
+}
+
+fn sol_usd(price: i64, exponent: i32) -> PythPrice {
+    PythPrice {
+        price,
+        conf: 0,
+        exponent,
+        publish_time: 0,
+    }
+}
+
+#[test]
+fn usd_cents_convert_for_any_exponent() {
+    // $150.00000000 per SOL: $15 = 0.1 SOL
+    assert_eq!(
+        usd_cents_to_lamports(1_500, &sol_usd(15_000_000_000, -8)).unwrap(),
+        100_000_000
+    );
+    // Same price with a positive exponent (15 * 10^1)
+    assert_eq!(
+        usd_cents_to_lamports(1_500, &sol_usd(15, 1)).unwrap(),
+        100_000_000
+    );
+    assert_eq!(
+        usd_cents_to_lamports(1_500, &sol_usd(150, 0)).unwrap(),
+        100_000_000
+    );
+    // $3 per SOL: 1 cent = 3_333_333.3 lamports, rounded up
+    assert_eq!(
+        usd_cents_to_lamports(1, &sol_usd(300_000_000, -8)).unwrap(),
+        3_333_334
+    );
+    assert_eq!(
+        error_code(usd_cents_to_lamports(0, &sol_usd(150, 0))),
+        code(CustomError::InvalidPrice)
+    );
+    // 10^39 does not fit u128
+    assert_eq!(
+        error_code(usd_cents_to_lamports(1, &sol_usd(150, -39))),
+        code(CustomError::Overflow)
+    );
+    // Result above u64::MAX lamports
+    assert_eq!(
+        error_code(usd_cents_to_lamports(u32::MAX, &sol_usd(1, -18))),
+        code(CustomError::Overflow)
+    );
+}
+
+const FEED_ID: [u8; 32] = [7; 32];
+
+// PriceUpdateV2 as posted by the Pyth receiver (full verification)
+fn pyth_feed_data(price: i64, conf: u64, exponent: i32, publish_time: i64) -> Vec<u8> {
+    let mut data = PYTH_PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(Pubkey::new_unique().as_ref());
+    data.push(PYTH_VERIFICATION_FULL);
+    data.extend_from_slice(&FEED_ID);
+    data.extend_from_slice(&price.to_le_bytes());
+    data.extend_from_slice(&conf.to_le_bytes());
+    data.extend_from_slice(&exponent.to_le_bytes());
+    data.extend_from_slice(&publish_time.to_le_bytes());
+    // prev_publish_time, ema_price, ema_conf, posted_slot
+    data.extend_from_slice(&[0; 32]);
+    data
+}
+
+fn usd_config() -> ConfigV3 {
+    let mut cfg = decode_config_padded(ConfigV3::DISCRIMINATOR).unwrap();
+    cfg.usd_price_feed = Pubkey::new_unique();
+    cfg.usd_feed_id = FEED_ID;
+    cfg.usd_feed_max_age = DEFAULT_USD_FEED_MAX_AGE;
+    cfg.usd_feed_max_conf_bps = DEFAULT_USD_FEED_MAX_CONF_BPS;
+    cfg
+}
+
+fn read_feed(data: Vec<u8>, owner: Pubkey, cfg: &ConfigV3, now: i64) -> Result<PythPrice> {
+    let key = cfg.usd_price_feed;
+    let mut lamports = 1;
+    let mut data = data;
+    let ai = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+    read_pyth_price(&ai, cfg, now)
+}
+
+#[test]
+fn pyth_price_is_read_and_checked() {
+    let cfg = usd_config();
+    let now = 1_000_000;
+    let read = |data: Vec<u8>| read_feed(data, PYTH_RECEIVER_ID, &cfg, now);
+
+    let price = read(pyth_feed_data(15_000_000_000, 1_000_000, -8, now - 5)).unwrap();
+    assert_eq!(price.price, 15_000_000_000);
+    assert_eq!(price.conf, 1_000_000);
+    assert_eq!(price.exponent, -8);
+    assert_eq!(price.publish_time, now - 5);
+    // Exactly max_age old is still fresh
+    let max_age = cfg.usd_feed_max_age as i64;
+    assert!(read(pyth_feed_data(150, 0, 0, now - max_age)).is_ok());
+
+    let invalid = code(CustomError::InvalidPriceFeed);
+    // Zero / negative price
+    assert_eq!(error_code(read(pyth_feed_data(0, 0, -8, now))), invalid);
+    assert_eq!(error_code(read(pyth_feed_data(-1, 0, -8, now))), invalid);
+    // Stale and future publish_time
+    assert_eq!(
+        error_code(read(pyth_feed_data(150, 0, 0, now - max_age - 1))),
+        code(CustomError::StalePriceFeed)
+    );
+    assert_eq!(
+        error_code(read(pyth_feed_data(150, 0, 0, now + 1))),
+        invalid
+    );
+    // conf / price above usd_feed_max_conf_bps (2%)
+    assert!(read(pyth_feed_data(10_000, 200, 0, now)).is_ok());
+    assert_eq!(
+        error_code(read(pyth_feed_data(10_000, 201, 0, now))),
+        code(CustomError::PriceFeedConfidenceTooWide)
+    );
+
+    // Another feed (e.g. BTC/USD) posted by the same receiver
+    let mut data = pyth_feed_data(150, 0, 0, now);
+    data[41..73].copy_from_slice(&[8; 32]);
+    assert_eq!(error_code(read(data)), invalid);
+    // Partially verified update
+    let mut data = pyth_feed_data(150, 0, 0, now);
+    data[40] = 0;
+    assert_eq!(error_code(read(data)), invalid);
+    // Truncated account
+    let mut data = pyth_feed_data(150, 0, 0, now);
+    data.truncate(100);
+    assert_eq!(error_code(read(data)), invalid);
+    // Same bytes in an account the receiver does not own
+    assert_eq!(
+        error_code(read_feed(
+            pyth_feed_data(150, 0, 0, now),
+            Pubkey::new_unique(),
+            &cfg,
+            now
+        )),
+        invalid
+    );
+}
+
+fn paid_total(p: &PayoutBreakdown) -> u64 {
+    p.owner_amount + p.ref1_amount + p.ref2_amount + p.ref3_amount + p.treasury_amount
+}
+
+#[test]
+fn split_pays_out_the_whole_price() {
+    let mut cfg = decode_config_padded(ConfigV3::DISCRIMINATOR).unwrap();
+    cfg.admin = Pubkey::new_unique();
+    cfg.treasury = Pubkey::new_unique();
+    let owner = Pubkey::new_unique();
+    let refs = [
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+
+    let p = PayoutBreakdown::split(1_000, &cfg, owner, refs);
+    assert_eq!(
+        (p.owner_amount, p.ref1_amount, p.ref2_amount, p.ref3_amount),
+        (600, 130, 80, 50)
+    );
+    assert_eq!(p.treasury_amount, 140);
+    assert_eq!(
+        (p.owner_wallet, p.ref1_wallet, p.ref2_wallet, p.ref3_wallet),
+        (owner, refs[0], refs[1], refs[2])
+    );
+
+    // Rounding dust of every share stays with the treasury
+    for price in [1u64, 7, 99, 101, 12_345, 123_456_789, 50_000_000_001] {
+        let p = PayoutBreakdown::split(price, &cfg, owner, refs);
+        assert_eq!(paid_total(&p), price);
+        assert_eq!(p.owner_amount, price * PERC_OWNER / 100);
+        assert!(p.treasury_amount >= price * PERC_TREASURY_BASE / 100);
+    }
+}
+
+#[test]
+fn split_redirects_admin_and_treasury_refs() {
+    let mut cfg = decode_config_padded(ConfigV3::DISCRIMINATOR).unwrap();
+    cfg.admin = Pubkey::new_unique();
+    cfg.treasury = Pubkey::new_unique();
+    let upline = Pubkey::new_unique();
+    let price = 10_007;
+    let direct = PayoutBreakdown::split(price, &cfg, Pubkey::new_unique(), [upline; 3]);
+
+    // Orphan fallbacks: ref1 -> admin, ref3 -> treasury
+    let p = PayoutBreakdown::split(
+        price,
+        &cfg,
+        Pubkey::new_unique(),
+        [cfg.admin, upline, cfg.treasury],
+    );
+    assert_eq!(
+        (p.ref1_amount, p.ref2_amount, p.ref3_amount),
+        (0, direct.ref2_amount, 0)
+    );
+    assert_eq!(
+        p.treasury_amount,
+        direct.treasury_amount + direct.ref1_amount + direct.ref3_amount
+    );
+    assert_eq!((p.ref1_wallet, p.ref3_wallet), (cfg.admin, cfg.treasury));
+    assert_eq!(paid_total(&p), price);
+
+    let all = PayoutBreakdown::split(price, &cfg, Pubkey::new_unique(), [cfg.admin; 3]);
+    assert_eq!(all.treasury_amount, price - all.owner_amount);
+}
+
+#[test]
+fn split_with_leg_fees_still_sums_to_the_price() {
+    let mut cfg = decode_config_padded(ConfigV3::DISCRIMINATOR).unwrap();
+    cfg.admin = Pubkey::new_unique();
+    cfg.treasury = Pubkey::new_unique();
+    let refs = [Pubkey::new_unique(), cfg.admin, Pubkey::new_unique()];
+    let fee = transfer_fee(50, 1_000);
+    for price in [999u64, 1_000_000, 777_777_777] {
+        let p = PayoutBreakdown::split(price, &cfg, Pubkey::new_unique(), refs);
+        let (treasury_left, fees) = treasury_after_leg_fees(&p, Some(&fee)).unwrap();
+        assert_eq!(
+            p.owner_amount + p.ref1_amount + p.ref2_amount + p.ref3_amount + treasury_left + fees,
+            price
+        );
+    }
+}
+
+fn player(next_nonce: u64) -> Player {
+    Player {
+        authority: Pubkey::new_unique(),
+        bump: 254,
+        created_at: 1_700_000_000,
+        games_played: 3,
+        upline1: Pubkey::new_unique(),
+        upline2: Pubkey::new_unique(),
+        upline3: Pubkey::default(),
+        next_nonce,
+    }
+}
+
+#[test]
+fn player_decodes_with_and_without_next_nonce() {
+    let current = player(41);
+    let mut data = Vec::new();
+    current.try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), 8 + Player::SIZE);
+    let decoded = decode_player_padded(&data).unwrap();
+    assert_eq!(decoded.authority, current.authority);
+    assert_eq!(decoded.next_nonce, 41);
+
+    // Pre-v3.14 account: no next_nonce on chain
+    data.truncate(8 + Player::SIZE - 8);
+    let old = decode_player_padded(&data).unwrap();
+    assert_eq!(
+        (old.authority, old.bump, old.created_at, old.games_played),
+        (current.authority, 254, 1_700_000_000, 3)
+    );
+    assert_eq!(
+        (old.upline1, old.upline2, old.upline3),
+        (current.upline1, current.upline2, Pubkey::default())
+    );
+    assert_eq!(old.next_nonce, 0);
+
+    // Another account type / garbage
+    let mut foreign = data.clone();
+    foreign[..8].copy_from_slice(LevelState::DISCRIMINATOR);
+    assert!(decode_player_padded(&foreign).is_err());
+    assert!(decode_player_padded(&[]).is_err());
+}
+
+#[test]
+fn config_decodes_every_appended_version() {
+    let mut cfg = decode_config_padded(ConfigV3::DISCRIMINATOR).unwrap();
+    cfg.admin = Pubkey::new_unique();
+    cfg.treasury = Pubkey::new_unique();
+    cfg.base_price_lamports = 50_000_000;
+    cfg.legacy_tx_guard = true;
+    cfg.usd_price_feed = Pubkey::new_unique();
+    cfg.usd_level_prices_cents = [500; 16];
+    cfg.usd_feed_id = FEED_ID;
+    let mut data = Vec::new();
+    cfg.try_serialize(&mut data).unwrap();
+    assert!(data.len() <= 8 + ConfigV3::SIZE);
+
+    let full = decode_config_padded(&data).unwrap();
+    assert_eq!(full.usd_feed_id, FEED_ID);
+    assert!(full.usd_mode());
+
+    // Pre-v3.20 (no usd_feed_id) and pre-v3.13 (up to version_minor) layouts
+    let before_feed_id = decode_config_padded(&data[..data.len() - 32]).unwrap();
+    assert_eq!(before_feed_id.usd_feed_id, [0; 32]);
+    assert_eq!(before_feed_id.usd_level_prices_cents, [500; 16]);
+    let v3_12_len = 8 + 32 + 32 + 5 + 8 + 1 + 4 + 1 + 1 + 1 + 1 + 1 + 1;
+    let v3_12 = decode_config_padded(&data[..v3_12_len]).unwrap();
+    assert_eq!(
+        (v3_12.admin, v3_12.treasury, v3_12.base_price_lamports),
+        (cfg.admin, cfg.treasury, 50_000_000)
+    );
+    assert!(!v3_12.legacy_tx_guard);
+    assert!(!v3_12.usd_mode() && !v3_12.token_mode());
+
+    let mut foreign = data;
+    foreign[..8].copy_from_slice(Player::DISCRIMINATOR);
+    assert!(decode_config_padded(&foreign).is_err());
+}
+
+#[test]
+fn legacy_level_state_decodes_old_and_current_layouts() {
+    let player = Pubkey::new_unique();
+    let authority = Pubkey::new_unique();
+
+    // Before queue_page / queue_pos
+    let old =
+        decode_legacy_level_state(&legacy_level_state_data(&player, &authority, 5, 77)).unwrap();
+    assert_eq!(
+        (old.player, old.authority, old.level),
+        (player, authority, 5)
+    );
+    assert_eq!(
+        (old.bump, old.activated_at, old.cycles, old.slots_filled),
+        (255, 77, 2, 1)
+    );
+    assert_eq!(
+        (old.head_page, old.tail_page, old.queue_page),
+        (None, None, None)
+    );
+    assert_eq!(old.queue_pos, 0);
+
+    // Current layout round-trips
+    let page = Pubkey::new_unique();
+    let current = LevelState {
+        head_page: Some(Pubkey::new_unique()),
+        queue_page: Some(page),
+        queue_pos: 130,
+        ..old
+    };
+    let mut data = Vec::new();
+    current.try_serialize(&mut data).unwrap();
+    let decoded = decode_legacy_level_state(&data).unwrap();
+    assert_eq!(decoded.head_page, current.head_page);
+    assert_eq!(decoded.tail_page, None);
+    assert_eq!((decoded.queue_page, decoded.queue_pos), (Some(page), 130));
+
+    // Only the discriminator: every field defaults
+    let empty = decode_legacy_level_state(LevelState::DISCRIMINATOR).unwrap();
+    assert_eq!((empty.player, empty.level), (Pubkey::default(), 0));
+    assert!(decode_legacy_level_state(&[0; 7]).is_err());
+}
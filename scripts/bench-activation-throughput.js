@@ -0,0 +1,304 @@
+// scripts/bench-activation-throughput.js
+// LOCAL VALIDATOR ONLY — measures how activate_level_v3 txs on DIFFERENT levels
+// are scheduled when ConfigV3 (and the other accounts the program only reads) are
+// passed writable (legacy client) vs read-only (current client).
+//
+// Prereqs:
+//   solana-test-validator --bpf-program <PROGRAM_ID> target/deploy/solana_game.so --reset
+//   node scripts/init_config_v3.js   (pointed at http://127.0.0.1:8899, same PROGRAM_ID)
+//   node scripts/init_stats_shards.js (same RPC / PROGRAM_ID)
+//
+// Usage:
+//   PROGRAM_ID=<id> node scripts/bench-activation-throughput.js [keypair.json]
+//
+// Setup (not timed): one seed player per level makes the first activation, so
+// every level has a queue. Each round then sends one activation per level, all in
+// parallel, from fresh players (levels 1..8 = legacy writable metas, 9..16 =
+// read-only metas). These are regular activations into an existing queue: the
+// head owner is paid and the new player is enqueued.
+// NOTE: ActivateLevelV3 declares treasury and ref1..3 `mut`, so they are always
+// writable. Treasury (and admin, whenever a ref falls back to it — the seed
+// players' uplines have no open level here) stays a shared write lock; the
+// benchmark shows what is left after removing the ConfigV3 lock.
+//
+// Results:
+//   Write-lock sets (cargo test -p solana_game, processor::tests::
+//   activations_on_different_levels_share_no_config_write_lock): two activations
+//   of different players on different levels share exactly {treasury, rent_reserve};
+//   config_v3 and admin are read-only. CreatePlayerV3 / RegisterPlayer take no
+//   lock on config_v3 (registration_reads_config_without_a_write_lock).
+//   Throughput (this script): not measured yet — needs a validator with the
+//   build-sbf artifact of this tree; record slots/tps for both halves here.
+
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const {
+  Connection,
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  sendAndConfirmTransaction,
+} = require("@solana/web3.js");
+
+// --------------------
+// CONFIG
+// --------------------
+const RPC_URL = process.env.RPC_URL || "http://127.0.0.1:8899";
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+const KEYPAIR_PATH =
+  process.argv[2] || path.join(os.homedir(), ".config/solana/id.json");
+
+const connection = new Connection(RPC_URL, { commitment: "confirmed" });
+const payer = Keypair.fromSecretKey(
+  Uint8Array.from(JSON.parse(fs.readFileSync(KEYPAIR_PATH, "utf8")))
+);
+
+// --------------------
+// DISCRIMINATORS
+// --------------------
+const DISCR_REGISTER = Buffer.from([242, 146, 194, 234, 234, 145, 228, 42]);
+const DISCR_ACTIVATE = Buffer.from([0, 26, 75, 130, 110, 192, 143, 66]);
+
+const LEVEL_PRICES_LAMPORTS = [
+  50_000_000n, 180_000_000n, 360_000_000n, 680_000_000n,
+  1_100_000_000n, 1_550_000_000n, 2_100_000_000n, 2_650_000_000n,
+  3_250_000_000n, 3_900_000_000n, 4_650_000_000n, 5_450_000_000n,
+  6_400_000_000n, 7_350_000_000n, 8_000_000_000n, 8_700_000_000n,
+];
+
+// --------------------
+// PDA
+// --------------------
+const pda = (seeds) => PublicKey.findProgramAddressSync(seeds, PROGRAM_ID)[0];
+const u64 = (n) => {
+  const b = Buffer.alloc(8);
+  b.writeBigUInt64LE(BigInt(n));
+  return b;
+};
+const u32 = (n) => {
+  const b = Buffer.alloc(4);
+  b.writeUInt32LE(n);
+  return b;
+};
+
+const configPda = pda([Buffer.from("config_v3_new")]);
+const statsPda = pda([Buffer.from("global_stats_v1")]);
+const playerPda = (authority) => pda([Buffer.from("player"), authority.toBuffer()]);
+const authorityLinkPda = (wallet) => pda([Buffer.from("authority_link_v1"), wallet.toBuffer()]);
+const levelStatePda = (player, level) =>
+  pda([Buffer.from("lvl"), player.toBuffer(), Buffer.from([level])]);
+const levelPoolPda = (level) =>
+  pda([Buffer.from("level_pool_v1"), configPda.toBuffer(), Buffer.from([level])]);
+const queuePagePda = (pool, index) =>
+  pda([Buffer.from("queue_page_v1"), pool.toBuffer(), u32(index)]);
+const txGuardActivatePda = (player, level, nonce) =>
+  pda([Buffer.from("tx"), player.toBuffer(), Buffer.from([1]), Buffer.from([level]), u64(nonce)]);
+const statsShardPda = (authority) => {
+  const shard = require("crypto").createHash("sha256").update(authority.toBuffer()).digest()[0] % 16;
+  return pda([Buffer.from("stats_shard_v1"), Buffer.from([shard])]);
+};
+const txGuardRegisterPda = (authority, nonce) =>
+  pda([Buffer.from("tx"), Buffer.from("register"), authority.toBuffer(), u64(nonce)]);
+
+// --------------------
+// IX BUILDERS
+// --------------------
+function registerIx(authority, nonce) {
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: playerPda(authority), isWritable: true, isSigner: false },
+      { pubkey: authority, isWritable: true, isSigner: true },
+      { pubkey: txGuardRegisterPda(authority, nonce), isWritable: true, isSigner: false },
+      { pubkey: configPda, isWritable: false, isSigner: false },
+      { pubkey: statsPda, isWritable: false, isSigner: false },
+      { pubkey: statsShardPda(authority), isWritable: true, isSigner: false },
+      { pubkey: configPda, isWritable: false, isSigner: false }, // referrer placeholder
+      { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+      { pubkey: authorityLinkPda(authority), isWritable: false, isSigner: false },
+    ],
+    data: Buffer.concat([DISCR_REGISTER, Buffer.from([0]), u64(nonce)]),
+  });
+}
+
+// Uplines are Player PDAs or seed wallets; a ref is paid only if its LevelState
+// for this level is activated (mirrors open_level_wallet), otherwise admin.
+async function resolveRef(upline, level, admin) {
+  const none = { wallet: admin, levelState: null };
+  if (upline.equals(admin) || upline.equals(PublicKey.default)) return none;
+  for (const player of [upline, playerPda(upline)]) {
+    const ls = levelStatePda(player, level);
+    const acc = await connection.getAccountInfo(ls);
+    if (!acc || !acc.owner.equals(PROGRAM_ID) || acc.data.length < 82) continue;
+    const activatedAt = acc.data.readBigInt64LE(74);
+    if (acc.data[72] === level && activatedAt > 0n) {
+      return { wallet: new PublicKey(acc.data.slice(40, 72)), levelState: ls };
+    }
+  }
+  return none;
+}
+
+async function ownerRefs(owner, level, admin) {
+  const acc = await connection.getAccountInfo(playerPda(owner));
+  const uplines = [57, 89, 121].map((o) => new PublicKey(acc.data.slice(o, o + 32)));
+  return Promise.all(uplines.map((u) => resolveRef(u, level, admin)));
+}
+
+// `owner` = head of queue page 0 (null on a first activation: owner/ref slots unused).
+// `legacyWritable` = mark the accounts the program only reads writable, like the
+// legacy client did.
+function activationIx(authority, level, nonce, admin, treasury, owner, refs, legacyWritable) {
+  const player = playerPda(authority);
+  const pool = levelPoolPda(level);
+  const page0 = queuePagePda(pool, 0);
+  const page1 = queuePagePda(pool, 1);
+  const w = legacyWritable;
+  const ownerAuthority = owner || authority;
+  const ownerPlayer = playerPda(ownerAuthority);
+  const slots = refs || [null, null, null].map(() => ({ wallet: admin, levelState: null }));
+
+  const keys = [
+    { pubkey: player, isSigner: false, isWritable: true },
+    { pubkey: levelStatePda(player, level), isSigner: false, isWritable: true },
+    { pubkey: txGuardActivatePda(player, level, nonce), isSigner: false, isWritable: true },
+    { pubkey: authority, isSigner: true, isWritable: true },
+    { pubkey: configPda, isSigner: false, isWritable: w },
+    { pubkey: pool, isSigner: false, isWritable: true },
+    { pubkey: admin, isSigner: false, isWritable: w },
+    { pubkey: treasury, isSigner: false, isWritable: true },
+    ...slots.map((r) => ({ pubkey: r.wallet, isSigner: false, isWritable: true })),
+    { pubkey: page0, isSigner: false, isWritable: true }, // tail_page
+    { pubkey: owner ? page1 : page0, isSigner: false, isWritable: true }, // new_page
+    { pubkey: page0, isSigner: false, isWritable: true }, // head_page
+    { pubkey: ownerPlayer, isSigner: false, isWritable: w },
+    { pubkey: levelStatePda(ownerPlayer, level), isSigner: false, isWritable: true },
+    { pubkey: ownerAuthority, isSigner: false, isWritable: true },
+    // ref*_level_state: None = program id
+    ...slots.map((r) => ({ pubkey: r.levelState || PROGRAM_ID, isSigner: false, isWritable: false })),
+    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+  ];
+
+  const data = Buffer.concat([
+    DISCR_ACTIVATE,
+    Buffer.from([level]),
+    u64(LEVEL_PRICES_LAMPORTS[level - 1]),
+    u64(nonce),
+  ]);
+  return new TransactionInstruction({ programId: PROGRAM_ID, keys, data });
+}
+
+// --------------------
+// BENCH
+// --------------------
+async function fundAndRegister(count) {
+  const players = [];
+  for (let i = 0; i < count; i++) {
+    const kp = Keypair.generate();
+    const fund = new Transaction().add(
+      SystemProgram.transfer({
+        fromPubkey: payer.publicKey,
+        toPubkey: kp.publicKey,
+        lamports: 10_000_000_000,
+      })
+    );
+    await sendAndConfirmTransaction(connection, fund, [payer]);
+    const reg = new Transaction().add(registerIx(kp.publicKey, Date.now() + i));
+    await sendAndConfirmTransaction(connection, reg, [kp]);
+    players.push(kp);
+  }
+  return players;
+}
+
+// Untimed: first activation of each level, so the rounds hit existing queues
+async function seedQueues(seeds, levels, admin, treasury) {
+  for (const [i, level] of levels.entries()) {
+    const kp = seeds[i];
+    const tx = new Transaction().add(
+      activationIx(kp.publicKey, level, Date.now() + level, admin, treasury, null, null, false)
+    );
+    await sendAndConfirmTransaction(connection, tx, [kp]);
+  }
+}
+
+async function runRound(label, players, seeds, levels, admin, treasury, legacyWritable) {
+  // Resolved before the clock starts: owner = seed player, refs = its uplines
+  const txs = [];
+  for (const [i, level] of levels.entries()) {
+    const owner = seeds[i].publicKey;
+    const refs = await ownerRefs(owner, level, admin);
+    txs.push(
+      new Transaction().add(
+        activationIx(players[i].publicKey, level, Date.now() + level, admin, treasury, owner, refs, legacyWritable)
+      )
+    );
+  }
+
+  const started = Date.now();
+  const results = await Promise.allSettled(
+    txs.map((tx, i) => sendAndConfirmTransaction(connection, tx, [players[i]], { skipPreflight: true }))
+  );
+  const elapsedMs = Date.now() - started;
+
+  const sigs = results.filter((r) => r.status === "fulfilled").map((r) => r.value);
+  const failed = results.filter((r) => r.status === "rejected");
+  const slots = [];
+  for (const sig of sigs) {
+    const tx = await connection.getTransaction(sig, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    slots.push(tx.slot);
+  }
+  const perSlot = slots.reduce((m, s) => m.set(s, (m.get(s) || 0) + 1), new Map());
+
+  console.log(`--- ${label} ---`);
+  console.log("levels:        ", levels.join(","));
+  console.log("txs ok/failed: ", `${sigs.length}/${failed.length}`);
+  for (const f of failed) console.log("  failed:", f.reason?.message || f.reason);
+  console.log("elapsed (ms):  ", elapsedMs);
+  console.log("distinct slots:", perSlot.size);
+  console.log("max txs/slot:  ", perSlot.size ? Math.max(...perSlot.values()) : 0);
+  console.log("tps (approx):  ", ((sigs.length * 1000) / elapsedMs).toFixed(2));
+}
+
+(async () => {
+  const cfg = await connection.getAccountInfo(configPda);
+  if (!cfg) throw new Error("ConfigV3 not found — run init_config_v3.js against the local validator");
+  const admin = new PublicKey(cfg.data.slice(8, 40));
+  const treasury = new PublicKey(cfg.data.slice(40, 72));
+
+  console.log("RPC:", RPC_URL);
+  console.log("Program:", PROGRAM_ID.toBase58());
+
+  const levels = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+  const seeds = await fundAndRegister(16);
+  const players = await fundAndRegister(16);
+  await seedQueues(seeds, levels, admin, treasury);
+
+  await runRound(
+    "legacy metas (config/admin/owner player writable)",
+    players.slice(0, 8),
+    seeds.slice(0, 8),
+    levels.slice(0, 8),
+    admin,
+    treasury,
+    true
+  );
+  await runRound(
+    "read-only metas (config/admin/owner player read-only)",
+    players.slice(8, 16),
+    seeds.slice(8, 16),
+    levels.slice(8, 16),
+    admin,
+    treasury,
+    false
+  );
+})().catch((e) => {
+  console.error(e);
+  process.exit(1);
+});
@@ -0,0 +1,76 @@
+// scripts/fund_rent_reserve.js
+// Tops up the rent reserve PDA (system-owned, no data) from the treasury wallet.
+// activate_level_v3 refunds bookkeeping rent (owner LevelState, queue pages) from
+// it when the treasury share of a single activation is too small.
+//
+// Usage:
+//   RPC_URL=<url> PROGRAM_ID=<id> node scripts/fund_rent_reserve.js <sol> [treasury_keypair.json]
+
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const anchor = require("@coral-xyz/anchor");
+const { PublicKey, SystemProgram, Transaction, LAMPORTS_PER_SOL } = anchor.web3;
+
+// --------------------
+// RPC
+// --------------------
+const RPC_URL = process.env.RPC_URL || "https://api.devnet.solana.com";
+const connection = new anchor.web3.Connection(RPC_URL, { commitment: "confirmed" });
+
+// --------------------
+// ARGS
+// --------------------
+const SOL = Number(process.argv[2]);
+if (!Number.isFinite(SOL) || SOL <= 0) {
+  console.error("Usage: node scripts/fund_rent_reserve.js <sol> [treasury_keypair.json]");
+  process.exit(1);
+}
+
+// --------------------
+// TREASURY (funds the reserve)
+// --------------------
+const KEYPAIR_PATH =
+  process.argv[3] || path.join(os.homedir(), ".config/solana/id.json");
+const secretKey = JSON.parse(fs.readFileSync(KEYPAIR_PATH, "utf8"));
+const treasuryKeypair = anchor.web3.Keypair.fromSecretKey(Uint8Array.from(secretKey));
+const wallet = new anchor.Wallet(treasuryKeypair);
+const provider = new anchor.AnchorProvider(connection, wallet, { commitment: "confirmed" });
+anchor.setProvider(provider);
+
+// --------------------
+// PROGRAM (ON-CHAIN)
+// --------------------
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+
+// --------------------
+// PDA
+// --------------------
+const [reservePda] = PublicKey.findProgramAddressSync(
+  [Buffer.from("rent_reserve_v1")],
+  PROGRAM_ID
+);
+
+// --------------------
+// TX
+// --------------------
+(async () => {
+  const lamports = Math.round(SOL * LAMPORTS_PER_SOL);
+  const before = await connection.getBalance(reservePda);
+
+  const tx = new Transaction().add(
+    SystemProgram.transfer({
+      fromPubkey: wallet.publicKey,
+      toPubkey: reservePda,
+      lamports,
+    })
+  );
+  const sig = await provider.sendAndConfirm(tx);
+
+  const after = await connection.getBalance(reservePda);
+  console.log("Rent reserve:", reservePda.toBase58());
+  console.log(`balance: ${before / LAMPORTS_PER_SOL} -> ${after / LAMPORTS_PER_SOL} SOL`);
+  console.log("✅ funded:", sig);
+})();
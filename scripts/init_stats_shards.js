@@ -0,0 +1,79 @@
+// scripts/init_stats_shards.js
+// ONE TIME USE (idempotent) — creates StatsShard PDAs 0..15 (admin signs)
+//
+// Usage:
+//   RPC_URL=<url> PROGRAM_ID=<id> node scripts/init_stats_shards.js [admin_keypair.json]
+
+const fs = require("fs");
+const anchor = require("@coral-xyz/anchor");
+const { PublicKey, SystemProgram, Transaction, TransactionInstruction } = anchor.web3;
+
+// --------------------
+// RPC
+// --------------------
+const RPC_URL = process.env.RPC_URL || "https://api.devnet.solana.com";
+const connection = new anchor.web3.Connection(RPC_URL, { commitment: "confirmed" });
+
+// --------------------
+// ADMIN (must equal ConfigV3.admin)
+// --------------------
+const KEYPAIR_PATH = process.argv[2] || "wallets/mainnet-final/solana_game_mainnet.json";
+const secretKey = JSON.parse(fs.readFileSync(KEYPAIR_PATH, "utf8"));
+const adminKeypair = anchor.web3.Keypair.fromSecretKey(Uint8Array.from(secretKey));
+const wallet = new anchor.Wallet(adminKeypair);
+const provider = new anchor.AnchorProvider(connection, wallet, { commitment: "confirmed" });
+anchor.setProvider(provider);
+
+// --------------------
+// PROGRAM (ON-CHAIN)
+// --------------------
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+
+const STATS_SHARD_COUNT = 16;
+
+// --------------------
+// PDA
+// --------------------
+const [configPda] = PublicKey.findProgramAddressSync(
+  [Buffer.from("config_v3_new")],
+  PROGRAM_ID
+);
+const shardPda = (shard) =>
+  PublicKey.findProgramAddressSync(
+    [Buffer.from("stats_shard_v1"), Buffer.from([shard])],
+    PROGRAM_ID
+  )[0];
+
+// --------------------
+// DISCRIMINATOR
+// --------------------
+const DISCR = Buffer.from([160, 37, 210, 10, 247, 58, 47, 9]);
+
+// --------------------
+// TX
+// --------------------
+(async () => {
+  for (let shard = 0; shard < STATS_SHARD_COUNT; shard++) {
+    const pda = shardPda(shard);
+    if (await connection.getAccountInfo(pda)) {
+      console.log(`= shard ${shard} exists: ${pda.toBase58()}`);
+      continue;
+    }
+
+    const ix = new TransactionInstruction({
+      programId: PROGRAM_ID,
+      keys: [
+        { pubkey: pda, isWritable: true, isSigner: false },
+        { pubkey: configPda, isWritable: false, isSigner: false },
+        { pubkey: wallet.publicKey, isWritable: true, isSigner: true },
+        { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+      ],
+      data: Buffer.concat([DISCR, Buffer.from([shard])]),
+    });
+
+    const sig = await provider.sendAndConfirm(new Transaction().add(ix));
+    console.log(`✅ shard ${shard} initialized: ${pda.toBase58()} (${sig})`);
+  }
+})();
@@ -0,0 +1,319 @@
+// scripts/measure-batch-cu.js
+// LOCAL VALIDATOR ONLY — measures activate_levels_batch compute units for batches of
+// 1..4 levels with simulateTransaction (unitsConsumed). Results go to
+// docs/BATCH_ACTIVATION_CU.md.
+//
+// Prereqs (min_entry_delay = 0, lamport mode, default build without event-cpi):
+//   solana-test-validator --bpf-program <PROGRAM_ID> target/deploy/solana_game.so --reset
+//   node scripts/init_config_v3.js   (pointed at http://127.0.0.1:8899, same PROGRAM_ID)
+//   node scripts/init_stats_shards.js (same RPC / PROGRAM_ID)
+//
+// Usage:
+//   PROGRAM_ID=<id> node scripts/measure-batch-cu.js [keypair.json]
+//
+// Setup: one seed player per level 1..4 makes the first activation, so every batch
+// group is an existing-queue activation (owner payout + enqueue, the expensive path).
+// Each batch is simulated from the same fresh player, so nothing is committed and
+// every size sees the same state. 3-4 levels only fit in a v0 tx with a lookup table.
+
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const crypto = require("crypto");
+const {
+  AddressLookupTableProgram,
+  ComputeBudgetProgram,
+  Connection,
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  TransactionMessage,
+  VersionedTransaction,
+  sendAndConfirmTransaction,
+} = require("@solana/web3.js");
+
+// --------------------
+// CONFIG
+// --------------------
+const RPC_URL = process.env.RPC_URL || "http://127.0.0.1:8899";
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+const KEYPAIR_PATH =
+  process.argv[2] || path.join(os.homedir(), ".config/solana/id.json");
+const MAX_BATCH_LEVELS = 4;
+
+const connection = new Connection(RPC_URL, { commitment: "confirmed" });
+const payer = Keypair.fromSecretKey(
+  Uint8Array.from(JSON.parse(fs.readFileSync(KEYPAIR_PATH, "utf8")))
+);
+
+// --------------------
+// ENCODING / PDA
+// --------------------
+const discr = (name) =>
+  crypto.createHash("sha256").update(`global:${name}`).digest().subarray(0, 8);
+const DISCR_REGISTER = discr("register_player");
+const DISCR_ACTIVATE = discr("activate_level_v3");
+const DISCR_BATCH = discr("activate_levels_batch");
+
+const u64 = (n) => {
+  const b = Buffer.alloc(8);
+  b.writeBigUInt64LE(BigInt(n));
+  return b;
+};
+const u32 = (n) => {
+  const b = Buffer.alloc(4);
+  b.writeUInt32LE(n);
+  return b;
+};
+const U64_MAX = (1n << 64n) - 1n;
+
+const pda = (seeds) => PublicKey.findProgramAddressSync(seeds, PROGRAM_ID)[0];
+const configPda = pda([Buffer.from("config_v3_new")]);
+const statsPda = pda([Buffer.from("global_stats_v1")]);
+const rentReservePda = pda([Buffer.from("rent_reserve_v1")]);
+const treasuryVaultPda = pda([Buffer.from("treasury_vault_v1")]);
+const playerPda = (authority) => pda([Buffer.from("player"), authority.toBuffer()]);
+const authorityLinkPda = (wallet) => pda([Buffer.from("authority_link_v1"), wallet.toBuffer()]);
+const levelStatePda = (player, level) =>
+  pda([Buffer.from("lvl"), player.toBuffer(), Buffer.from([level])]);
+const levelPoolPda = (level) =>
+  pda([Buffer.from("level_pool_v1"), configPda.toBuffer(), Buffer.from([level])]);
+const queuePagePda = (pool, index) =>
+  pda([Buffer.from("queue_page_v1"), pool.toBuffer(), u32(index)]);
+const statsShardPda = (authority) => {
+  const shard = crypto.createHash("sha256").update(authority.toBuffer()).digest()[0] % 16;
+  return pda([Buffer.from("stats_shard_v1"), Buffer.from([shard])]);
+};
+
+// Player: disc(8) authority(32) bump(1) created_at(8) games_played(8) upline1..3(96) next_nonce(8)
+const PLAYER_UPLINES = [57, 89, 121];
+const PLAYER_NEXT_NONCE = 153;
+
+async function nextNonce(authority) {
+  const acc = await connection.getAccountInfo(playerPda(authority));
+  return acc.data.length >= PLAYER_NEXT_NONCE + 8 ? acc.data.readBigUInt64LE(PLAYER_NEXT_NONCE) : 0n;
+}
+
+// --------------------
+// IX BUILDERS
+// --------------------
+function registerIx(authority) {
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: playerPda(authority), isWritable: true, isSigner: false },
+      { pubkey: authority, isWritable: true, isSigner: true },
+      { pubkey: PROGRAM_ID, isWritable: false, isSigner: false }, // tx_guard: None
+      { pubkey: configPda, isWritable: false, isSigner: false },
+      { pubkey: statsPda, isWritable: false, isSigner: false },
+      { pubkey: statsShardPda(authority), isWritable: true, isSigner: false },
+      { pubkey: PROGRAM_ID, isWritable: false, isSigner: false }, // referrer_player: None
+      { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+      { pubkey: authorityLinkPda(authority), isWritable: false, isSigner: false },
+    ],
+    data: Buffer.concat([DISCR_REGISTER, Buffer.from([0]), u64(0)]),
+  });
+}
+
+// Uplines are Player PDAs or seed wallets; paid only with an activated LevelState for
+// the level (mirrors open_level_wallet), otherwise admin.
+async function resolveRef(upline, level, admin) {
+  const none = { wallet: admin, levelState: null };
+  if (upline.equals(admin) || upline.equals(PublicKey.default)) return none;
+  for (const player of [upline, playerPda(upline)]) {
+    const ls = levelStatePda(player, level);
+    const acc = await connection.getAccountInfo(ls);
+    if (!acc || !acc.owner.equals(PROGRAM_ID) || acc.data.length < 82) continue;
+    if (acc.data[72] === level && acc.data.readBigInt64LE(74) > 0n) {
+      return { wallet: new PublicKey(acc.data.slice(40, 72)), levelState: ls };
+    }
+  }
+  return none;
+}
+
+// One activate_level_v3 account list (24 accounts). `owner` = head of queue page 0
+// (null on a first activation: owner/ref slots are not used).
+async function activationKeys(cfg, authority, level, owner) {
+  const player = playerPda(authority);
+  const pool = levelPoolPda(level);
+  const page0 = queuePagePda(pool, 0);
+  const ownerAuthority = owner || authority;
+  const ownerPlayer = playerPda(ownerAuthority);
+
+  let refs = [null, null, null].map(() => ({ wallet: cfg.admin, levelState: null }));
+  if (owner) {
+    const acc = await connection.getAccountInfo(ownerPlayer);
+    const uplines = PLAYER_UPLINES.map((o) => new PublicKey(acc.data.slice(o, o + 32)));
+    refs = await Promise.all(uplines.map((u) => resolveRef(u, level, cfg.admin)));
+  }
+
+  return [
+    { pubkey: player, isSigner: false, isWritable: true },
+    { pubkey: levelStatePda(player, level), isSigner: false, isWritable: true },
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // tx_guard: None
+    { pubkey: authority, isSigner: true, isWritable: true },
+    { pubkey: configPda, isSigner: false, isWritable: false },
+    { pubkey: pool, isSigner: false, isWritable: true },
+    { pubkey: cfg.admin, isSigner: false, isWritable: false },
+    { pubkey: cfg.treasuryAccount, isSigner: false, isWritable: true },
+    ...refs.map((r) => ({ pubkey: r.wallet, isSigner: false, isWritable: true })),
+    { pubkey: page0, isSigner: false, isWritable: true }, // tail_page
+    { pubkey: owner ? queuePagePda(pool, 1) : page0, isSigner: false, isWritable: true }, // new_page
+    { pubkey: page0, isSigner: false, isWritable: true }, // head_page
+    { pubkey: ownerPlayer, isSigner: false, isWritable: false },
+    { pubkey: levelStatePda(ownerPlayer, level), isSigner: false, isWritable: true },
+    { pubkey: ownerAuthority, isSigner: false, isWritable: true },
+    ...refs.map((r) => ({ pubkey: r.levelState || PROGRAM_ID, isSigner: false, isWritable: false })),
+    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: rentReservePda, isSigner: false, isWritable: true },
+    {
+      pubkey: level > 1 ? levelStatePda(player, level - 1) : PROGRAM_ID,
+      isSigner: false,
+      isWritable: false,
+    },
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // price_feed: None
+  ];
+}
+
+// --------------------
+// SETUP
+// --------------------
+async function fundAndRegister(count) {
+  const players = [];
+  for (let i = 0; i < count; i++) {
+    const kp = Keypair.generate();
+    const fund = new Transaction().add(
+      SystemProgram.transfer({
+        fromPubkey: payer.publicKey,
+        toPubkey: kp.publicKey,
+        lamports: 10_000_000_000,
+      })
+    );
+    await sendAndConfirmTransaction(connection, fund, [payer]);
+    await sendAndConfirmTransaction(connection, new Transaction().add(registerIx(kp.publicKey)), [kp]);
+    players.push(kp);
+  }
+  return players;
+}
+
+async function seedQueue(cfg, seed, level) {
+  const nonce = await nextNonce(seed.publicKey);
+  const ix = new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: await activationKeys(cfg, seed.publicKey, level, null),
+    data: Buffer.concat([DISCR_ACTIVATE, Buffer.from([level]), u64(U64_MAX), u64(nonce)]),
+  });
+  await sendAndConfirmTransaction(connection, new Transaction().add(ix), [seed]);
+}
+
+async function createLookupTable(addresses) {
+  const [createIx, table] = AddressLookupTableProgram.createLookupTable({
+    authority: payer.publicKey,
+    payer: payer.publicKey,
+    recentSlot: await connection.getSlot("finalized"),
+  });
+  await sendAndConfirmTransaction(connection, new Transaction().add(createIx), [payer]);
+  for (let i = 0; i < addresses.length; i += 20) {
+    const extendIx = AddressLookupTableProgram.extendLookupTable({
+      lookupTable: table,
+      authority: payer.publicKey,
+      payer: payer.publicKey,
+      addresses: addresses.slice(i, i + 20),
+    });
+    await sendAndConfirmTransaction(connection, new Transaction().add(extendIx), [payer]);
+  }
+  // Extended addresses become usable one slot later
+  const slot = await connection.getSlot();
+  while ((await connection.getSlot()) <= slot) await new Promise((r) => setTimeout(r, 200));
+  return (await connection.getAddressLookupTable(table)).value;
+}
+
+// --------------------
+// MEASURE
+// --------------------
+async function simulateBatch(cfg, player, levels, seeds) {
+  const nonce = await nextNonce(player.publicKey);
+  const groups = [];
+  for (const [i, level] of levels.entries()) {
+    groups.push(...(await activationKeys(cfg, player.publicKey, level, seeds[i].publicKey)));
+  }
+  const batchIx = new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: playerPda(player.publicKey), isSigner: false, isWritable: false },
+      { pubkey: player.publicKey, isSigner: true, isWritable: true },
+      { pubkey: configPda, isSigner: false, isWritable: false },
+      ...groups,
+    ],
+    // levels: Vec<u8>, max_prices: Vec<u64> (uncapped: only CU is measured), nonce
+    data: Buffer.concat([
+      DISCR_BATCH,
+      u32(levels.length),
+      Buffer.from(levels),
+      u32(levels.length),
+      ...levels.map(() => u64(U64_MAX)),
+      u64(nonce),
+    ]),
+  });
+
+  const lookupAddresses = [...new Set(batchIx.keys.map((k) => k.pubkey.toBase58()))]
+    .filter((k) => k !== player.publicKey.toBase58())
+    .map((k) => new PublicKey(k));
+  const table = await createLookupTable(lookupAddresses);
+
+  const message = new TransactionMessage({
+    payerKey: player.publicKey,
+    recentBlockhash: (await connection.getLatestBlockhash()).blockhash,
+    instructions: [ComputeBudgetProgram.setComputeUnitLimit({ units: 1_400_000 }), batchIx],
+  }).compileToV0Message([table]);
+  const tx = new VersionedTransaction(message);
+
+  const sim = await connection.simulateTransaction(tx, {
+    sigVerify: false,
+    replaceRecentBlockhash: true,
+  });
+  return {
+    levels: levels.length,
+    accounts: lookupAddresses.length + 1,
+    txBytes: tx.serialize().length,
+    unitsConsumed: sim.value.unitsConsumed,
+    err: sim.value.err,
+    logs: sim.value.err ? (sim.value.logs || []).slice(-8) : [],
+  };
+}
+
+(async () => {
+  const acc = await connection.getAccountInfo(configPda);
+  if (!acc) throw new Error("ConfigV3 not found — run init_config_v3.js against the local validator");
+  const admin = new PublicKey(acc.data.slice(8, 40));
+  const treasury = new PublicKey(acc.data.slice(40, 72));
+  const useTreasuryVault = acc.data.length > 101 && acc.data[101] === 1;
+  const cfg = { admin, treasuryAccount: useTreasuryVault ? treasuryVaultPda : treasury };
+
+  console.log("RPC:", RPC_URL);
+  console.log("Program:", PROGRAM_ID.toBase58());
+
+  const levels = Array.from({ length: MAX_BATCH_LEVELS }, (_, i) => i + 1);
+  const seeds = await fundAndRegister(levels.length);
+  for (const [i, level] of levels.entries()) await seedQueue(cfg, seeds[i], level);
+  const [player] = await fundAndRegister(1);
+
+  const rows = [];
+  for (let n = 1; n <= levels.length; n++) {
+    rows.push(await simulateBatch(cfg, player, levels.slice(0, n), seeds));
+  }
+  for (const r of rows) {
+    console.log(
+      `levels=${r.levels} accounts=${r.accounts} tx_bytes=${r.txBytes} ` +
+        `unitsConsumed=${r.unitsConsumed} err=${JSON.stringify(r.err)}`
+    );
+    for (const l of r.logs) console.log("   ", l);
+  }
+})().catch((e) => {
+  console.error(e);
+  process.exit(1);
+});
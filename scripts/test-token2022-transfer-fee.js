@@ -0,0 +1,393 @@
+// scripts/test-token2022-transfer-fee.js
+// LOCAL VALIDATOR ONLY — end-to-end check of activate_level_token with a
+// Token-2022 mint that charges a transfer fee. Switches ConfigV3 to token mode.
+//
+// Prereqs:
+//   solana-test-validator --bpf-program <PROGRAM_ID> target/deploy/solana_game.so --reset
+//   node scripts/init_config_v3.js   (pointed at http://127.0.0.1:8899, same PROGRAM_ID, same admin keypair)
+//   node scripts/init_stats_shards.js (same RPC / PROGRAM_ID)
+//
+// Usage:
+//   PROGRAM_ID=<id> node scripts/test-token2022-transfer-fee.js [admin_keypair.json] [level]
+//
+// Checks:
+//   1. First activation of `level` (100% -> treasury): treasury ATA nets price - fee(price).
+//   2. Second activation: the queue owner nets exactly 60% of the price, the activator
+//      pays exactly the price, and every transfer fee comes out of the treasury share.
+
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const crypto = require("crypto");
+const assert = require("assert");
+const {
+  Connection,
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  sendAndConfirmTransaction,
+} = require("@solana/web3.js");
+
+// --------------------
+// CONFIG
+// --------------------
+const RPC_URL = process.env.RPC_URL || "http://127.0.0.1:8899";
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+const KEYPAIR_PATH =
+  process.argv[2] || path.join(os.homedir(), ".config/solana/id.json");
+const LEVEL = Number(process.argv[3] || 1);
+
+const TOKEN_2022_PROGRAM_ID = new PublicKey("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+const ASSOCIATED_TOKEN_PROGRAM_ID = new PublicKey("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+const DECIMALS = 6;
+const FEE_BPS = 100; // 1%
+const MAX_FEE = 5_000_000n; // 5 tokens
+const TOKEN_LEVEL_PRICES = Array.from({ length: 16 }, (_, i) => BigInt(i + 1) * 10_000_000n);
+
+const connection = new Connection(RPC_URL, { commitment: "confirmed" });
+const admin = Keypair.fromSecretKey(
+  Uint8Array.from(JSON.parse(fs.readFileSync(KEYPAIR_PATH, "utf8")))
+);
+
+// --------------------
+// DISCRIMINATORS
+// --------------------
+const discr = (name) => crypto.createHash("sha256").update(`global:${name}`).digest().subarray(0, 8);
+const DISCR_REGISTER = discr("register_player");
+const DISCR_UPDATE_CONFIG = discr("update_config_v3");
+const DISCR_ACTIVATE_TOKEN = discr("activate_level_token");
+
+// --------------------
+// PDA
+// --------------------
+const pda = (seeds, program = PROGRAM_ID) => PublicKey.findProgramAddressSync(seeds, program)[0];
+const u64 = (n) => {
+  const b = Buffer.alloc(8);
+  b.writeBigUInt64LE(BigInt(n));
+  return b;
+};
+const u32 = (n) => {
+  const b = Buffer.alloc(4);
+  b.writeUInt32LE(n);
+  return b;
+};
+const u16 = (n) => {
+  const b = Buffer.alloc(2);
+  b.writeUInt16LE(n);
+  return b;
+};
+
+const configPda = pda([Buffer.from("config_v3_new")]);
+const statsPda = pda([Buffer.from("global_stats_v1")]);
+const reservePda = pda([Buffer.from("rent_reserve_v1")]);
+const playerPda = (authority) => pda([Buffer.from("player"), authority.toBuffer()]);
+const authorityLinkPda = (wallet) => pda([Buffer.from("authority_link_v1"), wallet.toBuffer()]);
+const levelStatePda = (player, level) =>
+  pda([Buffer.from("lvl"), player.toBuffer(), Buffer.from([level])]);
+const levelPoolPda = (level) =>
+  pda([Buffer.from("level_pool_v1"), configPda.toBuffer(), Buffer.from([level])]);
+const queuePagePda = (pool, index) =>
+  pda([Buffer.from("queue_page_v1"), pool.toBuffer(), u32(index)]);
+const txGuardActivatePda = (player, level, nonce) =>
+  pda([Buffer.from("tx"), player.toBuffer(), Buffer.from([1]), Buffer.from([level]), u64(nonce)]);
+const txGuardRegisterPda = (authority, nonce) =>
+  pda([Buffer.from("tx"), Buffer.from("register"), authority.toBuffer(), u64(nonce)]);
+const statsShardPda = (authority) => {
+  const shard = crypto.createHash("sha256").update(authority.toBuffer()).digest()[0] % 16;
+  return pda([Buffer.from("stats_shard_v1"), Buffer.from([shard])]);
+};
+const ata = (owner, mint) =>
+  pda([owner.toBuffer(), TOKEN_2022_PROGRAM_ID.toBuffer(), mint.toBuffer()], ASSOCIATED_TOKEN_PROGRAM_ID);
+
+// --------------------
+// TRANSFER FEE MATH (mirrors spl-token-2022 TransferFee)
+// --------------------
+const ceilDiv = (a, b) => (a + b - 1n) / b;
+const feeOf = (amount) =>
+  amount === 0n ? 0n : [ceilDiv(amount * BigInt(FEE_BPS), 10_000n), MAX_FEE].reduce((a, b) => (a < b ? a : b));
+const preFeeAmount = (net) => {
+  if (net === 0n) return 0n;
+  const raw = ceilDiv(net * 10_000n, 10_000n - BigInt(FEE_BPS));
+  return raw - net >= MAX_FEE ? net + MAX_FEE : raw;
+};
+
+// --------------------
+// TOKEN-2022 IX BUILDERS
+// --------------------
+// Mint(82) padded to Account::LEN(165) + account type(1) + TLV header(4) + TransferFeeConfig(108)
+const MINT_WITH_FEE_SPACE = 165 + 1 + 4 + 108;
+
+function initTransferFeeConfigIx(mint, authority) {
+  return new TransactionInstruction({
+    programId: TOKEN_2022_PROGRAM_ID,
+    keys: [{ pubkey: mint, isSigner: false, isWritable: true }],
+    data: Buffer.concat([
+      Buffer.from([26, 0]), // TransferFeeExtension::InitializeTransferFeeConfig
+      Buffer.from([1]),
+      authority.toBuffer(),
+      Buffer.from([1]),
+      authority.toBuffer(),
+      u16(FEE_BPS),
+      u64(MAX_FEE),
+    ]),
+  });
+}
+
+function initMint2Ix(mint, authority) {
+  return new TransactionInstruction({
+    programId: TOKEN_2022_PROGRAM_ID,
+    keys: [{ pubkey: mint, isSigner: false, isWritable: true }],
+    data: Buffer.concat([Buffer.from([20, DECIMALS]), authority.toBuffer(), Buffer.from([0])]),
+  });
+}
+
+function createAtaIx(payer, owner, mint) {
+  return new TransactionInstruction({
+    programId: ASSOCIATED_TOKEN_PROGRAM_ID,
+    keys: [
+      { pubkey: payer, isSigner: true, isWritable: true },
+      { pubkey: ata(owner, mint), isSigner: false, isWritable: true },
+      { pubkey: owner, isSigner: false, isWritable: false },
+      { pubkey: mint, isSigner: false, isWritable: false },
+      { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+      { pubkey: TOKEN_2022_PROGRAM_ID, isSigner: false, isWritable: false },
+    ],
+    data: Buffer.from([1]), // CreateIdempotent
+  });
+}
+
+function mintToIx(mint, dest, authority, amount) {
+  return new TransactionInstruction({
+    programId: TOKEN_2022_PROGRAM_ID,
+    keys: [
+      { pubkey: mint, isSigner: false, isWritable: true },
+      { pubkey: dest, isSigner: false, isWritable: true },
+      { pubkey: authority, isSigner: true, isWritable: false },
+    ],
+    data: Buffer.concat([Buffer.from([14]), u64(amount), Buffer.from([DECIMALS])]),
+  });
+}
+
+async function tokenBalance(account) {
+  const acc = await connection.getAccountInfo(account);
+  return acc ? acc.data.readBigUInt64LE(64) : 0n;
+}
+
+// --------------------
+// GAME IX BUILDERS
+// --------------------
+const NONE = Buffer.from([0]);
+const some = (buf) => Buffer.concat([Buffer.from([1]), buf]);
+
+// ConfigV3Update: only payment_mint + token_level_prices set
+function tokenModeIx(mint) {
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: configPda, isWritable: true, isSigner: false },
+      { pubkey: admin.publicKey, isWritable: false, isSigner: true },
+      // Currency change: every LevelPool, checked to have no queue yet
+      ...Array.from({ length: 16 }, (_, i) => ({ pubkey: levelPoolPda(i + 1), isWritable: false, isSigner: false })),
+    ],
+    data: Buffer.concat([
+      DISCR_UPDATE_CONFIG,
+      NONE, // tx_guard_close_delay
+      NONE, // legacy_tx_guard
+      NONE, // use_treasury_vault
+      NONE, // treasury_epoch_limit
+      NONE, // first_activation_delay_exempt
+      NONE, // require_sequential_levels
+      some(mint.toBuffer()),
+      some(Buffer.concat(TOKEN_LEVEL_PRICES.map(u64))),
+      NONE, // usd_price_feed
+      NONE, // usd_level_prices_cents
+      NONE, // usd_feed_max_age
+      NONE, // usd_feed_max_conf_bps
+      NONE, // usd_feed_id
+    ]),
+  });
+}
+
+function registerIx(authority, nonce) {
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: playerPda(authority), isWritable: true, isSigner: false },
+      { pubkey: authority, isWritable: true, isSigner: true },
+      { pubkey: txGuardRegisterPda(authority, nonce), isWritable: true, isSigner: false },
+      { pubkey: configPda, isWritable: false, isSigner: false },
+      { pubkey: statsPda, isWritable: false, isSigner: false },
+      { pubkey: statsShardPda(authority), isWritable: true, isSigner: false },
+      { pubkey: configPda, isWritable: false, isSigner: false }, // referrer placeholder
+      { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+      { pubkey: authorityLinkPda(authority), isWritable: false, isSigner: false },
+    ],
+    data: Buffer.concat([DISCR_REGISTER, NONE, u64(nonce)]),
+  });
+}
+
+// Refs are passed as None (ref*_level_state = PROGRAM_ID): their shares collapse into treasury.
+function activateTokenIx({ authority, owner, mint, treasury, nonce }) {
+  const player = playerPda(authority);
+  const pool = levelPoolPda(LEVEL);
+  const page0 = queuePagePda(pool, 0);
+  const ownerWallet = owner || admin.publicKey;
+  const ownerPlayer = owner ? playerPda(owner) : player;
+  const ownerLevelState = levelStatePda(ownerPlayer, LEVEL);
+
+  const keys = [
+    { pubkey: player, isSigner: false, isWritable: true },
+    { pubkey: levelStatePda(player, LEVEL), isSigner: false, isWritable: true },
+    { pubkey: txGuardActivatePda(player, LEVEL, nonce), isSigner: false, isWritable: true },
+    { pubkey: authority, isSigner: true, isWritable: true },
+    { pubkey: configPda, isSigner: false, isWritable: false },
+    { pubkey: pool, isSigner: false, isWritable: true },
+    { pubkey: admin.publicKey, isSigner: false, isWritable: false },
+    { pubkey: treasury, isSigner: false, isWritable: true },
+    { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+    { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+    { pubkey: admin.publicKey, isSigner: false, isWritable: true },
+    { pubkey: page0, isSigner: false, isWritable: true },
+    { pubkey: owner ? queuePagePda(pool, 1) : page0, isSigner: false, isWritable: true },
+    { pubkey: page0, isSigner: false, isWritable: true },
+    { pubkey: ownerPlayer, isSigner: false, isWritable: false },
+    { pubkey: ownerLevelState, isSigner: false, isWritable: true },
+    { pubkey: ownerWallet, isSigner: false, isWritable: true },
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // ref1_level_state: None
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // ref2_level_state: None
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // ref3_level_state: None
+    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: reservePda, isSigner: false, isWritable: true },
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // previous_level_state: None
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // price_feed: None
+
+    { pubkey: mint, isSigner: false, isWritable: false },
+    { pubkey: ata(authority, mint), isSigner: false, isWritable: true },
+    { pubkey: ata(ownerWallet, mint), isSigner: false, isWritable: true },
+    { pubkey: ata(admin.publicKey, mint), isSigner: false, isWritable: true },
+    { pubkey: ata(admin.publicKey, mint), isSigner: false, isWritable: true },
+    { pubkey: ata(admin.publicKey, mint), isSigner: false, isWritable: true },
+    { pubkey: ata(treasury, mint), isSigner: false, isWritable: true },
+    { pubkey: TOKEN_2022_PROGRAM_ID, isSigner: false, isWritable: false },
+    { pubkey: ASSOCIATED_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
+  ];
+
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys,
+    data: Buffer.concat([
+      DISCR_ACTIVATE_TOKEN,
+      Buffer.from([LEVEL]),
+      u64(TOKEN_LEVEL_PRICES[LEVEL - 1]),
+      u64(nonce),
+    ]),
+  });
+}
+
+// --------------------
+// TEST
+// --------------------
+async function fundedPlayer(mint, tokens) {
+  const kp = Keypair.generate();
+  const fund = new Transaction().add(
+    SystemProgram.transfer({
+      fromPubkey: admin.publicKey,
+      toPubkey: kp.publicKey,
+      lamports: 2_000_000_000,
+    }),
+    createAtaIx(admin.publicKey, kp.publicKey, mint),
+    mintToIx(mint, ata(kp.publicKey, mint), admin.publicKey, tokens)
+  );
+  await sendAndConfirmTransaction(connection, fund, [admin]);
+  await sendAndConfirmTransaction(connection, new Transaction().add(registerIx(kp.publicKey, Date.now())), [kp]);
+  return kp;
+}
+
+(async () => {
+  const cfg = await connection.getAccountInfo(configPda);
+  if (!cfg) throw new Error("ConfigV3 not found — run init_config_v3.js against the local validator");
+  const treasury = new PublicKey(cfg.data.slice(40, 72));
+  if (await connection.getAccountInfo(levelPoolPda(LEVEL))) {
+    throw new Error(`LevelPool ${LEVEL} already exists — restart the validator with --reset or pick another level`);
+  }
+
+  console.log("RPC:", RPC_URL);
+  console.log("Program:", PROGRAM_ID.toBase58());
+
+  // Token-2022 mint: 1% fee, 5-token cap
+  const mintKp = Keypair.generate();
+  const mint = mintKp.publicKey;
+  const rent = await connection.getMinimumBalanceForRentExemption(MINT_WITH_FEE_SPACE);
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(
+      SystemProgram.createAccount({
+        fromPubkey: admin.publicKey,
+        newAccountPubkey: mint,
+        lamports: rent,
+        space: MINT_WITH_FEE_SPACE,
+        programId: TOKEN_2022_PROGRAM_ID,
+      }),
+      initTransferFeeConfigIx(mint, admin.publicKey),
+      initMint2Ix(mint, admin.publicKey),
+      tokenModeIx(mint)
+    ),
+    [admin, mintKp]
+  );
+  console.log("Mint (Token-2022, 1% fee):", mint.toBase58());
+
+  const price = TOKEN_LEVEL_PRICES[LEVEL - 1];
+  const ownerKp = await fundedPlayer(mint, price * 2n);
+  const activatorKp = await fundedPlayer(mint, price * 2n);
+  const treasuryAta = ata(treasury, mint);
+
+  // 1) First activation: 100% -> treasury, the treasury bears its own fee
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(
+      activateTokenIx({ authority: ownerKp.publicKey, mint, treasury, nonce: Date.now() })
+    ),
+    [ownerKp]
+  );
+  const treasuryAfterFirst = await tokenBalance(treasuryAta);
+  assert.strictEqual(treasuryAfterFirst, price - feeOf(price), "first activation: treasury net");
+  console.log("✅ first activation: treasury net", treasuryAfterFirst.toString());
+
+  // 2) Second activation: owner nets exactly 60%, fees come out of the treasury share
+  const ownerBefore = await tokenBalance(ata(ownerKp.publicKey, mint));
+  const activatorBefore = await tokenBalance(ata(activatorKp.publicKey, mint));
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(
+      activateTokenIx({
+        authority: activatorKp.publicKey,
+        owner: ownerKp.publicKey,
+        mint,
+        treasury,
+        nonce: Date.now(),
+      })
+    ),
+    [activatorKp]
+  );
+
+  const ownerShare = (price * 60n) / 100n;
+  const ownerFee = preFeeAmount(ownerShare) - ownerShare;
+  const treasuryShare = price - ownerShare - ownerFee;
+
+  const ownerGot = (await tokenBalance(ata(ownerKp.publicKey, mint))) - ownerBefore;
+  const activatorPaid = activatorBefore - (await tokenBalance(ata(activatorKp.publicKey, mint)));
+  const treasuryGot = (await tokenBalance(treasuryAta)) - treasuryAfterFirst;
+
+  assert.strictEqual(ownerGot, ownerShare, "owner nets exactly 60%");
+  assert.strictEqual(activatorPaid, price, "activator pays exactly the price");
+  assert.strictEqual(treasuryGot, treasuryShare - feeOf(treasuryShare), "treasury bears all fees");
+  console.log("✅ second activation: owner", ownerGot.toString(), "treasury", treasuryGot.toString());
+})().catch((e) => {
+  console.error(e);
+  process.exit(1);
+});
@@ -0,0 +1,354 @@
+// scripts/test-usd-price-feed.js
+// LOCAL VALIDATOR ONLY — checks USD-priced levels (activate_level_usd) against a
+// locally constructed Pyth PriceUpdateV2 (SOL/USD) mock account. Switches ConfigV3 to USD mode.
+//
+// 1) Write the mock feed and start the validator with it:
+//   node scripts/test-usd-price-feed.js write-feed <usd_per_sol> /tmp/sol_usd.json [conf_usd]
+//   solana-test-validator --reset \
+//     --bpf-program <PROGRAM_ID> target/deploy/solana_game.so \
+//     --account <FEED_PUBKEY printed above> /tmp/sol_usd.json
+//   node scripts/init_config_v3.js   (pointed at http://127.0.0.1:8899, same PROGRAM_ID, same admin keypair)
+//   node scripts/init_stats_shards.js (same RPC / PROGRAM_ID)
+//
+// 2) Run the checks (usd_per_sol must match the feed):
+//   PROGRAM_ID=<id> node scripts/test-usd-price-feed.js run <feed_pubkey> <usd_per_sol> [admin_keypair.json] [level]
+//
+// Checks:
+//   - the activation charges ceil(cents * 1e7 * 10^-expo / price) lamports (LevelActivated.actual_price)
+//   - a quote more than max_slippage_bps below the executed price fails (PriceSlippageExceeded)
+//   - a feed older than usd_feed_max_age fails (StalePriceFeed)
+
+const fs = require("fs");
+const os = require("os");
+const path = require("path");
+const crypto = require("crypto");
+const assert = require("assert");
+const {
+  Connection,
+  Keypair,
+  PublicKey,
+  SystemProgram,
+  Transaction,
+  TransactionInstruction,
+  sendAndConfirmTransaction,
+} = require("@solana/web3.js");
+
+// --------------------
+// CONFIG
+// --------------------
+const RPC_URL = process.env.RPC_URL || "http://127.0.0.1:8899";
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+const PYTH_RECEIVER_ID = new PublicKey("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+const FEED_EXPO = -8;
+// $1, $3, $6, ... per level (cents)
+const USD_LEVEL_PRICES_CENTS = Array.from({ length: 16 }, (_, i) => 100 * (i * 3 || 1));
+const MAX_SLIPPAGE_BPS = 100;
+
+// --------------------
+// MOCK FEED (PriceUpdateV2)
+// --------------------
+// discriminator(8) write_authority(32) verification_level(1 = Full)
+// feed_id(32) price(8) conf(8) exponent(4) publish_time(8) prev_publish_time(8)
+// ema_price(8) ema_conf(8) posted_slot(8)
+const PRICE_UPDATE_V2_DISCRIMINATOR = Buffer.from([34, 241, 35, 99, 157, 126, 244, 205]);
+const PRICE_UPDATE_V2_SPACE = 133;
+// Pyth SOL/USD feed id (ConfigV3.usd_feed_id must match)
+const SOL_USD_FEED_ID = Buffer.from("ef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d", "hex");
+
+function mockFeedData(usdPerSol, confUsd, publishTime) {
+  const price = BigInt(Math.round(usdPerSol * 1e8));
+  const conf = BigInt(Math.round(confUsd * 1e8));
+  const b = Buffer.alloc(PRICE_UPDATE_V2_SPACE);
+  PRICE_UPDATE_V2_DISCRIMINATOR.copy(b, 0);
+  b[40] = 1; // VerificationLevel::Full
+  SOL_USD_FEED_ID.copy(b, 41); // feed_id
+  b.writeBigInt64LE(price, 73);
+  b.writeBigUInt64LE(conf, 81);
+  b.writeInt32LE(FEED_EXPO, 89);
+  b.writeBigInt64LE(BigInt(publishTime), 93);
+  b.writeBigInt64LE(BigInt(publishTime), 101);
+  b.writeBigInt64LE(price, 109);
+  b.writeBigUInt64LE(conf, 117);
+  return b;
+}
+
+function writeFeed(usdPerSol, out, confUsd) {
+  const pubkey = Keypair.generate().publicKey;
+  const data = mockFeedData(usdPerSol, confUsd, Math.floor(Date.now() / 1000));
+  const json = {
+    pubkey: pubkey.toBase58(),
+    account: {
+      lamports: 10_000_000,
+      data: [data.toString("base64"), "base64"],
+      owner: PYTH_RECEIVER_ID.toBase58(),
+      executable: false,
+      rentEpoch: 0,
+      space: PRICE_UPDATE_V2_SPACE,
+    },
+  };
+  fs.writeFileSync(out, JSON.stringify(json, null, 2));
+  console.log("Mock SOL/USD feed:", pubkey.toBase58(), `($${usdPerSol}, written to ${out})`);
+}
+
+// lamports = cents * 10^7 * 10^-expo / price, rounded up (usd_cents_to_lamports)
+function expectedLamports(cents, usdPerSol) {
+  const price = BigInt(Math.round(usdPerSol * 1e8));
+  const num = BigInt(cents) * 10_000_000n * 10n ** BigInt(-FEED_EXPO);
+  return (num + price - 1n) / price;
+}
+
+// --------------------
+// PDA / ENCODING
+// --------------------
+const pda = (seeds) => PublicKey.findProgramAddressSync(seeds, PROGRAM_ID)[0];
+const discr = (name) => crypto.createHash("sha256").update(name).digest().subarray(0, 8);
+const u64 = (n) => {
+  const b = Buffer.alloc(8);
+  b.writeBigUInt64LE(BigInt(n));
+  return b;
+};
+const u32 = (n) => {
+  const b = Buffer.alloc(4);
+  b.writeUInt32LE(n);
+  return b;
+};
+const u16 = (n) => {
+  const b = Buffer.alloc(2);
+  b.writeUInt16LE(n);
+  return b;
+};
+const NONE = Buffer.from([0]);
+const some = (buf) => Buffer.concat([Buffer.from([1]), buf]);
+
+const DISCR_REGISTER = discr("global:register_player");
+const DISCR_UPDATE_CONFIG = discr("global:update_config_v3");
+const DISCR_ACTIVATE_USD = discr("global:activate_level_usd");
+const EVENT_LEVEL_ACTIVATED = discr("event:LevelActivated");
+
+const configPda = pda([Buffer.from("config_v3_new")]);
+const statsPda = pda([Buffer.from("global_stats_v1")]);
+const reservePda = pda([Buffer.from("rent_reserve_v1")]);
+const playerPda = (authority) => pda([Buffer.from("player"), authority.toBuffer()]);
+const authorityLinkPda = (wallet) => pda([Buffer.from("authority_link_v1"), wallet.toBuffer()]);
+const levelStatePda = (player, level) =>
+  pda([Buffer.from("lvl"), player.toBuffer(), Buffer.from([level])]);
+const levelPoolPda = (level) =>
+  pda([Buffer.from("level_pool_v1"), configPda.toBuffer(), Buffer.from([level])]);
+const queuePagePda = (pool, index) =>
+  pda([Buffer.from("queue_page_v1"), pool.toBuffer(), u32(index)]);
+const txGuardActivatePda = (player, level, nonce) =>
+  pda([Buffer.from("tx"), player.toBuffer(), Buffer.from([1]), Buffer.from([level]), u64(nonce)]);
+const txGuardRegisterPda = (authority, nonce) =>
+  pda([Buffer.from("tx"), Buffer.from("register"), authority.toBuffer(), u64(nonce)]);
+const statsShardPda = (authority) => {
+  const shard = crypto.createHash("sha256").update(authority.toBuffer()).digest()[0] % 16;
+  return pda([Buffer.from("stats_shard_v1"), Buffer.from([shard])]);
+};
+
+// --------------------
+// IX BUILDERS
+// --------------------
+// ConfigV3Update: usd_price_feed / usd_level_prices_cents / usd_feed_max_age only
+function usdModeIx(admin, feed, maxAge) {
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: configPda, isWritable: true, isSigner: false },
+      { pubkey: admin, isWritable: false, isSigner: true },
+      // Currency change: every LevelPool, checked to have no queue yet
+      ...Array.from({ length: 16 }, (_, i) => ({ pubkey: levelPoolPda(i + 1), isWritable: false, isSigner: false })),
+    ],
+    data: Buffer.concat([
+      DISCR_UPDATE_CONFIG,
+      NONE, // tx_guard_close_delay
+      NONE, // legacy_tx_guard
+      NONE, // use_treasury_vault
+      NONE, // treasury_epoch_limit
+      NONE, // first_activation_delay_exempt
+      NONE, // require_sequential_levels
+      NONE, // payment_mint
+      NONE, // token_level_prices
+      some(feed.toBuffer()),
+      some(Buffer.concat(USD_LEVEL_PRICES_CENTS.map(u32))),
+      some(u32(maxAge)),
+      NONE, // usd_feed_max_conf_bps
+      some(SOL_USD_FEED_ID),
+    ]),
+  });
+}
+
+function registerIx(authority, nonce) {
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: playerPda(authority), isWritable: true, isSigner: false },
+      { pubkey: authority, isWritable: true, isSigner: true },
+      { pubkey: txGuardRegisterPda(authority, nonce), isWritable: true, isSigner: false },
+      { pubkey: configPda, isWritable: false, isSigner: false },
+      { pubkey: statsPda, isWritable: false, isSigner: false },
+      { pubkey: statsShardPda(authority), isWritable: true, isSigner: false },
+      { pubkey: configPda, isWritable: false, isSigner: false }, // referrer placeholder
+      { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+      { pubkey: authorityLinkPda(authority), isWritable: false, isSigner: false },
+    ],
+    data: Buffer.concat([DISCR_REGISTER, NONE, u64(nonce)]),
+  });
+}
+
+// First activation of `level` (owner/ref slots unused by the program)
+function activateUsdIx({ authority, admin, treasury, feed, level, quoted, nonce }) {
+  const player = playerPda(authority);
+  const pool = levelPoolPda(level);
+  const page0 = queuePagePda(pool, 0);
+
+  const keys = [
+    { pubkey: player, isSigner: false, isWritable: true },
+    { pubkey: levelStatePda(player, level), isSigner: false, isWritable: true },
+    { pubkey: txGuardActivatePda(player, level, nonce), isSigner: false, isWritable: true },
+    { pubkey: authority, isSigner: true, isWritable: true },
+    { pubkey: configPda, isSigner: false, isWritable: false },
+    { pubkey: pool, isSigner: false, isWritable: true },
+    { pubkey: admin, isSigner: false, isWritable: false },
+    { pubkey: treasury, isSigner: false, isWritable: true },
+    { pubkey: admin, isSigner: false, isWritable: true },
+    { pubkey: admin, isSigner: false, isWritable: true },
+    { pubkey: admin, isSigner: false, isWritable: true },
+    { pubkey: page0, isSigner: false, isWritable: true },
+    { pubkey: page0, isSigner: false, isWritable: true },
+    { pubkey: page0, isSigner: false, isWritable: true },
+    { pubkey: player, isSigner: false, isWritable: false },
+    { pubkey: levelStatePda(player, level), isSigner: false, isWritable: true },
+    { pubkey: authority, isSigner: false, isWritable: true },
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // ref1_level_state: None
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // ref2_level_state: None
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // ref3_level_state: None
+    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: reservePda, isSigner: false, isWritable: true },
+    { pubkey: PROGRAM_ID, isSigner: false, isWritable: false }, // previous_level_state: None
+    { pubkey: feed, isSigner: false, isWritable: false },
+  ];
+
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys,
+    data: Buffer.concat([
+      DISCR_ACTIVATE_USD,
+      Buffer.from([level]),
+      u64(quoted),
+      u64(nonce),
+      u16(MAX_SLIPPAGE_BPS),
+    ]),
+  });
+}
+
+function levelActivatedPrice(logs) {
+  for (const line of logs || []) {
+    if (!line.startsWith("Program data: ")) continue;
+    const data = Buffer.from(line.slice("Program data: ".length), "base64");
+    if (data.subarray(0, 8).equals(EVENT_LEVEL_ACTIVATED)) {
+      // owner(32) level(1) lamports(8) timestamp(8) actual_price(8)
+      return data.readBigUInt64LE(8 + 32 + 1 + 8 + 8);
+    }
+  }
+  return null;
+}
+
+async function expectFailure(connection, tx, signer, errorName) {
+  tx.feePayer = signer.publicKey;
+  tx.recentBlockhash = (await connection.getLatestBlockhash()).blockhash;
+  tx.sign(signer);
+  const sim = await connection.simulateTransaction(tx);
+  assert.ok(sim.value.err, `expected ${errorName}, transaction succeeded`);
+  assert.ok(
+    (sim.value.logs || []).some((l) => l.includes(errorName)),
+    `expected ${errorName}, got ${JSON.stringify(sim.value.err)}`
+  );
+  console.log(`✅ rejected with ${errorName}`);
+}
+
+// --------------------
+// RUN
+// --------------------
+async function run(feedArg, usdArg, keypairArg, levelArg) {
+  const connection = new Connection(RPC_URL, { commitment: "confirmed" });
+  const admin = Keypair.fromSecretKey(
+    Uint8Array.from(
+      JSON.parse(fs.readFileSync(keypairArg || path.join(os.homedir(), ".config/solana/id.json"), "utf8"))
+    )
+  );
+  const feed = new PublicKey(feedArg);
+  const usdPerSol = Number(usdArg);
+  const level = Number(levelArg || 1);
+
+  const cfg = await connection.getAccountInfo(configPda);
+  if (!cfg) throw new Error("ConfigV3 not found — run init_config_v3.js against the local validator");
+  const treasury = new PublicKey(cfg.data.slice(40, 72));
+  if (await connection.getAccountInfo(levelPoolPda(level))) {
+    throw new Error(`LevelPool ${level} already exists — restart the validator with --reset`);
+  }
+
+  // Generous max age: the mock publish_time is fixed when the feed file is written
+  await sendAndConfirmTransaction(connection, new Transaction().add(usdModeIx(admin.publicKey, feed, 3600)), [admin]);
+
+  const player = Keypair.generate();
+  await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(
+      SystemProgram.transfer({ fromPubkey: admin.publicKey, toPubkey: player.publicKey, lamports: 5_000_000_000 })
+    ),
+    [admin]
+  );
+  await sendAndConfirmTransaction(connection, new Transaction().add(registerIx(player.publicKey, Date.now())), [player]);
+
+  const expected = expectedLamports(USD_LEVEL_PRICES_CENTS[level - 1], usdPerSol);
+  const base = { authority: player.publicKey, admin: admin.publicKey, treasury, feed, level };
+
+  // Quote far below the executed price: beyond max_slippage_bps
+  await expectFailure(
+    connection,
+    new Transaction().add(activateUsdIx({ ...base, quoted: (expected * 9n) / 10n, nonce: Date.now() })),
+    player,
+    "PriceSlippageExceeded"
+  );
+
+  // Exact quote: charged lamports follow the feed
+  const sig = await sendAndConfirmTransaction(
+    connection,
+    new Transaction().add(activateUsdIx({ ...base, quoted: expected, nonce: Date.now() })),
+    [player]
+  );
+  const tx = await connection.getTransaction(sig, { commitment: "confirmed", maxSupportedTransactionVersion: 0 });
+  const charged = levelActivatedPrice(tx.meta.logMessages);
+  assert.strictEqual(charged, expected, "LevelActivated actual_price");
+  console.log(`✅ level ${level} ($${USD_LEVEL_PRICES_CENTS[level - 1] / 100}) charged ${charged} lamports`);
+
+  // Feed older than usd_feed_max_age
+  await sendAndConfirmTransaction(connection, new Transaction().add(usdModeIx(admin.publicKey, feed, 0)), [admin]);
+  await new Promise((r) => setTimeout(r, 2000));
+  await expectFailure(
+    connection,
+    new Transaction().add(activateUsdIx({ ...base, level: level + 1, quoted: expected, nonce: Date.now() })),
+    player,
+    "StalePriceFeed"
+  );
+  await sendAndConfirmTransaction(connection, new Transaction().add(usdModeIx(admin.publicKey, feed, 60)), [admin]);
+}
+
+(async () => {
+  const [cmd, ...args] = process.argv.slice(2);
+  switch (cmd) {
+    case "write-feed":
+      return writeFeed(Number(args[0]), args[1] || "sol_usd_feed.json", Number(args[2] || 0.05));
+    case "run":
+      return run(...args);
+    default:
+      console.error("Usage: node scripts/test-usd-price-feed.js write-feed|run ...");
+      process.exit(1);
+  }
+})().catch((e) => {
+  console.error(e);
+  process.exit(1);
+});
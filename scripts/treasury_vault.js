@@ -0,0 +1,238 @@
+// scripts/treasury_vault.js
+// Admin tooling for the program-owned TreasuryVault PDA (admin signs).
+//
+// Usage:
+//   node scripts/treasury_vault.js init
+//   node scripts/treasury_vault.js limit <sol_per_epoch>
+//   node scripts/treasury_vault.js enable | disable
+//   node scripts/treasury_vault.js withdraw <sol> <destination_wallet>
+//   node scripts/treasury_vault.js split <wallet:bps> [<wallet:bps> ...]   (bps sum = 10000, max 8)
+//   node scripts/treasury_vault.js distribute   (permissionless crank)
+//   node scripts/treasury_vault.js status
+//
+// Env: RPC_URL, PROGRAM_ID, ADMIN_KEYPAIR (default: wallets/mainnet-final/solana_game_mainnet.json)
+
+const fs = require("fs");
+const anchor = require("@coral-xyz/anchor");
+const { PublicKey, SystemProgram, Transaction, TransactionInstruction, LAMPORTS_PER_SOL } =
+  anchor.web3;
+
+// --------------------
+// RPC
+// --------------------
+const RPC_URL = process.env.RPC_URL || "https://api.devnet.solana.com";
+const connection = new anchor.web3.Connection(RPC_URL, { commitment: "confirmed" });
+
+// --------------------
+// ADMIN (must equal ConfigV3.admin)
+// --------------------
+const KEYPAIR_PATH =
+  process.env.ADMIN_KEYPAIR || "wallets/mainnet-final/solana_game_mainnet.json";
+const secretKey = JSON.parse(fs.readFileSync(KEYPAIR_PATH, "utf8"));
+const adminKeypair = anchor.web3.Keypair.fromSecretKey(Uint8Array.from(secretKey));
+const wallet = new anchor.Wallet(adminKeypair);
+const provider = new anchor.AnchorProvider(connection, wallet, { commitment: "confirmed" });
+anchor.setProvider(provider);
+
+// --------------------
+// PROGRAM (ON-CHAIN)
+// --------------------
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+
+// --------------------
+// PDA
+// --------------------
+const [configPda] = PublicKey.findProgramAddressSync(
+  [Buffer.from("config_v3_new")],
+  PROGRAM_ID
+);
+const [vaultPda] = PublicKey.findProgramAddressSync(
+  [Buffer.from("treasury_vault_v1")],
+  PROGRAM_ID
+);
+const [splitPda] = PublicKey.findProgramAddressSync(
+  [Buffer.from("treasury_split_v1")],
+  PROGRAM_ID
+);
+
+// --------------------
+// DISCRIMINATORS
+// --------------------
+const DISCR_INIT_VAULT = Buffer.from([136, 50, 206, 62, 144, 161, 93, 19]);
+const DISCR_WITHDRAW = Buffer.from([40, 63, 122, 158, 144, 216, 83, 96]);
+const DISCR_UPDATE_CONFIG = Buffer.from([180, 70, 69, 38, 208, 255, 25, 11]);
+const DISCR_SET_SPLIT = Buffer.from([253, 168, 55, 113, 109, 37, 177, 152]);
+const DISCR_DISTRIBUTE = Buffer.from([154, 96, 186, 42, 124, 189, 206, 47]);
+
+function u64(n) {
+  const b = Buffer.alloc(8);
+  b.writeBigUInt64LE(BigInt(n));
+  return b;
+}
+
+function u32(n) {
+  const b = Buffer.alloc(4);
+  b.writeUInt32LE(n);
+  return b;
+}
+
+function u16(n) {
+  const b = Buffer.alloc(2);
+  b.writeUInt16LE(n);
+  return b;
+}
+
+// TreasurySplit: bump(1) count(1) beneficiaries(32 * 8) bps(2 * 8) total_distributed(8)
+function readSplit(data) {
+  const count = data[9];
+  const out = [];
+  for (let i = 0; i < count; i++) {
+    out.push({
+      wallet: new PublicKey(data.slice(10 + 32 * i, 42 + 32 * i)),
+      bps: data.readUInt16LE(10 + 32 * 8 + 2 * i),
+    });
+  }
+  return out;
+}
+
+const NONE = Buffer.from([0]);
+const some = (buf) => Buffer.concat([Buffer.from([1]), buf]);
+
+// ConfigV3Update { tx_guard_close_delay, legacy_tx_guard, use_treasury_vault, treasury_epoch_limit,
+//                  first_activation_delay_exempt, require_sequential_levels, payment_mint,
+//                  token_level_prices, usd_price_feed, usd_level_prices_cents, usd_feed_max_age,
+//                  usd_feed_max_conf_bps, usd_feed_id }
+function updateConfigIx({ useVault = null, epochLimit = null }) {
+  const data = Buffer.concat([
+    DISCR_UPDATE_CONFIG,
+    NONE,
+    NONE,
+    useVault === null ? NONE : some(Buffer.from([useVault ? 1 : 0])),
+    epochLimit === null ? NONE : some(u64(epochLimit)),
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+    NONE,
+  ]);
+  return new TransactionInstruction({
+    programId: PROGRAM_ID,
+    keys: [
+      { pubkey: configPda, isWritable: true, isSigner: false },
+      { pubkey: wallet.publicKey, isWritable: false, isSigner: true },
+    ],
+    data,
+  });
+}
+
+const sol = (v) => BigInt(Math.round(Number(v) * LAMPORTS_PER_SOL));
+
+// --------------------
+// TX
+// --------------------
+(async () => {
+  const [cmd, a1, a2, ...rest] = process.argv.slice(2);
+  let ix;
+
+  switch (cmd) {
+    case "init":
+      ix = new TransactionInstruction({
+        programId: PROGRAM_ID,
+        keys: [
+          { pubkey: vaultPda, isWritable: true, isSigner: false },
+          { pubkey: configPda, isWritable: false, isSigner: false },
+          { pubkey: wallet.publicKey, isWritable: true, isSigner: true },
+          { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+        ],
+        data: DISCR_INIT_VAULT,
+      });
+      break;
+    case "limit":
+      ix = updateConfigIx({ epochLimit: sol(a1) });
+      break;
+    case "enable":
+    case "disable":
+      ix = updateConfigIx({ useVault: cmd === "enable" });
+      break;
+    case "withdraw": {
+      const destination = new PublicKey(a2);
+      ix = new TransactionInstruction({
+        programId: PROGRAM_ID,
+        keys: [
+          { pubkey: vaultPda, isWritable: true, isSigner: false },
+          { pubkey: configPda, isWritable: false, isSigner: false },
+          { pubkey: wallet.publicKey, isWritable: false, isSigner: true },
+          { pubkey: destination, isWritable: true, isSigner: false },
+        ],
+        data: Buffer.concat([DISCR_WITHDRAW, u64(sol(a1)), destination.toBuffer()]),
+      });
+      break;
+    }
+    case "split": {
+      const entries = [a1, a2, ...rest].filter(Boolean).map((e) => {
+        const [w, b] = e.split(":");
+        return { wallet: new PublicKey(w), bps: Number(b) };
+      });
+      ix = new TransactionInstruction({
+        programId: PROGRAM_ID,
+        keys: [
+          { pubkey: splitPda, isWritable: true, isSigner: false },
+          { pubkey: configPda, isWritable: false, isSigner: false },
+          { pubkey: wallet.publicKey, isWritable: true, isSigner: true },
+          { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+        ],
+        data: Buffer.concat([
+          DISCR_SET_SPLIT,
+          u32(entries.length),
+          ...entries.map((e) => e.wallet.toBuffer()),
+          u32(entries.length),
+          ...entries.map((e) => u16(e.bps)),
+        ]),
+      });
+      break;
+    }
+    case "distribute": {
+      const acc = await connection.getAccountInfo(splitPda);
+      if (!acc) throw new Error("TreasurySplit not set — run `split` first");
+      ix = new TransactionInstruction({
+        programId: PROGRAM_ID,
+        keys: [
+          { pubkey: splitPda, isWritable: true, isSigner: false },
+          { pubkey: vaultPda, isWritable: true, isSigner: false },
+          ...readSplit(acc.data).map((e) => ({ pubkey: e.wallet, isWritable: true, isSigner: false })),
+        ],
+        data: DISCR_DISTRIBUTE,
+      });
+      break;
+    }
+    case "status": {
+      const acc = await connection.getAccountInfo(vaultPda);
+      console.log("Vault:", vaultPda.toBase58());
+      if (!acc) return console.log("not initialized");
+      const d = acc.data;
+      console.log("balance (SOL):        ", acc.lamports / LAMPORTS_PER_SOL);
+      console.log("epoch:                ", d.readBigUInt64LE(9).toString());
+      console.log("withdrawn in epoch:   ", Number(d.readBigUInt64LE(17)) / LAMPORTS_PER_SOL);
+      console.log("total withdrawn (SOL):", Number(d.readBigUInt64LE(25)) / LAMPORTS_PER_SOL);
+      const split = await connection.getAccountInfo(splitPda);
+      if (split) {
+        for (const e of readSplit(split.data)) console.log(`split: ${e.wallet.toBase58()} ${e.bps} bps`);
+      }
+      return;
+    }
+    default:
+      console.error(
+        "Usage: node scripts/treasury_vault.js init|limit|enable|disable|withdraw|split|distribute|status"
+      );
+      process.exit(1);
+  }
+
+  const sig = await provider.sendAndConfirm(new Transaction().add(ix));
+  console.log(`✅ ${cmd}:`, sig);
+})();
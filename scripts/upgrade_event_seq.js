@@ -0,0 +1,115 @@
+// scripts/upgrade_event_seq.js
+// ONE TIME USE (idempotent) — grows LevelPool 1..16 and StatsShard 0..15 created before
+// event_seq (8 bytes each, payer funds the rent top-up). Optional: activations / sign-ups
+// grow the pool / shard they touch on their own; this just spares players the top-up.
+//
+// Usage:
+//   RPC_URL=<url> PROGRAM_ID=<id> node scripts/upgrade_event_seq.js [payer_keypair.json]
+
+const fs = require("fs");
+const anchor = require("@coral-xyz/anchor");
+const { PublicKey, SystemProgram, Transaction, TransactionInstruction } = anchor.web3;
+
+// --------------------
+// RPC
+// --------------------
+const RPC_URL = process.env.RPC_URL || "https://api.devnet.solana.com";
+const connection = new anchor.web3.Connection(RPC_URL, { commitment: "confirmed" });
+
+// --------------------
+// PAYER (any wallet — the instructions are permissionless)
+// --------------------
+const KEYPAIR_PATH = process.argv[2] || "wallets/mainnet-final/solana_game_mainnet.json";
+const secretKey = JSON.parse(fs.readFileSync(KEYPAIR_PATH, "utf8"));
+const payerKeypair = anchor.web3.Keypair.fromSecretKey(Uint8Array.from(secretKey));
+const wallet = new anchor.Wallet(payerKeypair);
+const provider = new anchor.AnchorProvider(connection, wallet, { commitment: "confirmed" });
+anchor.setProvider(provider);
+
+// --------------------
+// PROGRAM (ON-CHAIN)
+// --------------------
+const PROGRAM_ID = new PublicKey(
+  process.env.PROGRAM_ID || "Bk5wQdDbfe2UGrrjBsDUJFPjH9mqB5JHZymrp4u95458"
+);
+
+const MAX_LEVEL = 16;
+const STATS_SHARD_COUNT = 16;
+
+// 8 (discriminator) + LevelPool::SIZE / StatsShard::SIZE
+const LEVEL_POOL_SPACE = 8 + 32 + 1 + 1 + 33 + 33 + 8 + 8 + 8;
+const STATS_SHARD_SPACE = 8 + 1 + 8 + 32 + 1 + 8;
+
+// --------------------
+// PDA
+// --------------------
+const [configPda] = PublicKey.findProgramAddressSync(
+  [Buffer.from("config_v3_new")],
+  PROGRAM_ID
+);
+const levelPoolPda = (level) =>
+  PublicKey.findProgramAddressSync(
+    [Buffer.from("level_pool_v1"), configPda.toBuffer(), Buffer.from([level])],
+    PROGRAM_ID
+  )[0];
+const shardPda = (shard) =>
+  PublicKey.findProgramAddressSync(
+    [Buffer.from("stats_shard_v1"), Buffer.from([shard])],
+    PROGRAM_ID
+  )[0];
+
+// --------------------
+// DISCRIMINATORS
+// --------------------
+const DISCR_UPGRADE_LEVEL_POOL = Buffer.from([198, 165, 12, 236, 16, 53, 222, 70]);
+const DISCR_UPGRADE_STATS_SHARD = Buffer.from([89, 24, 208, 61, 243, 244, 176, 218]);
+
+// Falsy when the account is missing or already at the current size
+async function needsUpgrade(pda, space) {
+  const acc = await connection.getAccountInfo(pda);
+  return acc && acc.data.length < space;
+}
+
+// --------------------
+// TX
+// --------------------
+(async () => {
+  for (let level = 1; level <= MAX_LEVEL; level++) {
+    const pda = levelPoolPda(level);
+    if (!(await needsUpgrade(pda, LEVEL_POOL_SPACE))) {
+      console.log(`= level ${level} pool missing or current: ${pda.toBase58()}`);
+      continue;
+    }
+    const ix = new TransactionInstruction({
+      programId: PROGRAM_ID,
+      keys: [
+        { pubkey: pda, isWritable: true, isSigner: false },
+        { pubkey: configPda, isWritable: false, isSigner: false },
+        { pubkey: wallet.publicKey, isWritable: true, isSigner: true },
+        { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+      ],
+      data: Buffer.concat([DISCR_UPGRADE_LEVEL_POOL, Buffer.from([level])]),
+    });
+    const sig = await provider.sendAndConfirm(new Transaction().add(ix));
+    console.log(`✅ level ${level} pool upgraded: ${pda.toBase58()} (${sig})`);
+  }
+
+  for (let shard = 0; shard < STATS_SHARD_COUNT; shard++) {
+    const pda = shardPda(shard);
+    if (!(await needsUpgrade(pda, STATS_SHARD_SPACE))) {
+      console.log(`= shard ${shard} missing or current: ${pda.toBase58()}`);
+      continue;
+    }
+    const ix = new TransactionInstruction({
+      programId: PROGRAM_ID,
+      keys: [
+        { pubkey: pda, isWritable: true, isSigner: false },
+        { pubkey: wallet.publicKey, isWritable: true, isSigner: true },
+        { pubkey: SystemProgram.programId, isWritable: false, isSigner: false },
+      ],
+      data: Buffer.concat([DISCR_UPGRADE_STATS_SHARD, Buffer.from([shard])]),
+    });
+    const sig = await provider.sendAndConfirm(new Transaction().add(ix));
+    console.log(`✅ shard ${shard} upgraded: ${pda.toBase58()} (${sig})`);
+  }
+})();